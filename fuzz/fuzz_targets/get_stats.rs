@@ -0,0 +1,42 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasm_stats::{get_stats_with, Options};
+
+fuzz_target!(|wasm: &[u8]| {
+    let mut options = Options::default();
+    options.scan_data = Some(4096);
+    options.top_functions = 10;
+    options.opcodes = true;
+    options.import_names = true;
+    options.validate = true;
+    // Errors are expected; only panics are bugs.
+    let _ = get_stats_with(wasm, &options);
+    // Also read the same sections as the other kind of binary, so that
+    // inputs found for core modules exercise the component path and the
+    // other way around.
+    if wasm.len() >= 8 {
+        let mut swapped = wasm.to_vec();
+        swapped[4..8].copy_from_slice(if wasm[6..8] == [1, 0] {
+            &[1, 0, 0, 0]
+        } else {
+            &[0x0d, 0, 1, 0]
+        });
+        let _ = get_stats_with(&swapped, &options);
+    }
+});
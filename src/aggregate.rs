@@ -0,0 +1,171 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Summary of a whole corpus for `--aggregate`, in place of per-module
+//! records.
+use crate::output::fields;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use wasm_stats::Stats;
+
+/// Sum and mean of one `size` field, over the modules that have it.
+#[derive(Debug, Default, Serialize)]
+pub struct Total {
+    pub modules: usize,
+    pub sum: u64,
+    pub mean: f64,
+}
+
+/// Modules whose `size.total` is at least `min` bytes but less than twice
+/// that; the first bucket also holds empty modules.
+#[derive(Debug, Serialize)]
+pub struct Bucket {
+    pub min: u64,
+    pub modules: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Aggregate {
+    /// Modules that were analysed successfully.
+    pub modules: usize,
+    /// Inputs that couldn't be read or analysed.
+    pub failed: usize,
+    pub languages: BTreeMap<String, usize>,
+    /// Totals for each field of `size`, in the same order.
+    pub size: Fields<Total>,
+    /// Number of modules using each proposal at all.
+    pub proposals: Fields<usize>,
+    /// Histogram of `size.total` in power-of-two buckets, smallest first.
+    #[serde(serialize_with = "serialize_buckets")]
+    pub size_histogram: BTreeMap<u32, usize>,
+}
+
+/// Values keyed by the name of the `Stats` field they summarise, kept in
+/// field order and serialized as a map.
+#[derive(Debug)]
+pub struct Fields<T>(pub Vec<(String, T)>);
+
+impl<T> Default for Fields<T> {
+    fn default() -> Self {
+        Fields(Vec::new())
+    }
+}
+
+impl<T: Default> Fields<T> {
+    /// Finds the value for `name`, adding it at the end if it isn't there yet.
+    fn entry(&mut self, name: &str) -> &mut T {
+        let i = match self.0.iter().position(|(n, _)| n == name) {
+            Some(i) => i,
+            None => {
+                self.0.push((name.to_owned(), T::default()));
+                self.0.len() - 1
+            }
+        };
+        &mut self.0[i].1
+    }
+}
+
+impl<T: Serialize> Serialize for Fields<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(name, value)| (name, value)))
+    }
+}
+
+impl Aggregate {
+    pub fn add(&mut self, stats: &Stats) {
+        self.modules += 1;
+        let language = serde_json::to_value(stats.language).unwrap_or_default();
+        *self
+            .languages
+            .entry(language.as_str().unwrap_or_default().to_owned())
+            .or_default() += 1;
+        for (name, value) in fields(&stats.size) {
+            let total = self.size.entry(&name);
+            if let Some(value) = value.as_u64() {
+                total.modules += 1;
+                total.sum += value;
+            }
+        }
+        for (name, value) in fields(&stats.instr.proposals) {
+            let adopted = self.proposals.entry(&name);
+            if value.as_u64().unwrap_or(0) > 0 {
+                *adopted += 1;
+            }
+        }
+        let bucket = match stats.size.total {
+            0 => 0,
+            size => usize::BITS - 1 - size.leading_zeros(),
+        };
+        *self.size_histogram.entry(bucket).or_default() += 1;
+    }
+
+    /// Fills in the means once every module has been added.
+    pub fn finish(mut self, failed: usize) -> Self {
+        self.failed = failed;
+        for (_, total) in &mut self.size.0 {
+            if total.modules > 0 {
+                total.mean = total.sum as f64 / total.modules as f64;
+            }
+        }
+        self
+    }
+}
+
+fn serialize_buckets<S: serde::Serializer>(
+    buckets: &BTreeMap<u32, usize>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(buckets.iter().map(|(&bucket, &modules)| Bucket {
+        min: 1u64 << bucket,
+        modules,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn languages_and_adoption() -> Result<()> {
+        let mut aggregate = Aggregate::default();
+        for wat in [
+            r#"(module (import "go" "debug" (func)))"#,
+            r#"(module (import "go" "debug" (func)) (func i32.const 0 i32.extend8_s drop))"#,
+            "(module (func v128.const i64x2 0 0 drop))",
+        ] {
+            aggregate.add(&wasm_stats::get_stats(&wat::parse_str(wat)?)?);
+        }
+        let aggregate = aggregate.finish(1);
+        let value = serde_json::to_value(&aggregate)?;
+        assert_eq!(value["modules"], 3);
+        assert_eq!(value["failed"], 1);
+        assert_eq!(
+            value["languages"],
+            serde_json::json!({ "Go": 2, "Unknown": 1 })
+        );
+        assert_eq!(value["proposals"]["simd"], 1);
+        assert_eq!(value["proposals"]["sign_extend"], 1);
+        assert_eq!(value["proposals"]["atomics"], 0);
+        assert_eq!(value["size"]["compressed"]["modules"], 0);
+        let histogram = value["size_histogram"].as_array().unwrap();
+        let modules: u64 = histogram
+            .iter()
+            .map(|b| b["modules"].as_u64().unwrap())
+            .sum();
+        assert_eq!(modules, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Files embedded in data segments, such as fonts, images and nested
+//! modules, found by their magic numbers.
+use crate::{sections, AssetFormat, EmbeddedAsset};
+use std::io::Read;
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_MAGIC: &[u8] = b"\xff\xd8\xff";
+const JPEG_END: &[u8] = b"\xff\xd9";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b\x08";
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Stops inflating zlib and gzip streams after this much output, which is
+/// enough to tell a real stream from bytes that happen to look like one.
+const MAX_INFLATED: u64 = 1 << 20;
+
+/// Looks for assets starting in the first `limit` bytes of a data segment,
+/// adding them to `assets`. An asset may run past `limit`, but not past the
+/// end of the segment.
+pub fn scan(segment: usize, data: &[u8], limit: usize, assets: &mut Vec<EmbeddedAsset>) {
+    let mut offset = 0;
+    while offset < data.len().min(limit) {
+        match identify(&data[offset..]) {
+            Some((format, size)) => {
+                assets.push(EmbeddedAsset {
+                    format,
+                    segment,
+                    offset,
+                    approx_size: size,
+                });
+                offset += size.max(1);
+            }
+            None => offset += 1,
+        }
+    }
+}
+
+/// The format and approximate size of an asset at the start of `bytes`.
+fn identify(bytes: &[u8]) -> Option<(AssetFormat, usize)> {
+    if bytes.starts_with(PNG_MAGIC) {
+        Some((AssetFormat::Png, png_size(bytes)))
+    } else if bytes.starts_with(JPEG_MAGIC) {
+        // The end marker can also turn up inside an embedded thumbnail, so
+        // this may fall short.
+        let end = find(&bytes[JPEG_MAGIC.len()..], JPEG_END)
+            .map_or(bytes.len(), |end| end + JPEG_MAGIC.len() + JPEG_END.len());
+        Some((AssetFormat::Jpeg, end))
+    } else if bytes.starts_with(b"wOFF") {
+        Some((AssetFormat::Woff, woff_size(bytes, 44)?))
+    } else if bytes.starts_with(b"wOF2") {
+        Some((AssetFormat::Woff2, woff_size(bytes, 48)?))
+    } else if bytes.starts_with(GZIP_MAGIC) {
+        Some((AssetFormat::Gzip, inflated_size(bytes, AssetFormat::Gzip)?))
+    } else if is_zlib_header(bytes) {
+        Some((AssetFormat::Zlib, inflated_size(bytes, AssetFormat::Zlib)?))
+    } else if bytes.starts_with(WASM_MAGIC) && bytes.len() >= sections::HEADER_SIZE {
+        // Sections are read up to the first one that doesn't fit, so
+        // trailing bytes that aren't part of the module are left out.
+        let (found, _) = sections::split(bytes);
+        let end = found.last().map_or(sections::HEADER_SIZE, |section| {
+            section.payload_offset + section.payload.len()
+        });
+        Some((AssetFormat::Wasm, end))
+    } else {
+        None
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn read_u32_be(bytes: &[u8], at: usize) -> Option<usize> {
+    let word = bytes.get(at..at + 4)?;
+    Some(u32::from_be_bytes([word[0], word[1], word[2], word[3]]) as usize)
+}
+
+/// Follows the PNG chunks up to and including `IEND`, or for as long as
+/// they fit.
+fn png_size(bytes: &[u8]) -> usize {
+    let mut end = PNG_MAGIC.len();
+    // Each chunk has a length, a type and a CRC around its data.
+    while let Some(len) = read_u32_be(bytes, end) {
+        let next = match end.checked_add(len + 12) {
+            Some(next) if next <= bytes.len() => next,
+            _ => break,
+        };
+        let is_end = &bytes[end + 4..end + 8] == b"IEND";
+        end = next;
+        if is_end {
+            break;
+        }
+    }
+    end
+}
+
+/// The length from a WOFF or WOFF2 header, which must be at least
+/// `header_size`.
+fn woff_size(bytes: &[u8], header_size: usize) -> Option<usize> {
+    let len = read_u32_be(bytes, 8)?;
+    (len >= header_size).then(|| len.min(bytes.len()))
+}
+
+/// A zlib header with the deflate method, a window size deflate accepts and
+/// a valid check value.
+fn is_zlib_header(bytes: &[u8]) -> bool {
+    match bytes {
+        [cmf, flg, ..] => {
+            cmf & 0x0f == 8 && cmf >> 4 <= 7 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0
+        }
+        _ => false,
+    }
+}
+
+/// How many bytes of a gzip or zlib stream at the start of `bytes` are read
+/// before it ends or `MAX_INFLATED` bytes come out, or `None` if it isn't a
+/// valid stream.
+fn inflated_size(bytes: &[u8], format: AssetFormat) -> Option<usize> {
+    fn drain(reader: impl Read) -> std::io::Result<u64> {
+        std::io::copy(&mut reader.take(MAX_INFLATED), &mut std::io::sink())
+    }
+    // The decoders only consume what they need from a `BufRead`, so what's
+    // left afterwards is what follows the stream.
+    let mut rest = bytes;
+    match format {
+        AssetFormat::Gzip => drain(flate2::bufread::GzDecoder::new(&mut rest)),
+        _ => drain(flate2::bufread::ZlibDecoder::new(&mut rest)),
+    }
+    .ok()?;
+    Some(bytes.len() - rest.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    fn scan_all(data: &[u8]) -> Vec<(AssetFormat, usize, usize)> {
+        let mut assets = Vec::new();
+        scan(0, data, data.len(), &mut assets);
+        assets
+            .into_iter()
+            .map(|asset| (asset.format, asset.offset, asset.approx_size))
+            .collect()
+    }
+
+    #[test]
+    fn compressed_streams() -> Result<(), std::io::Error> {
+        let text = "wasm-stats ".repeat(100);
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(text.as_bytes())?;
+        let gzip = gzip.finish()?;
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(text.as_bytes())?;
+        let zlib = zlib.finish()?;
+
+        let mut data = vec![0; 3];
+        data.extend(&gzip);
+        data.extend(&zlib);
+        data.extend([0; 5]);
+        assert_eq!(
+            scan_all(&data),
+            [
+                (AssetFormat::Gzip, 3, gzip.len()),
+                (AssetFormat::Zlib, 3 + gzip.len(), zlib.len()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lookalikes() {
+        // A zlib header with nothing valid after it, a WOFF header that's too
+        // short, and a JPEG with no end marker.
+        let data = b"\x78\x9c\xff\xff\xff\xff wOFF\0\0\0\0\0\0\0\x04 \xff\xd8\xff\xe0";
+        assert_eq!(scan_all(data), [(AssetFormat::Jpeg, 20, 4)]);
+    }
+}
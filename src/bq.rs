@@ -0,0 +1,279 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! The flat column layout of the almanac's BigQuery tables, where nested
+//! fields become `parent_child` columns, e.g. `instr_proposals_simd`.
+use serde_json::{json, Map, Value};
+
+/// Column names may only contain letters, digits and underscores.
+fn column(prefix: &str, key: &str) -> String {
+    let key = key.replace('-', "_");
+    if prefix.is_empty() {
+        key
+    } else {
+        format!("{}_{}", prefix, key)
+    }
+}
+
+/// Flattens a record into a row of the BigQuery table, guided by the JSON
+/// Schema of the records.
+///
+/// Arrays stay as repeated columns, with any objects in them flattened in
+/// turn, and maps with arbitrary keys become repeated `{key, value}` records.
+/// Nulls are left out, which BigQuery loads as `NULL`, so that missing nested
+/// records don't produce a column of their own.
+pub fn flatten_row(record: &Value, schema: &Value) -> Value {
+    let mut row = Map::new();
+    flatten_into("", record, schema, &schema["$defs"], &mut row);
+    Value::Object(row)
+}
+
+fn flatten_into(
+    prefix: &str,
+    value: &Value,
+    schema: &Value,
+    defs: &Value,
+    row: &mut Map<String, Value>,
+) {
+    let (schema, _) = resolve(schema, defs);
+    match value {
+        Value::Null => {}
+        Value::Object(map) if is_map(schema) => {
+            let entries = map
+                .iter()
+                .map(|(key, value)| {
+                    let mut entry = Map::new();
+                    entry.insert("key".to_owned(), Value::String(key.clone()));
+                    flatten_into(
+                        "value",
+                        value,
+                        &schema["additionalProperties"],
+                        defs,
+                        &mut entry,
+                    );
+                    Value::Object(entry)
+                })
+                .collect();
+            row.insert(prefix.to_owned(), Value::Array(entries));
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                let schema = &schema["properties"][key];
+                flatten_into(&column(prefix, key), value, schema, defs, row);
+            }
+        }
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| match item {
+                    Value::Object(_) => {
+                        let mut nested = Map::new();
+                        flatten_into("", item, &schema["items"], defs, &mut nested);
+                        Value::Object(nested)
+                    }
+                    _ => item.clone(),
+                })
+                .collect();
+            row.insert(prefix.to_owned(), Value::Array(items));
+        }
+        _ => {
+            row.insert(prefix.to_owned(), value.clone());
+        }
+    }
+}
+
+/// Follows `$ref`s and unwraps `Option`s, returning the underlying schema
+/// and whether it can be null.
+pub fn resolve<'a>(schema: &'a Value, defs: &'a Value) -> (&'a Value, bool) {
+    if let Some(name) = schema["$ref"]
+        .as_str()
+        .and_then(|r| r.strip_prefix("#/$defs/"))
+    {
+        return resolve(&defs[name], defs);
+    }
+    if let Some(variants) = schema["anyOf"].as_array() {
+        let not_null: Vec<_> = variants.iter().filter(|v| v["type"] != "null").collect();
+        if let [inner] = not_null[..] {
+            let (inner, _) = resolve(inner, defs);
+            return (inner, not_null.len() < variants.len());
+        }
+    }
+    match schema["type"].as_array() {
+        Some(types) => (schema, types.iter().any(|t| t == "null")),
+        None => (schema, false),
+    }
+}
+
+/// Whether the schema is for a map with arbitrary keys, rather than a struct.
+pub fn is_map(schema: &Value) -> bool {
+    schema["type"] == "object" && !schema["properties"].is_object()
+}
+
+/// Maps a scalar JSON Schema type to a BigQuery column type.
+fn scalar_type(schema: &Value) -> &'static str {
+    let ty = match &schema["type"] {
+        Value::Array(types) => types.iter().find(|t| *t != "null").unwrap_or(&Value::Null),
+        ty => ty,
+    };
+    match ty.as_str() {
+        Some("integer") => "INTEGER",
+        Some("number") => "FLOAT",
+        Some("boolean") => "BOOLEAN",
+        // Plain strings, and enums whose variants are documented one by one.
+        _ => "STRING",
+    }
+}
+
+fn table_fields(
+    prefix: &str,
+    schema: &Value,
+    defs: &Value,
+    required: bool,
+    fields: &mut Vec<Value>,
+) {
+    let (schema, nullable) = resolve(schema, defs);
+    let mode = if required && !nullable {
+        "REQUIRED"
+    } else {
+        "NULLABLE"
+    };
+    if let Some(properties) = schema["properties"].as_object() {
+        let required_keys = schema["required"].as_array();
+        for (key, property) in properties {
+            let required = mode == "REQUIRED"
+                && required_keys.is_some_and(|keys| keys.iter().any(|k| k == key));
+            table_fields(&column(prefix, key), property, defs, required, fields);
+        }
+    } else if is_map(schema) {
+        let mut nested = vec![json!({ "name": "key", "type": "STRING", "mode": "REQUIRED" })];
+        table_fields(
+            "value",
+            &schema["additionalProperties"],
+            defs,
+            true,
+            &mut nested,
+        );
+        fields.push(
+            json!({ "name": prefix, "type": "RECORD", "mode": "REPEATED", "fields": nested }),
+        );
+    } else if schema["type"] == "array" {
+        let (items, _) = resolve(&schema["items"], defs);
+        let field = if items["properties"].is_object() {
+            let mut nested = Vec::new();
+            table_fields("", items, defs, true, &mut nested);
+            json!({ "name": prefix, "type": "RECORD", "mode": "REPEATED", "fields": nested })
+        } else {
+            json!({ "name": prefix, "type": scalar_type(items), "mode": "REPEATED" })
+        };
+        fields.push(field);
+    } else {
+        fields.push(json!({ "name": prefix, "type": scalar_type(schema), "mode": mode }));
+    }
+}
+
+/// Converts the JSON Schema of the records into a BigQuery table schema
+/// matching the rows from [`flatten_row`].
+pub fn table_schema(schema: &Value) -> Value {
+    let mut fields = Vec::new();
+    table_fields("", schema, &schema["$defs"], true, &mut fields);
+    Value::Array(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatten_row_columns() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "import_modules": {
+                    "type": "object",
+                    "additionalProperties": { "type": "integer" },
+                },
+            },
+        });
+        let row = flatten_row(
+            &json!({
+                "file": "a.wasm",
+                "instr": { "proposals": { "simd": 3 } },
+                "producers": null,
+                "custom_sections": [{ "name": "name", "size": 4 }],
+                "target_features": ["simd128"],
+                "import_modules": { "env": 2, "wbg": 1 },
+            }),
+            &schema,
+        );
+        assert_eq!(
+            row,
+            json!({
+                "file": "a.wasm",
+                "instr_proposals_simd": 3,
+                "custom_sections": [{ "name": "name", "size": 4 }],
+                "target_features": ["simd128"],
+                "import_modules": [{ "key": "env", "value": 2 }, { "key": "wbg", "value": 1 }],
+            })
+        );
+    }
+
+    #[test]
+    fn table_schema_modes() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "size": { "$ref": "#/$defs/Size" },
+                "producers": { "anyOf": [{ "$ref": "#/$defs/Producers" }, { "type": "null" }] },
+                "wasi": { "type": ["string", "null"] },
+            },
+            "required": ["size"],
+            "$defs": {
+                "Size": {
+                    "type": "object",
+                    "properties": { "code": { "type": "integer" } },
+                    "required": ["code"],
+                },
+                "Producers": {
+                    "type": "object",
+                    "properties": {
+                        "processed-by": {
+                            "type": "array",
+                            "items": { "$ref": "#/$defs/Tool" },
+                        },
+                    },
+                    "required": ["processed-by"],
+                },
+                "Tool": {
+                    "type": "object",
+                    "properties": { "name": { "type": "string" } },
+                    "required": ["name"],
+                },
+            },
+        });
+        assert_eq!(
+            table_schema(&schema),
+            json!([
+                { "name": "size_code", "type": "INTEGER", "mode": "REQUIRED" },
+                {
+                    "name": "producers_processed_by",
+                    "type": "RECORD",
+                    "mode": "REPEATED",
+                    "fields": [{ "name": "name", "type": "STRING", "mode": "REQUIRED" }],
+                },
+                { "name": "wasi", "type": "STRING", "mode": "NULLABLE" },
+            ])
+        );
+    }
+}
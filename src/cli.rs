@@ -0,0 +1,383 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::{
+    compression::Encoding,
+    output::{FlatArrays, OutputFormat},
+};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use std::{ffi::OsString, num::NonZeroUsize, path::PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorRecords {
+    /// Only when analysing more than one input.
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShowProgress {
+    /// Only when stderr is a terminal and there's more than one input.
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print the JSON Schema of the output records.
+    Schema {
+        /// Print the BigQuery table schema for `--bq` rows instead.
+        #[arg(long)]
+        bq: bool,
+    },
+    /// Compare the stats of two modules, printing only what changed.
+    Diff { a: PathBuf, b: PathBuf },
+}
+
+/// Collects statistics about WebAssembly modules for the Web Almanac.
+#[derive(Debug, Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Wasm files or directories to analyse; reads stdin when empty or `-`.
+    /// Builds with the `fetch` feature also take `http://` and `https://`
+    /// URLs, which are downloaded with `curl`, so it needs to be on the PATH.
+    pub paths: Vec<PathBuf>,
+
+    /// File extension to look for when walking directories (repeatable).
+    #[arg(long = "ext", value_name = "EXT", default_value = "wasm", value_parser = parse_ext)]
+    pub exts: Vec<OsString>,
+
+    /// Decompress inputs with this encoding. By default gzip is detected
+    /// automatically, and Brotli is assumed for `.br` files that aren't wasm.
+    #[arg(long, value_enum, value_name = "ENCODING")]
+    pub decompress: Option<Encoding>,
+
+    /// Write records to this file instead of stdout (truncated unless --append).
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Append to the --output file instead of truncating it, for resumable runs.
+    #[arg(long, requires = "output")]
+    pub append: bool,
+
+    /// Format of the emitted records.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub output_format: OutputFormat,
+
+    /// Emit a `{"file", "error", "size"}` record for inputs that fail, in
+    /// addition to reporting them on stderr.
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ErrorRecords::Auto)]
+    pub error_records: ErrorRecords,
+
+    /// Show a progress bar on stderr while analysing.
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ShowProgress::Auto)]
+    pub progress: ShowProgress,
+
+    /// Write `{processed, succeeded, failed, skipped, wall_time_ms}` counts
+    /// for the run to this file as JSON.
+    #[arg(long, value_name = "PATH")]
+    pub summary_json: Option<PathBuf>,
+
+    /// Only emit these comma-separated fields of each record, e.g.
+    /// `size,instr.proposals.simd`; `file` is always kept.
+    #[arg(
+        long,
+        value_name = "PATHS",
+        value_delimiter = ',',
+        value_parser = parse_field,
+        conflicts_with = "aggregate"
+    )]
+    pub fields: Option<Vec<Vec<String>>>,
+
+    /// Emit JSON rows flattened into the columns of the BigQuery tables, e.g.
+    /// `size_code`, as described by `wasm-stats schema --bq`.
+    #[arg(long, conflicts_with = "output_format")]
+    pub bq: bool,
+
+    /// Emit single-level JSON objects with underscore-joined keys, e.g.
+    /// `instr_categories_load_store`.
+    #[arg(long, conflicts_with_all = ["output_format", "bq"])]
+    pub flatten: bool,
+
+    /// How --flatten writes arrays and maps such as `custom_sections`.
+    #[arg(long, value_enum, value_name = "AS", default_value_t = FlatArrays::Json, requires = "flatten")]
+    pub flatten_arrays: FlatArrays,
+
+    /// Pretty-print JSON records instead of emitting one per line.
+    #[arg(long)]
+    pub pretty: bool,
+
+    /// Search data segments for strings that identify the source language,
+    /// and report how much of them is text.
+    #[arg(long)]
+    pub scan_data: bool,
+
+    /// Only search this many bytes at the start of each data segment.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = 65536,
+        requires = "scan_data"
+    )]
+    pub scan_data_limit: usize,
+
+    /// Look for files embedded in data segments, such as images, fonts,
+    /// compressed streams and nested modules.
+    #[arg(long)]
+    pub data_contents: bool,
+
+    /// Only look for files that start in this many bytes at the start of
+    /// each data segment.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = 1 << 20,
+        requires = "data_contents"
+    )]
+    pub data_contents_limit: usize,
+
+    /// Also report the N largest functions, with their names where known.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub top_functions: usize,
+
+    /// Also list the module and name of every import.
+    #[arg(long)]
+    pub import_names: bool,
+
+    /// List at most this many export names in each record.
+    #[arg(long, value_name = "N", default_value_t = wasm_stats::DEFAULT_EXPORT_NAMES)]
+    pub export_names: usize,
+
+    /// Also validate each module, which roughly doubles the processing time.
+    #[arg(long)]
+    pub validate: bool,
+
+    /// Add a `timings` object to each record with the microseconds spent in
+    /// each stage of the analysis.
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Add `size.gzip` and `size.brotli`, the size of each module compressed
+    /// at the highest settings. This is slow: Brotli especially.
+    #[arg(long)]
+    pub estimate_compression: bool,
+
+    /// The Brotli quality for --estimate-compression, from 0 to 11; lower is
+    /// faster.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = crate::compression::MAX_BROTLI_QUALITY,
+        value_parser = clap::value_parser!(u32).range(0..=crate::compression::MAX_BROTLI_QUALITY as i64),
+        requires = "estimate_compression"
+    )]
+    pub brotli_quality: u32,
+
+    /// Also count every instruction by mnemonic (JSON output only).
+    #[arg(long)]
+    pub opcodes: bool,
+
+    /// Emit a single summary of all the inputs instead of a record for each:
+    /// language counts, size totals, proposal adoption and a size histogram.
+    #[arg(long)]
+    pub aggregate: bool,
+
+    /// Skip inputs larger than this, before or after decompression, with a
+    /// "skipped" error. No limit by default.
+    #[arg(long, value_name = "BYTES")]
+    pub max_size: Option<u64>,
+
+    /// Give up on inputs still being analysed after this many seconds, with a
+    /// "skipped" error. No limit by default.
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub timeout: Option<u64>,
+
+    /// Analyse up to N inputs at once; defaults to the number of CPUs.
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+}
+
+/// Normalises an `--ext` value so that both `wasm` and `.wasm` are accepted.
+fn parse_ext(ext: &str) -> Result<OsString, String> {
+    Ok(OsString::from(ext.strip_prefix('.').unwrap_or(ext)))
+}
+
+/// Checks a `--fields` path against the schema of the records.
+fn parse_field(path: &str) -> Result<Vec<String>, String> {
+    crate::select::parse_path(path, &crate::record_schema()).map_err(|e| e.to_string())
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        Self::try_parse_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_parse_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut args = <Self as Parser>::try_parse_from(args)?;
+        if args.bq {
+            args.output_format = OutputFormat::Bq;
+        }
+        if args.flatten {
+            args.output_format = OutputFormat::Flat;
+        }
+        args.validate()?;
+        Ok(args)
+    }
+
+    /// Checks combinations of flags that clap can't express declaratively.
+    fn validate(&self) -> Result<(), clap::Error> {
+        if self.pretty && self.output_format != OutputFormat::Json {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "--pretty can only be used with JSON output",
+            ));
+        }
+        // Each module has its own set of opcodes, which won't fit in the
+        // columns taken from the first record.
+        if self.opcodes && self.output_format != OutputFormat::Json {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "--opcodes can only be used with JSON output",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_to_stdin() {
+        let args = Args::try_parse_from(["wasm-stats"]).unwrap();
+        assert!(args.paths.is_empty());
+        assert_eq!(args.exts, [OsString::from("wasm")]);
+        assert_eq!(args.output_format, OutputFormat::Json);
+        assert!(!args.pretty);
+        assert_eq!(args.error_records, ErrorRecords::Auto);
+        assert!(!args.scan_data);
+    }
+
+    #[test]
+    fn missing_option_value() {
+        let err = Args::try_parse_from(["wasm-stats", "dir", "--ext"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn unknown_flag() {
+        let err = Args::try_parse_from(["wasm-stats", "--bogus", "a.wasm"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+    }
+
+    #[test]
+    fn fields() {
+        let args =
+            Args::try_parse_from(["wasm-stats", "--fields", "size,instr.proposals.simd"]).unwrap();
+        assert_eq!(
+            args.fields.unwrap(),
+            [vec!["size"], vec!["instr", "proposals", "simd"]]
+        );
+        let err = Args::try_parse_from(["wasm-stats", "--fields", "size,instr.bogus"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn conflicting_flags() {
+        let err =
+            Args::try_parse_from(["wasm-stats", "--pretty", "--output-format", "csv"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        let err = Args::try_parse_from(["wasm-stats", "--opcodes", "--output-format", "csv"])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn append_requires_output() {
+        let err = Args::try_parse_from(["wasm-stats", "--append", "a.wasm"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+        let args = Args::try_parse_from(["wasm-stats", "-o", "out.ndjson", "--append"]).unwrap();
+        assert_eq!(args.output, Some(PathBuf::from("out.ndjson")));
+        assert!(args.append);
+    }
+
+    #[test]
+    fn scan_data_limit() {
+        let args = Args::try_parse_from(["wasm-stats", "--scan-data", "a.wasm"]).unwrap();
+        assert!(args.scan_data);
+        assert_eq!(args.scan_data_limit, 65536);
+        let args = Args::try_parse_from(["wasm-stats", "--scan-data", "--scan-data-limit", "100"])
+            .unwrap();
+        assert_eq!(args.scan_data_limit, 100);
+        let err = Args::try_parse_from(["wasm-stats", "--scan-data-limit", "100"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn jobs() {
+        let args = Args::try_parse_from(["wasm-stats", "-j", "4"]).unwrap();
+        assert_eq!(args.jobs, NonZeroUsize::new(4));
+        let err = Args::try_parse_from(["wasm-stats", "--jobs", "0"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn bq() {
+        let args = Args::try_parse_from(["wasm-stats", "--bq", "a.wasm"]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Bq);
+        let err =
+            Args::try_parse_from(["wasm-stats", "--bq", "--output-format", "csv"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        let err = Args::try_parse_from(["wasm-stats", "--bq", "--opcodes"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        let args = Args::try_parse_from(["wasm-stats", "schema", "--bq"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Schema { bq: true })));
+    }
+
+    #[test]
+    fn flatten() {
+        let args = Args::try_parse_from(["wasm-stats", "--flatten", "a.wasm"]).unwrap();
+        assert_eq!(args.output_format, OutputFormat::Flat);
+        assert_eq!(args.flatten_arrays, FlatArrays::Json);
+        let err = Args::try_parse_from(["wasm-stats", "--flatten", "--bq"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        let err = Args::try_parse_from(["wasm-stats", "--flatten-arrays", "count"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn diff() {
+        let args = Args::try_parse_from(["wasm-stats", "diff", "a.wasm", "b.wasm"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Diff { .. })));
+        let err = Args::try_parse_from(["wasm-stats", "diff", "a.wasm"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn ext_normalisation() {
+        let args = Args::try_parse_from(["wasm-stats", "--ext", ".bin", "--ext", "wasm"]).unwrap();
+        assert_eq!(args.exts, [OsString::from("bin"), OsString::from("wasm")]);
+    }
+}
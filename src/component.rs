@@ -0,0 +1,104 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Outline stats for component-model binaries, which only walk the outer
+//! section structure rather than analysing the core modules inside.
+use crate::{sections, BinaryKind, ComponentSection, CustomSection, Stats, SCHEMA_VERSION};
+
+const CUSTOM: u8 = 0;
+const CORE_MODULE: u8 = 1;
+const COMPONENT: u8 = 4;
+
+/// Whether the header has the component layer set in its version word.
+pub fn is_component(wasm: &[u8]) -> bool {
+    wasm.len() >= sections::HEADER_SIZE && wasm[..4] == *b"\0asm" && wasm[6..8] == [1, 0]
+}
+
+fn kind_name(id: u8) -> String {
+    let name = match id {
+        CUSTOM => "custom",
+        CORE_MODULE => "core_module",
+        2 => "core_instance",
+        3 => "core_type",
+        COMPONENT => "component",
+        5 => "instance",
+        6 => "alias",
+        7 => "type",
+        8 => "canon",
+        9 => "start",
+        10 => "import",
+        11 => "export",
+        12 => "value",
+        _ => return format!("unknown({})", id),
+    };
+    name.to_owned()
+}
+
+/// Counts the core modules in a component, including nested components.
+/// Nested components are walked with a work list rather than recursion, so
+/// that deeply nested input can't overflow the stack.
+fn count_core_modules(wasm: &[u8]) -> usize {
+    let mut count = 0;
+    let mut pending = vec![wasm];
+    while let Some(component) = pending.pop() {
+        for raw in sections::split(component).0 {
+            match raw.id {
+                CORE_MODULE => count += 1,
+                COMPONENT if is_component(raw.payload) => pending.push(raw.payload),
+                _ => {}
+            }
+        }
+    }
+    count
+}
+
+pub fn get_stats(wasm: &[u8]) -> Stats {
+    let mut stats = Stats {
+        schema_version: SCHEMA_VERSION,
+        binary_kind: BinaryKind::Component,
+        wasm_version: sections::version(wasm).unwrap_or_default(),
+        ..Default::default()
+    };
+    stats.size.total = wasm.len();
+    let (raw_sections, truncated) = sections::split_with(wasm, kind_name);
+    for raw in &raw_sections {
+        match raw.id {
+            CUSTOM => match raw.reader().read_string() {
+                Ok(name) => {
+                    stats.size.custom += raw.size();
+                    stats.custom_sections.push(CustomSection {
+                        name: name.to_owned(),
+                        size: raw.size(),
+                    });
+                }
+                Err(e) => stats
+                    .decode_errors
+                    .push(sections::error_with(kind_name, raw.id, raw.offset, e)),
+            },
+            CORE_MODULE => stats.component.core_modules += 1,
+            COMPONENT if is_component(raw.payload) => {
+                stats.component.core_modules += count_core_modules(raw.payload);
+            }
+            _ => {}
+        }
+        stats.component.sections.push(ComponentSection {
+            kind: kind_name(raw.id),
+            size: raw.size(),
+        });
+    }
+    stats.decode_errors.extend(truncated);
+    stats.size.fill_other();
+    stats
+}
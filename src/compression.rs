@@ -0,0 +1,218 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::inputs::Bytes;
+use anyhow::{Context, Result};
+use flate2::{read::GzEncoder, Compression};
+use std::io::Read;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const WASM_MAGIC: [u8; 4] = *b"\0asm";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    Gzip,
+    Br,
+}
+
+/// Context attached to errors from the decompressor, so they can be told
+/// apart from IO errors while reading the input.
+#[derive(Debug)]
+pub struct DecompressError(Encoding);
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self.0 {
+            Encoding::Gzip => "gzip",
+            Encoding::Br => "Brotli",
+        };
+        write!(f, "Failed to decompress {} input", name)
+    }
+}
+
+/// Figures out how the input is compressed when not told explicitly.
+///
+/// Gzip has a magic number, but Brotli doesn't, so for the latter we rely on
+/// a `.br` extension on anything that doesn't already look like wasm.
+fn guess_encoding(name: &str, input: &[u8]) -> Option<Encoding> {
+    if input.starts_with(&GZIP_MAGIC) {
+        Some(Encoding::Gzip)
+    } else if name.ends_with(".br") && !input.starts_with(&WASM_MAGIC) {
+        Some(Encoding::Br)
+    } else {
+        None
+    }
+}
+
+/// A module as read from its input, decompressed if needed.
+#[derive(Debug)]
+pub struct Decoded {
+    pub wasm: Bytes,
+    /// The size of the input before decompression, if it was compressed.
+    pub compressed_size: Option<usize>,
+}
+
+/// Decompresses the input named `name`, either with the given encoding or
+/// with one guessed from its contents and name.
+pub fn decompress(name: &str, input: Bytes, encoding: Option<Encoding>) -> Result<Decoded> {
+    let encoding = match encoding.or_else(|| guess_encoding(name, &input)) {
+        Some(encoding) => encoding,
+        None => {
+            return Ok(Decoded {
+                wasm: input,
+                compressed_size: None,
+            })
+        }
+    };
+    let mut wasm = Vec::new();
+    match encoding {
+        Encoding::Gzip => flate2::read::MultiGzDecoder::new(&input[..])
+            .read_to_end(&mut wasm)
+            .context(DecompressError(encoding))?,
+        Encoding::Br => brotli::Decompressor::new(&input[..], 4096)
+            .read_to_end(&mut wasm)
+            .context(DecompressError(encoding))?,
+    };
+    Ok(Decoded {
+        wasm: Bytes::Read(wasm),
+        compressed_size: Some(input.len()),
+    })
+}
+
+/// The highest Brotli quality, and the default for `--brotli-quality`.
+pub const MAX_BROTLI_QUALITY: u32 = 11;
+
+/// What a module would be served as, for `--estimate-compression`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    pub gzip: usize,
+    pub brotli: usize,
+}
+
+/// Compresses `wasm` with gzip at level 9 and Brotli at `brotli_quality`,
+/// only keeping the sizes.
+pub fn estimate(wasm: &[u8], brotli_quality: u32) -> Estimate {
+    fn size(mut reader: impl Read) -> usize {
+        // Reading from a slice can't fail.
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap_or(0) as usize
+    }
+    Estimate {
+        gzip: size(GzEncoder::new(wasm, Compression::best())),
+        brotli: size(brotli::CompressorReader::new(
+            wasm,
+            4096,
+            brotli_quality,
+            22,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn gzip_input() -> Result<()> {
+        let wasm = wat::parse_str("(module (func) (func (export \"f\")))")?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&wasm)?;
+        let gzipped = encoder.finish()?;
+
+        let decoded = decompress("module.wasm", gzipped.clone().into(), None)?;
+        assert_eq!(decoded.wasm[..], wasm[..]);
+        assert_eq!(decoded.compressed_size, Some(gzipped.len()));
+
+        let plain = wasm_stats::get_stats(&wasm)?;
+        let unzipped = wasm_stats::get_stats(&decoded.wasm)?;
+        assert_eq!(
+            serde_json::to_value(&plain)?,
+            serde_json::to_value(&unzipped)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn uncompressed_input() -> Result<()> {
+        let wasm = wat::parse_str("(module)")?;
+        let decoded = decompress("module.wasm", wasm.clone().into(), None)?;
+        assert_eq!(decoded.wasm[..], wasm[..]);
+        assert_eq!(decoded.compressed_size, None);
+        // Even with a `.br` extension, valid wasm isn't mistaken for Brotli.
+        let decoded = decompress("module.wasm.br", wasm.clone().into(), None)?;
+        assert_eq!(decoded.compressed_size, None);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_gzip() {
+        let err = decompress(
+            "module.wasm",
+            vec![0x1f, 0x8b, 0x08, 0x00, 0x01].into(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Failed to decompress gzip input");
+    }
+
+    fn brotli(wasm: &[u8]) -> Result<Vec<u8>> {
+        let mut compressed = Vec::new();
+        brotli::CompressorReader::new(wasm, 4096, 9, 22).read_to_end(&mut compressed)?;
+        Ok(compressed)
+    }
+
+    #[test]
+    fn brotli_flag() -> Result<()> {
+        let wasm = wat::parse_str("(module (func))")?;
+        let compressed = brotli(&wasm)?;
+        let decoded = decompress("-", compressed.clone().into(), Some(Encoding::Br))?;
+        assert_eq!(decoded.wasm[..], wasm[..]);
+        assert_eq!(decoded.compressed_size, Some(compressed.len()));
+        Ok(())
+    }
+
+    #[test]
+    fn brotli_extension() -> Result<()> {
+        let wasm = wat::parse_str("(module (func))")?;
+        let decoded = decompress("module.wasm.br", brotli(&wasm)?.into(), None)?;
+        assert_eq!(decoded.wasm[..], wasm[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn estimate_sizes() -> Result<()> {
+        let wasm =
+            wat::parse_str(r#"(module (memory 1) (data (i32.const 0) "ababababababababab"))"#)?;
+        let fast = estimate(&wasm, 1);
+        let best = estimate(&wasm, MAX_BROTLI_QUALITY);
+        assert_eq!(fast.gzip, best.gzip);
+        assert!(best.brotli <= fast.brotli, "{:?} {:?}", best, fast);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&wasm)?;
+        let gzipped = encoder.finish()?;
+        assert_eq!(best.gzip, gzipped.len());
+        let decoded = decompress("-", gzipped.into(), None)?;
+        assert_eq!(decoded.wasm[..], wasm[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_brotli() {
+        let err = decompress("module.wasm.br", vec![0xff; 16].into(), None).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to decompress Brotli input");
+    }
+}
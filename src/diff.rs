@@ -0,0 +1,179 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Structural comparison of two modules for `wasm-stats diff`.
+use crate::output::fields;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+use wasm_stats::Stats;
+use wasmparser::{Parser, Payload};
+
+/// A module's stats, along with the names of its imports, which the stats
+/// only count.
+pub struct Module {
+    pub stats: Stats,
+    pub imports: BTreeSet<String>,
+}
+
+impl Module {
+    pub fn new(wasm: &[u8]) -> Result<Self> {
+        let stats = wasm_stats::get_stats(wasm)?;
+        let mut imports = BTreeSet::new();
+        // The stats would have reported any decode errors already, so just
+        // collect what can be read.
+        for payload in Parser::new(0).parse_all(wasm).flatten() {
+            if let Payload::ImportSection(reader) = payload {
+                for import in reader.into_imports().flatten() {
+                    imports.insert(format!("{}.{}", import.module, import.name));
+                }
+            }
+        }
+        Ok(Module { stats, imports })
+    }
+}
+
+/// Deltas between the numeric fields of `a` and `b` that differ, with the
+/// change as a percentage of `a` where it isn't zero.
+fn deltas(a: impl Serialize, b: impl Serialize) -> Map<String, Value> {
+    let b = fields(b);
+    let mut deltas = Map::new();
+    for (name, a) in fields(a) {
+        let (a, b) = match (a.as_u64(), b.get(&name).and_then(Value::as_u64)) {
+            (Some(a), Some(b)) if a != b => (a, b),
+            _ => continue,
+        };
+        let delta = b as i64 - a as i64;
+        let percent = (a != 0).then(|| delta as f64 * 100.0 / a as f64);
+        deltas.insert(
+            name,
+            json!({ "a": a, "b": b, "delta": delta, "percent": percent }),
+        );
+    }
+    deltas
+}
+
+/// Names in `b` but not `a`, and in `a` but not `b`.
+fn added_and_removed<'a>(
+    a: impl IntoIterator<Item = &'a str>,
+    b: impl IntoIterator<Item = &'a str>,
+) -> Map<String, Value> {
+    let a: BTreeSet<_> = a.into_iter().collect();
+    let b: BTreeSet<_> = b.into_iter().collect();
+    let mut changes = Map::new();
+    let added: Vec<_> = b.difference(&a).collect();
+    if !added.is_empty() {
+        changes.insert("added".to_owned(), json!(added));
+    }
+    let removed: Vec<_> = a.difference(&b).collect();
+    if !removed.is_empty() {
+        changes.insert("removed".to_owned(), json!(removed));
+    }
+    changes
+}
+
+fn custom_section_names(stats: &Stats) -> impl Iterator<Item = &str> {
+    stats
+        .custom_sections
+        .iter()
+        .map(|section| section.name.as_str())
+}
+
+/// Compares two modules, leaving out everything that's the same in both.
+pub fn diff(a: &Module, b: &Module) -> Value {
+    let (a_stats, b_stats) = (&a.stats, &b.stats);
+    let groups = [
+        ("size", deltas(&a_stats.size, &b_stats.size)),
+        (
+            "instr_categories",
+            deltas(&a_stats.instr.categories, &b_stats.instr.categories),
+        ),
+        (
+            "proposals",
+            deltas(&a_stats.instr.proposals, &b_stats.instr.proposals),
+        ),
+        ("imports", deltas(&a_stats.imports, &b_stats.imports)),
+        ("exports", deltas(&a_stats.exports, &b_stats.exports)),
+        (
+            "custom_sections",
+            added_and_removed(custom_section_names(a_stats), custom_section_names(b_stats)),
+        ),
+        (
+            "import_names",
+            added_and_removed(
+                a.imports.iter().map(String::as_str),
+                b.imports.iter().map(String::as_str),
+            ),
+        ),
+    ];
+    let mut diff = Map::new();
+    for (name, group) in groups {
+        if !group.is_empty() {
+            diff.insert(name.to_owned(), Value::Object(group));
+        }
+    }
+    Value::Object(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(wat: &str) -> Result<Module> {
+        Module::new(&wat::parse_str(wat)?)
+    }
+
+    #[test]
+    fn one_function_and_import() -> Result<()> {
+        let a = module(
+            r#"(module
+                (import "env" "f" (func))
+                (func (export "g") call 0))"#,
+        )?;
+        let b = module(
+            r#"(module
+                (import "env" "f" (func))
+                (import "env" "h" (func))
+                (func (export "g") call 0)
+                (func call 1))"#,
+        )?;
+        let diff = diff(&a, &b);
+        assert_eq!(
+            diff["imports"],
+            json!({ "funcs": { "a": 1, "b": 2, "delta": 1, "percent": 100.0 } })
+        );
+        assert_eq!(
+            diff["instr_categories"],
+            json!({ "direct_calls": { "a": 1, "b": 2, "delta": 1, "percent": 100.0 } })
+        );
+        // One more body: a size byte, no locals, `call 1` and `end`.
+        assert_eq!(diff["size"]["code"]["delta"], 5);
+        assert_eq!(diff["import_names"], json!({ "added": ["env.h"] }));
+        // Unchanged groups are left out entirely.
+        assert!(diff.get("exports").is_none());
+        assert!(diff.get("proposals").is_none());
+        assert!(diff.get("custom_sections").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn identical_modules() -> Result<()> {
+        let a = module("(module (func))")?;
+        let b = module("(module (func))")?;
+        assert_eq!(diff(&a, &b), json!({}));
+        Ok(())
+    }
+}
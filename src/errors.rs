@@ -0,0 +1,131 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::compression::DecompressError;
+use serde::Serialize;
+use std::time::Duration;
+use wasm_stats::HeaderError;
+
+/// Emitted in place of the stats when an input couldn't be analysed, so that
+/// downstream joins still see every input that was attempted.
+#[derive(Debug, Serialize)]
+pub struct ErrorRecord<'a> {
+    pub file: &'a str,
+    pub error: String,
+    /// Size of the input in bytes, if it could be read at all.
+    pub size: Option<usize>,
+}
+
+impl<'a> ErrorRecord<'a> {
+    pub fn new(file: &'a str, err: &anyhow::Error, size: Option<usize>) -> Self {
+        ErrorRecord {
+            file,
+            error: describe(err),
+            size,
+        }
+    }
+}
+
+/// A panic caught while analysing an input, which would otherwise have taken
+/// the rest of the batch down with it.
+#[derive(Debug)]
+pub struct Panic(String);
+
+impl Panic {
+    pub fn new(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => (*message).to_owned(),
+                Err(_) => "unknown cause".to_owned(),
+            },
+        };
+        Panic(message)
+    }
+}
+
+impl std::fmt::Display for Panic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Analysis panicked: {}", self.0)
+    }
+}
+
+impl std::error::Error for Panic {}
+
+/// An input that was left out because it ran into one of the resource
+/// limits.
+#[derive(Debug)]
+pub enum Skipped {
+    /// Bigger than `--max-size` bytes, before or after decompression.
+    TooLarge { size: u64, max: u64 },
+    /// Still being analysed after `--timeout`.
+    Timeout(Duration),
+    /// A URL whose body was cut off at `--max-size` bytes, so its full size
+    /// isn't known.
+    #[cfg(feature = "fetch")]
+    DownloadTooLarge(u64),
+}
+
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Skipped::TooLarge { size, max } => {
+                write!(f, "too large: {} bytes, over the limit of {}", size, max)
+            }
+            Skipped::Timeout(limit) => write!(f, "timeout: not done after {:?}", limit),
+            #[cfg(feature = "fetch")]
+            Skipped::DownloadTooLarge(max) => {
+                write!(f, "too large: download over the limit of {} bytes", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+#[cfg(feature = "fetch")]
+fn is_fetch_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<crate::fetch::FetchError>().is_some()
+}
+
+#[cfg(not(feature = "fetch"))]
+fn is_fetch_error(_: &anyhow::Error) -> bool {
+    false
+}
+
+/// Prefixes the error message with a category that can be grouped on.
+fn describe(err: &anyhow::Error) -> String {
+    let category = if let Some(err) = err.downcast_ref::<HeaderError>() {
+        match err {
+            HeaderError::InvalidMagic => "invalid magic or version",
+            _ => "decode error",
+        }
+    } else if err.downcast_ref::<Skipped>().is_some() {
+        "skipped"
+    } else if err.downcast_ref::<Panic>().is_some() {
+        "panic"
+    } else if err.downcast_ref::<DecompressError>().is_some() {
+        "decompression error"
+    } else if err.downcast_ref::<wat::Error>().is_some() {
+        "text format error"
+    } else if is_fetch_error(err) {
+        "network error"
+    } else if err.downcast_ref::<std::io::Error>().is_some() {
+        "io error"
+    } else {
+        "error"
+    };
+    format!("{}: {:#}", category, err)
+}
@@ -0,0 +1,282 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::{errors::Skipped, inputs::HttpInfo};
+use anyhow::Result;
+use std::{
+    io::Read,
+    process::{Command, Stdio},
+};
+
+/// The program URLs are fetched with, looked up on the `PATH`.
+const CURL: &str = "curl";
+
+/// Starts the line that curl writes the response details to on stderr, so
+/// that it can be told apart from curl's own messages.
+const MARKER: &str = "wasm-stats response: ";
+
+/// curl's exit code when `--max-filesize` is exceeded.
+const FILESIZE_EXCEEDED: i32 = 63;
+
+/// Whether a path given on the command line is a URL to fetch.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// A failed request, whether curl couldn't complete it or the server
+/// responded with an error status.
+#[derive(Debug)]
+pub struct FetchError(String);
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// Checks that curl can be run, so that a batch of URLs fails up front with
+/// one clear error rather than with a confusing one for each URL.
+pub fn check_curl() -> Result<()> {
+    Ok(check(CURL)?)
+}
+
+fn check(program: &str) -> Result<(), FetchError> {
+    Command::new(program)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(drop)
+        .map_err(|e| spawn_error(program, e))
+}
+
+fn spawn_error(program: &str, e: std::io::Error) -> FetchError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        FetchError(format!(
+            "Fetching URLs needs `{}`, which wasn't found on the PATH",
+            program
+        ))
+    } else {
+        FetchError(format!("Failed to run {}: {}", program, e))
+    }
+}
+
+/// A response body, already decoded if the server used a `Content-Encoding`.
+#[derive(Debug)]
+pub struct Fetched {
+    pub body: Vec<u8>,
+    pub http: HttpInfo,
+}
+
+/// Fetches a URL with the system's `curl`, following redirects, so that the
+/// tool doesn't need an HTTP and TLS stack of its own. Bodies over
+/// `max_size` bytes fail with [`Skipped::DownloadTooLarge`], without being
+/// downloaded in full.
+pub fn fetch(url: &str, max_size: Option<u64>) -> Result<Fetched> {
+    let mut command = Command::new(CURL);
+    command
+        .args(["--silent", "--show-error", "--location", "--compressed"])
+        .args(["--max-redirs", "10", "--proto", "=http,https"])
+        .arg("--write-out")
+        .arg(format!(
+            "%{{stderr}}{}%{{url_effective}}\\t%{{http_code}}\\t%{{content_type}}\\n",
+            MARKER
+        ));
+    if let Some(max) = max_size {
+        command.arg("--max-filesize").arg(max.to_string());
+    }
+    let mut child = command
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| spawn_error(CURL, e))?;
+
+    // Servers don't always send a `Content-Length` for curl to check, so the
+    // body is cut off one byte over the limit as well.
+    let mut body = Vec::new();
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let limit = max_size.map_or(u64::MAX, |max| max.saturating_add(1));
+    stdout.take(limit).read_to_end(&mut body)?;
+    if let Some(max) = max_size.filter(|&max| body.len() as u64 > max) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(Skipped::DownloadTooLarge(max).into());
+    }
+
+    let output = child.wait_with_output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if let (Some(FILESIZE_EXCEEDED), Some(max)) = (output.status.code(), max_size) {
+        return Err(Skipped::DownloadTooLarge(max).into());
+    }
+    if !output.status.success() {
+        let message: Vec<_> = stderr
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with(MARKER))
+            .collect();
+        return Err(FetchError(format!("Failed to fetch {}: {}", url, message.join("; "))).into());
+    }
+    let details = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix(MARKER))
+        .ok_or_else(|| FetchError(format!("No response details from curl for {}", url)))?;
+    let mut details = details.split('\t');
+    let (final_url, status, content_type) = (details.next(), details.next(), details.next());
+    let http = HttpInfo {
+        url: final_url.unwrap_or(url).to_owned(),
+        status: status.and_then(|status| status.parse().ok()).unwrap_or(0),
+        content_type: content_type
+            .filter(|content_type| !content_type.is_empty())
+            .map(str::to_owned),
+    };
+    if !(200..300).contains(&http.status) {
+        return Err(FetchError(format!("HTTP status {} from {}", http.status, http.url)).into());
+    }
+    Ok(Fetched { body, http })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    /// A server on a local port that answers each of the given requests in
+    /// turn, returning its base URL. Each response is sent with
+    /// `Connection: close`, so every request needs a connection of its own.
+    pub fn serve(responses: Vec<Vec<u8>>) -> std::io::Result<(String, JoinHandle<()>)> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = match listener.accept() {
+                    Ok(connection) => connection,
+                    Err(_) => return,
+                };
+                // Skip the request, up to the blank line after its headers.
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                let _ = stream.write_all(&response);
+            }
+        });
+        Ok((url, server))
+    }
+
+    /// An HTTP/1.1 response with the given status line and headers.
+    pub fn response(status: &str, headers: &[&str], body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            status,
+            body.len()
+        );
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+        response.push_str("\r\n");
+        let mut response = response.into_bytes();
+        response.extend(body);
+        response
+    }
+
+    #[test]
+    fn redirect_and_content_encoding() -> Result<()> {
+        let wasm = wat::parse_str("(module (func (export \"f\")))")?;
+        let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+        gzipped.write_all(&wasm)?;
+        let gzipped = gzipped.finish()?;
+        let (url, server) = serve(vec![
+            response("302 Found", &["Location: /module.wasm"], b""),
+            response(
+                "200 OK",
+                &["Content-Type: application/wasm", "Content-Encoding: gzip"],
+                &gzipped,
+            ),
+        ])?;
+
+        let fetched = fetch(&format!("{}/old.wasm", url), None)?;
+        server.join().unwrap();
+        assert_eq!(fetched.body, wasm);
+        assert_eq!(fetched.http.url, format!("{}/module.wasm", url));
+        assert_eq!(fetched.http.status, 200);
+        assert_eq!(
+            fetched.http.content_type.as_deref(),
+            Some("application/wasm")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_status() -> Result<()> {
+        let (url, server) = serve(vec![response("404 Not Found", &[], b"gone")])?;
+        let err = fetch(&format!("{}/missing.wasm", url), None).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("HTTP status 404 from {}/missing.wasm", url)
+        );
+        assert!(err.downcast_ref::<FetchError>().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn connection_refused() -> Result<()> {
+        // Nothing listens on a port that was just given up.
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let err = fetch(&format!("http://127.0.0.1:{}/", port), None).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("Failed to fetch"), "{}", message);
+        assert!(message.contains("curl: (7)"), "{}", message);
+        Ok(())
+    }
+
+    #[test]
+    fn max_size() -> Result<()> {
+        let body = vec![0; 4096];
+        // With and without a `Content-Length` for curl to go on.
+        let mut chunked = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+        chunked.extend(&body);
+        let (url, server) = serve(vec![response("200 OK", &[], &body), chunked])?;
+        for _ in 0..2 {
+            let err = fetch(&url, Some(1024)).unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(Skipped::DownloadTooLarge(1024))),
+                "{:#}",
+                err
+            );
+        }
+        server.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn missing_curl() {
+        assert!(check(CURL).is_ok());
+        let err = check("wasm-stats-no-such-curl").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Fetching URLs needs `wasm-stats-no-such-curl`, which wasn't found on the PATH"
+        );
+    }
+}
@@ -0,0 +1,347 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use anyhow::{anyhow, Result};
+use memmap2::Mmap;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::File,
+    io::{Read, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// Files at least this big are memory-mapped instead of being copied into
+/// memory before decoding.
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// The contents of an input, either read into memory or mapped from a file.
+#[derive(Debug)]
+pub enum Bytes {
+    Read(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Read(bytes) => bytes,
+            Bytes::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes::Read(bytes)
+    }
+}
+
+/// Reads a file, memory-mapping it if it's at least `mmap_threshold` bytes.
+///
+/// Empty files can't be mapped, so they're always read.
+fn read_file(path: &Path, mmap_threshold: u64) -> Result<Bytes> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len > 0 && len >= mmap_threshold {
+        // Safety: the mapping is only read from, and inputs aren't expected
+        // to be modified while they're being analysed.
+        if let Ok(mmap) = unsafe { Mmap::map(&file) } {
+            return Ok(Bytes::Mapped(mmap));
+        }
+    }
+    let mut bytes = Vec::with_capacity(len as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(Bytes::Read(bytes))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    File(PathBuf),
+    Stdin,
+    #[cfg(feature = "fetch")]
+    Url(String),
+}
+
+/// The response an input given as a URL was fetched from.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct HttpInfo {
+    /// The URL after following redirects.
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+}
+
+/// A single module to analyse, along with the name it's reported under.
+#[derive(Debug, PartialEq)]
+pub struct Input {
+    pub source: Source,
+    pub name: String,
+}
+
+impl Input {
+    pub fn file(path: PathBuf, name: &Path) -> Self {
+        Input {
+            name: name.to_string_lossy().into_owned(),
+            source: Source::File(path),
+        }
+    }
+
+    #[cfg(feature = "fetch")]
+    pub fn url(url: &str) -> Self {
+        Input {
+            name: url.to_owned(),
+            source: Source::Url(url.to_owned()),
+        }
+    }
+
+    pub fn stdin() -> Self {
+        Input {
+            name: "-".to_owned(),
+            source: Source::Stdin,
+        }
+    }
+
+    /// Reads the whole module, memory-mapping files of `MMAP_THRESHOLD`
+    /// bytes or more.
+    pub fn read(&self, stdin: &mut impl Read) -> Result<Bytes> {
+        self.read_with_threshold(stdin, MMAP_THRESHOLD)
+    }
+
+    fn read_with_threshold(&self, stdin: &mut impl Read, mmap_threshold: u64) -> Result<Bytes> {
+        match &self.source {
+            Source::File(path) => read_file(path, mmap_threshold),
+            Source::Stdin => {
+                let mut wasm = Vec::new();
+                stdin.read_to_end(&mut wasm)?;
+                if wasm.is_empty() {
+                    return Err(anyhow!("No wasm module was provided on stdin"));
+                }
+                Ok(Bytes::Read(wasm))
+            }
+            #[cfg(feature = "fetch")]
+            Source::Url(url) => Ok(crate::fetch::fetch(url, None)?.body.into()),
+        }
+    }
+}
+
+fn matches_ext(path: &Path, exts: &[OsString]) -> bool {
+    match path.extension() {
+        Some(ext) => exts.iter().any(|e| e == ext),
+        None => false,
+    }
+}
+
+fn walk_dir(
+    root: &Path,
+    dir: &Path,
+    exts: &[OsString],
+    visited: &mut HashSet<PathBuf>,
+    inputs: &mut Vec<Input>,
+    warn: &mut impl Write,
+) -> Result<()> {
+    // Canonical paths let us notice when a symlink leads back to a directory
+    // we've already walked.
+    match std::fs::canonicalize(dir) {
+        Ok(canonical) => {
+            if !visited.insert(canonical) {
+                writeln!(warn, "warning: {}: skipping symlink loop", dir.display())?;
+                return Ok(());
+            }
+        }
+        Err(e) => {
+            writeln!(warn, "warning: {}: {}", dir.display(), e)?;
+            return Ok(());
+        }
+    }
+    let mut entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            writeln!(warn, "warning: {}: {}", dir.display(), e)?;
+            return Ok(());
+        }
+    };
+    entries.sort();
+    for path in entries {
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                writeln!(warn, "warning: {}: {}", path.display(), e)?;
+                continue;
+            }
+        };
+        if metadata.is_dir() {
+            walk_dir(root, &path, exts, visited, inputs, warn)?;
+        } else if matches_ext(&path, exts) {
+            if let Err(e) = std::fs::File::open(&path) {
+                writeln!(warn, "warning: {}: {}", path.display(), e)?;
+                continue;
+            }
+            let name = path.strip_prefix(root).unwrap_or(&path).to_owned();
+            inputs.push(Input::file(path, &name));
+        }
+    }
+    Ok(())
+}
+
+/// An input for a path that's really a URL.
+#[cfg(feature = "fetch")]
+fn url_input(path: &Path) -> Option<Input> {
+    let url = path.to_str().filter(|path| crate::fetch::is_url(path))?;
+    Some(Input::url(url))
+}
+
+#[cfg(not(feature = "fetch"))]
+fn url_input(_: &Path) -> Option<Input> {
+    None
+}
+
+/// Expands the command line paths into the list of modules to analyse.
+///
+/// Files are taken as-is, while directories are walked recursively for files
+/// with one of the given extensions, which are then named relative to the
+/// directory. Problems encountered while walking are reported to `warn` and
+/// the affected entries are skipped.
+///
+/// A path of `-`, or no paths at all, means the module is read from stdin.
+/// With the `fetch` feature, `http://` and `https://` URLs are fetched.
+pub fn collect_inputs(
+    paths: &[PathBuf],
+    exts: &[OsString],
+    warn: &mut impl Write,
+) -> Result<Vec<Input>> {
+    if paths.is_empty() {
+        return Ok(vec![Input::stdin()]);
+    }
+    let mut inputs = Vec::new();
+    for path in paths {
+        if path.as_os_str() == "-" {
+            inputs.push(Input::stdin());
+        } else if let Some(input) = url_input(path) {
+            inputs.push(input);
+        } else if path.is_dir() {
+            walk_dir(path, path, exts, &mut HashSet::new(), &mut inputs, warn)?;
+        } else {
+            inputs.push(Input::file(path.clone(), path));
+        }
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_inputs_walks_directories() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("a/b"))?;
+        std::fs::write(dir.path().join("top.wasm"), b"")?;
+        std::fs::write(dir.path().join("a/b/nested.wasm"), b"")?;
+        std::fs::write(dir.path().join("a/ignored.js"), b"")?;
+
+        let mut warn = Vec::new();
+        let inputs = collect_inputs(
+            &[dir.path().to_owned()],
+            &[OsString::from("wasm")],
+            &mut warn,
+        )?;
+        let names: Vec<_> = inputs.iter().map(|input| input.name.as_str()).collect();
+        assert_eq!(names, ["a/b/nested.wasm", "top.wasm"]);
+        assert!(warn.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn collect_inputs_stdin() -> Result<()> {
+        let inputs = collect_inputs(&[], &[], &mut Vec::new())?;
+        assert_eq!(inputs, [Input::stdin()]);
+        let inputs = collect_inputs(&[PathBuf::from("-")], &[], &mut Vec::new())?;
+        assert_eq!(inputs, [Input::stdin()]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_empty_stdin() {
+        let err = Input::stdin().read(&mut &b""[..]).unwrap_err();
+        assert_eq!(err.to_string(), "No wasm module was provided on stdin");
+    }
+
+    #[test]
+    fn read_maps_large_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("m.wasm");
+        let wasm = wat::parse_str("(module (func (export \"f\") (result i32) i32.const 1))")?;
+        std::fs::write(&path, &wasm)?;
+        let input = Input::file(path.clone(), Path::new("m.wasm"));
+
+        let read = input.read_with_threshold(&mut std::io::empty(), wasm.len() as u64 + 1)?;
+        assert!(matches!(read, Bytes::Read(_)));
+        let mapped = input.read_with_threshold(&mut std::io::empty(), wasm.len() as u64)?;
+        assert!(matches!(mapped, Bytes::Mapped(_)));
+        assert_eq!(
+            serde_json::to_value(wasm_stats::get_stats(&read)?)?,
+            serde_json::to_value(wasm_stats::get_stats(&mapped)?)?
+        );
+
+        // Empty files can't be mapped, so they're read even below the threshold.
+        std::fs::write(&path, b"")?;
+        let empty = input.read_with_threshold(&mut std::io::empty(), 0)?;
+        assert!(matches!(&empty, Bytes::Read(bytes) if bytes.is_empty()));
+        Ok(())
+    }
+
+    #[test]
+    fn collect_inputs_custom_ext() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("one.wasm"), b"")?;
+        std::fs::write(dir.path().join("two.bin"), b"")?;
+
+        let inputs = collect_inputs(
+            &[dir.path().to_owned()],
+            &[OsString::from("bin")],
+            &mut Vec::new(),
+        )?;
+        let names: Vec<_> = inputs.iter().map(|input| input.name.as_str()).collect();
+        assert_eq!(names, ["two.bin"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_inputs_skips_symlink_loops() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("sub/m.wasm"), b"")?;
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop"))?;
+
+        let mut warn = Vec::new();
+        let inputs = collect_inputs(
+            &[dir.path().to_owned()],
+            &[OsString::from("wasm")],
+            &mut warn,
+        )?;
+        assert_eq!(inputs.len(), 1);
+        assert!(String::from_utf8(warn)?.contains("symlink loop"));
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Which Web APIs a module calls, going by the names of its imports.
+use crate::{GraphicsApi, JsApiStats};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Dom,
+    Gpu,
+    Fetch,
+    Audio,
+    Canvas2d,
+    Crypto,
+    Workers,
+}
+
+enum Pattern {
+    /// Anywhere in the name, ignoring case.
+    Contains(&'static str),
+    /// A C-style prefix followed by a capital, as in OpenGL's `glDrawArrays`.
+    CamelPrefix(&'static str),
+}
+
+use Pattern::*;
+
+/// Patterns for each category, tried in order, so more specific categories
+/// come before ones with generic patterns like `element`.
+const RULES: &[(Category, &[Pattern])] = &[
+    (
+        Category::Gpu,
+        &[
+            Contains("webgl"),
+            Contains("webgpu"),
+            Contains("gpu"),
+            Contains("requestadapter"),
+            Contains("drawarrays"),
+            Contains("drawelements"),
+            Contains("bindbuffer"),
+            Contains("bindtexture"),
+            Contains("shader"),
+            Contains("uniform"),
+            Contains("vertexattrib"),
+            CamelPrefix("gl"),
+        ],
+    ),
+    (
+        Category::Canvas2d,
+        &[
+            Contains("canvasrenderingcontext2d"),
+            Contains("fillrect"),
+            Contains("filltext"),
+            Contains("strokerect"),
+            Contains("stroketext"),
+            Contains("fillstyle"),
+            Contains("strokestyle"),
+            Contains("drawimage"),
+            Contains("putimagedata"),
+            Contains("getimagedata"),
+            Contains("beginpath"),
+        ],
+    ),
+    (
+        Category::Audio,
+        &[
+            Contains("audio"),
+            Contains("oscillator"),
+            Contains("openal"),
+            CamelPrefix("al"),
+        ],
+    ),
+    (
+        Category::Crypto,
+        &[
+            Contains("crypto"),
+            Contains("getrandomvalues"),
+            Contains("randomfillsync"),
+        ],
+    ),
+    (
+        Category::Fetch,
+        &[
+            Contains("fetch"),
+            Contains("xmlhttprequest"),
+            Contains("wget"),
+        ],
+    ),
+    (
+        Category::Workers,
+        &[
+            Contains("worker"),
+            Contains("postmessage"),
+            Contains("pthread"),
+        ],
+    ),
+    (
+        Category::Dom,
+        &[
+            Contains("document"),
+            Contains("window"),
+            Contains("element"),
+            Contains("queryselector"),
+            Contains("appendchild"),
+            Contains("eventlistener"),
+            Contains("innerhtml"),
+            Contains("textcontent"),
+            Contains("attribute"),
+            Contains("requestanimationframe"),
+            // Emscripten's HTML5 API: main loops, event callbacks and canvas
+            // sizes.
+            Contains("emscripten_set_"),
+        ],
+    ),
+];
+
+/// Patterns for each graphics API, newest first, since a name that only
+/// WebGL 2 has, like `glTexStorage2D`, also looks like any other WebGL call.
+const GRAPHICS_RULES: &[(GraphicsApi, &[Pattern])] = &[
+    (
+        GraphicsApi::Webgpu,
+        &[
+            // Also wgpu and Emscripten's `wgpu*` bindings.
+            Contains("gpu"),
+            Contains("requestadapter"),
+            Contains("createshadermodule"),
+            Contains("createrenderpipeline"),
+            Contains("createcommandencoder"),
+            Contains("createbindgroup"),
+            Contains("getpreferredcanvasformat"),
+        ],
+    ),
+    (
+        GraphicsApi::Webgl2,
+        &[
+            Contains("webgl2"),
+            Contains("teximage3d"),
+            Contains("texsubimage3d"),
+            Contains("texstorage2d"),
+            Contains("texstorage3d"),
+            Contains("blitframebuffer"),
+            Contains("clearbuffer"),
+            Contains("copybuffersubdata"),
+            Contains("getbuffersubdata"),
+            Contains("invalidateframebuffer"),
+            Contains("renderbufferstoragemultisample"),
+            Contains("getuniformblockindex"),
+            Contains("uniformblockbinding"),
+            Contains("bindbufferbase"),
+            Contains("bindbufferrange"),
+            Contains("vertexattribipointer"),
+            Contains("transformfeedback"),
+            Contains("fencesync"),
+            Contains("bindsampler"),
+        ],
+    ),
+    (
+        GraphicsApi::Webgl,
+        &[
+            Contains("webgl"),
+            Contains("emscripten_gl"),
+            Contains("drawarrays"),
+            Contains("drawelements"),
+            Contains("bindbuffer"),
+            Contains("bindtexture"),
+            Contains("bindframebuffer"),
+            Contains("shadersource"),
+            Contains("compileshader"),
+            Contains("linkprogram"),
+            Contains("useprogram"),
+            Contains("vertexattrib"),
+            Contains("getuniformlocation"),
+            CamelPrefix("gl"),
+        ],
+    ),
+];
+
+/// Drops wasm-bindgen's `__wbg_` prefix and hash suffix, which could
+/// otherwise happen to contain a pattern.
+fn strip_wasm_bindgen(name: &str) -> &str {
+    let name = match name.strip_prefix("__wbg_") {
+        Some(name) => name,
+        None => return name,
+    };
+    match name.rsplit_once('_') {
+        Some((name, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            name
+        }
+        _ => name,
+    }
+}
+
+fn matches(pattern: &Pattern, name: &str, lowercase: &str) -> bool {
+    match *pattern {
+        Contains(needle) => lowercase.contains(needle),
+        CamelPrefix(prefix) => name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.chars().next())
+            .is_some_and(|c| c.is_ascii_uppercase()),
+    }
+}
+
+fn classify(name: &str) -> Option<Category> {
+    let name = strip_wasm_bindgen(name);
+    let lowercase = name.to_ascii_lowercase();
+    RULES
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|p| matches(p, name, &lowercase)))
+        .map(|&(category, _)| category)
+}
+
+/// The graphics API an imported function belongs to, if any. Leading
+/// underscores are ignored, as Unity's `_glDrawArrays` has one.
+pub(crate) fn graphics_api(name: &str) -> Option<GraphicsApi> {
+    let name = strip_wasm_bindgen(name).trim_start_matches('_');
+    let lowercase = name.to_ascii_lowercase();
+    GRAPHICS_RULES
+        .iter()
+        .find(|(_, patterns)| patterns.iter().any(|p| matches(p, name, &lowercase)))
+        .map(|&(api, _)| api)
+}
+
+impl JsApiStats {
+    /// Counts an imported function towards the category its name suggests.
+    pub(crate) fn add(&mut self, name: &str) {
+        let count = match classify(name) {
+            Some(Category::Dom) => &mut self.dom,
+            Some(Category::Gpu) => &mut self.gpu,
+            Some(Category::Fetch) => &mut self.fetch,
+            Some(Category::Audio) => &mut self.audio,
+            Some(Category::Canvas2d) => &mut self.canvas2d,
+            Some(Category::Crypto) => &mut self.crypto,
+            Some(Category::Workers) => &mut self.workers,
+            None => &mut self.uncategorized,
+        };
+        *count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crawled_import_names() {
+        let names = [
+            ("__wbg_fetch_b5d6bebed1e6c2d2", Some(Category::Fetch)),
+            ("__wbg_open_f4f9d5ba0a6ec4fd", None),
+            ("emscripten_webgl_create_context", Some(Category::Gpu)),
+            ("glDrawArrays", Some(Category::Gpu)),
+            ("__wbg_requestAdapter_913357b9788f14cd", Some(Category::Gpu)),
+            ("__wbg_gpu_7e4dc1a5f4ee3b2f", Some(Category::Gpu)),
+            ("__wbg_createElement_8bae7856a4bb7411", Some(Category::Dom)),
+            ("_emscripten_set_main_loop", Some(Category::Dom)),
+            (
+                "emscripten_set_keydown_callback_on_thread",
+                Some(Category::Dom),
+            ),
+            (
+                "__wbg_getRandomValues_37fa2ca9e4e07fab",
+                Some(Category::Crypto),
+            ),
+            (
+                "__wbg_postMessage_5bc72bd4d1ba2d36",
+                Some(Category::Workers),
+            ),
+            ("__emscripten_init_main_thread_js", None),
+            ("alSourcePlay", Some(Category::Audio)),
+            (
+                "__wbg_createOscillator_a52e43fde0b0bb4c",
+                Some(Category::Audio),
+            ),
+            ("__wbg_fillRect_b5c8166281bac9df", Some(Category::Canvas2d)),
+            ("__wbindgen_throw", None),
+            ("invoke_viii", None),
+            ("a", None),
+        ];
+        for (name, category) in names {
+            assert_eq!(classify(name), category, "{}", name);
+        }
+    }
+
+    #[test]
+    fn graphics_import_names() {
+        let names = [
+            // Unity's WebGL 2 builds.
+            ("_glTexStorage2D", Some(GraphicsApi::Webgl2)),
+            ("_glBlitFramebuffer", Some(GraphicsApi::Webgl2)),
+            ("_glClearBufferfv", Some(GraphicsApi::Webgl2)),
+            ("_glGetUniformBlockIndex", Some(GraphicsApi::Webgl2)),
+            ("_glDrawElements", Some(GraphicsApi::Webgl)),
+            ("_glBindVertexArray", Some(GraphicsApi::Webgl)),
+            ("_emscripten_webgl_create_context", Some(GraphicsApi::Webgl)),
+            ("emscripten_glGenBuffers", Some(GraphicsApi::Webgl)),
+            ("_JS_Sound_Play", None),
+            ("_JS_SystemInfo_GetCanvasClientSize", None),
+            // wgpu, through wasm-bindgen.
+            (
+                "__wbg_requestAdapter_913357b9788f14cd",
+                Some(GraphicsApi::Webgpu),
+            ),
+            ("__wbg_gpu_7e4dc1a5f4ee3b2f", Some(GraphicsApi::Webgpu)),
+            (
+                "__wbg_createShaderModule_036b780a18124d9e",
+                Some(GraphicsApi::Webgpu),
+            ),
+            (
+                "__wbg_getPreferredCanvasFormat_c57006806f2efe1b",
+                Some(GraphicsApi::Webgpu),
+            ),
+            (
+                "__wbg_instanceof_WebGl2RenderingContext_f921526c513bf717",
+                Some(GraphicsApi::Webgl2),
+            ),
+            (
+                "__wbg_drawArrays_c91ce3f736bf1f2a",
+                Some(GraphicsApi::Webgl),
+            ),
+            (
+                "__wbg_texImage3D_88ff1fa41be127b9",
+                Some(GraphicsApi::Webgl2),
+            ),
+            // Emscripten's WebGPU bindings.
+            ("wgpuDeviceCreateBuffer", Some(GraphicsApi::Webgpu)),
+            ("__wbg_fetch_b5d6bebed1e6c2d2", None),
+            ("glob", None),
+        ];
+        for (name, api) in names {
+            assert_eq!(graphics_api(name), api, "{}", name);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let mut stats = JsApiStats::default();
+        for name in [
+            "glClear",
+            "glViewport",
+            "fd_write",
+            "emscripten_set_main_loop",
+        ] {
+            stats.add(name);
+        }
+        assert_eq!(stats.gpu, 2);
+        assert_eq!(stats.dom, 1);
+        assert_eq!(stats.uncategorized, 1);
+    }
+}
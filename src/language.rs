@@ -0,0 +1,551 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Heuristics for guessing the source language of a module.
+use crate::{
+    wasi_version, Confidence, GoStats, Language, ModuleInfo, ProducersInfo, RustPackaging, Stats,
+};
+use std::collections::BTreeMap;
+use wasmparser::{Export, Import, ValType};
+
+/// The Mono runtime on its own has well under this many functions, while AOT
+/// compiling even a small app adds tens of thousands.
+pub const DOTNET_AOT_MIN_FUNCS: usize = 20_000;
+
+/// The language a rule picked, and why.
+pub struct Inference {
+    pub language: Language,
+    pub confidence: Confidence,
+    /// Descriptions of the items that matched, e.g. `import env.abort`.
+    pub evidence: Vec<String>,
+}
+
+impl Inference {
+    fn new(language: Language, confidence: Confidence, evidence: Vec<String>) -> Option<Self> {
+        Some(Inference {
+            language,
+            confidence,
+            evidence,
+        })
+    }
+}
+
+/// A heuristic, returning its guess if it matches.
+type Rule = fn(&ModuleInfo, &Stats) -> Option<Inference>;
+
+/// The first import matching `pred`, as evidence.
+fn import(module: &ModuleInfo, pred: impl Fn(&Import) -> bool) -> Option<String> {
+    let i = module.imports.iter().find(|i| pred(i))?;
+    Some(format!("import {}.{}", i.module, i.name))
+}
+
+/// The first export matching `pred`, as evidence.
+fn export(module: &ModuleInfo, pred: impl Fn(&Export) -> bool) -> Option<String> {
+    let e = module.exports.iter().find(|e| pred(e))?;
+    Some(format!("export {}", e.name))
+}
+
+/// The first data segment containing `needle`, as evidence.
+fn data(module: &ModuleInfo, needle: &str) -> Option<String> {
+    module
+        .data
+        .iter()
+        .find(|data| data.windows(needle.len()).any(|w| w == needle.as_bytes()))?;
+    Some(format!("data {}", needle))
+}
+
+/// The first function name matching `pred`, as evidence.
+fn name(module: &ModuleInfo, pred: impl Fn(&str) -> bool) -> Option<String> {
+    let (_, name) = module.function_names.iter().find(|(_, name)| pred(name))?;
+    Some(format!("name {}", name))
+}
+
+/// Trusts the producers section, which toolchains fill in themselves.
+fn producers(_: &ModuleInfo, stats: &Stats) -> Option<Inference> {
+    let producers: &ProducersInfo = stats.producers.as_ref()?;
+    let language = |name: &str| {
+        let tool = producers.language.iter().find(|tool| tool.name == name)?;
+        Some(vec![format!("producers language {}", tool.name)])
+    };
+    let processed_by = |name: &str| {
+        let tool = producers
+            .processed_by
+            .iter()
+            .find(|tool| tool.name == name)?;
+        Some(vec![format!("producers processed-by {}", tool.name)])
+    };
+    let (language, evidence) = if let Some(evidence) = language("Rust") {
+        (Language::Rust, evidence)
+    } else if let Some(evidence) = processed_by("wasm-bindgen") {
+        (Language::Rust, evidence)
+    } else if let Some(evidence) = language("Go") {
+        (Language::Go, evidence)
+    } else if let Some(evidence) = language("AssemblyScript") {
+        (Language::AssemblyScript, evidence)
+    } else if let Some(evidence) = language("Kotlin") {
+        (Language::Kotlin, evidence)
+    } else if let Some(evidence) = processed_by("Emscripten") {
+        (Language::Emscripten, evidence)
+    } else {
+        return None;
+    };
+    Inference::new(language, Confidence::High, evidence)
+}
+
+/// AssemblyScript's runtime is exported alongside the `env.abort` import it
+/// always uses, and debug builds name its standard library.
+///
+/// Emscripten also imports `env.abort`, so this comes before its rules.
+fn assemblyscript(module: &ModuleInfo, _: &Stats) -> Option<Inference> {
+    if let Some(name) = name(module, |name| name.starts_with("~lib/")) {
+        return Inference::new(Language::AssemblyScript, Confidence::High, vec![name]);
+    }
+    let abort = import(module, |i| {
+        i.module == "env"
+            && i.name == "abort"
+            && module
+                .import_type(i)
+                .is_some_and(|ty| ty.params() == [ValType::I32; 4] && ty.results().is_empty())
+    })?;
+    let runtime = export(module, |e| {
+        ["__new", "__pin", "__unpin", "__collect"].contains(&e.name)
+    })?;
+    Inference::new(
+        Language::AssemblyScript,
+        Confidence::Medium,
+        vec![abort, runtime],
+    )
+}
+
+/// Kotlin/Wasm imports its JS glue from `js_code`, and targets wasm-gc.
+fn kotlin(module: &ModuleInfo, stats: &Stats) -> Option<Inference> {
+    let strong = import(module, |i| i.module == "js_code" || i.module == "kotlin")
+        .or_else(|| name(module, |name| name.starts_with("kotlin.")));
+    if let Some(evidence) = strong {
+        return Inference::new(Language::Kotlin, Confidence::High, vec![evidence]);
+    }
+    if stats.instr.proposals.gc == 0 {
+        return None;
+    }
+    let initialize = export(module, |e| e.name == "_initialize")?;
+    Inference::new(
+        Language::Kotlin,
+        Confidence::Low,
+        vec![initialize, "gc proposal".to_owned()],
+    )
+}
+
+/// The .NET (Mono) runtime, from the JS interop functions it imports.
+///
+/// It's built with Emscripten, so this comes before the Emscripten rules.
+fn dotnet(module: &ModuleInfo, _: &Stats) -> Option<Inference> {
+    let evidence = import(module, |i| {
+        i.name.contains("blazor")
+            || i.name.starts_with("mono_wasm_")
+            || i.name.starts_with("corehost_")
+    })?;
+    Inference::new(Language::Blazor, Confidence::High, vec![evidence])
+}
+
+/// IL2CPP compiles the whole engine along with the game, leaving far more
+/// functions than a typical Emscripten app.
+const UNITY_MIN_FUNCS: usize = 20_000;
+
+/// Unity WebGL, from the IL2CPP runtime and the engine's JS interop.
+///
+/// These are Emscripten builds, so this comes before the Emscripten rules.
+fn unity(module: &ModuleInfo, stats: &Stats) -> Option<Inference> {
+    let is_unity = |name: &str| {
+        let lower = name.to_ascii_lowercase();
+        name.contains("SendMessage") || lower.contains("il2cpp") || lower.contains("unity")
+    };
+    let strong = import(module, |i| is_unity(i.module) || is_unity(i.name))
+        .or_else(|| export(module, |e| is_unity(e.name)))
+        .or_else(|| name(module, |name| name.contains("il2cpp")))
+        .or_else(|| data(module, "UnityEngine"));
+    if let Some(evidence) = strong {
+        return Inference::new(Language::Unity, Confidence::High, vec![evidence]);
+    }
+    if stats.dyncall_exports == 0 || stats.funcs < UNITY_MIN_FUNCS {
+        return None;
+    }
+    let framework = data(module, "unityFramework")?;
+    let dyncall = export(module, |e| e.name.starts_with("dynCall_"))?;
+    Inference::new(
+        Language::Unity,
+        Confidence::Medium,
+        vec![dyncall, framework],
+    )
+}
+
+/// Exports that Emscripten's JS glue relies on, which survive minification.
+const EMSCRIPTEN_EXPORTS: &[&str] = &[
+    "stackSave",
+    "stackRestore",
+    "stackAlloc",
+    "__wasm_call_ctors",
+    "dynCall_",
+];
+
+fn emscripten(module: &ModuleInfo, stats: &Stats) -> Option<Inference> {
+    // Only Emscripten produces dynamically linked side modules.
+    let strong = if stats.dylink.is_some() {
+        Some("custom section dylink".to_owned())
+    } else {
+        import(module, |i| i.name.contains("emscripten"))
+    };
+    if let Some(evidence) = strong {
+        return Inference::new(Language::Emscripten, Confidence::High, vec![evidence]);
+    }
+    // Minified builds only import single letters, but still export these.
+    // The `dynCall_*` trampolines only count once between them.
+    let fingerprints: Vec<_> = EMSCRIPTEN_EXPORTS
+        .iter()
+        .filter_map(|&fingerprint| {
+            export(module, |e| {
+                e.name == fingerprint
+                    || (fingerprint.ends_with('_') && e.name.starts_with(fingerprint))
+            })
+        })
+        .collect();
+    if fingerprints.len() < 2 {
+        return None;
+    }
+    Inference::new(Language::Emscripten, Confidence::Medium, fingerprints)
+}
+
+fn go(module: &ModuleInfo, _: &Stats) -> Option<Inference> {
+    // `wasm_exec.js` renamed the module to `gojs` in Go 1.21.
+    let evidence = import(module, |i| i.module == "go" || i.module == "gojs")?;
+    Inference::new(Language::Go, Confidence::High, vec![evidence])
+}
+
+/// Whether a symbol uses Rust's mangling: either v0 (`_R…`) or the legacy
+/// Itanium-like scheme, which ends with a `17h<hash>E` suffix.
+fn is_rust_mangled(name: &str) -> bool {
+    if name.starts_with("_R") {
+        return true;
+    }
+    let hash = name
+        .strip_prefix("_ZN")
+        .and_then(|name| name.strip_suffix('E'))
+        .and_then(|name| name.len().checked_sub(16).map(|i| name.split_at(i)));
+    match hash {
+        Some((rest, hash)) => rest.ends_with("17h") && hash.bytes().all(|b| b.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+fn rust(module: &ModuleInfo, _: &Stats) -> Option<Inference> {
+    // these are all based on Rust using wasm-bindgen
+    let wasm_bindgen = import(module, |i| {
+        i.name.contains("wbindgen")
+            || i.name.contains("wbg")
+            || i.module == "wbg"
+            || i.module == "wbindgen"
+    })
+    .or_else(|| export(module, |e| e.name.contains("wbindgen")));
+    if let Some(evidence) = wasm_bindgen {
+        return Inference::new(Language::Rust, Confidence::High, vec![evidence]);
+    }
+    let evidence = name(module, is_rust_mangled)?;
+    Inference::new(Language::Rust, Confidence::Medium, vec![evidence])
+}
+
+/// Functions from wasi-libc, which clang links into C and C++ programs.
+const LIBC_SYMBOLS: &[&str] = &[
+    "__original_main",
+    "__libc_start_main",
+    "__wasi_proc_exit",
+    "malloc",
+    "free",
+    "memcpy",
+    "printf",
+];
+
+/// Plain clang (e.g. the WASI SDK) exports the linker's symbols or entry
+/// points, and imports nothing but WASI.
+///
+/// Zig, TinyGo and Go's wasip1 port do the same, so without the producers
+/// section or names pointing at clang or libc this is only a weak guess.
+/// Rust modules also export `__heap_base`, so this comes after the Rust rule.
+fn clang(module: &ModuleInfo, stats: &Stats) -> Option<Inference> {
+    if module.imports.is_empty()
+        || !module
+            .imports
+            .iter()
+            .all(|i| wasi_version(i.module) == Some("preview1"))
+    {
+        return None;
+    }
+    let mut evidence = vec![export(module, |e| {
+        ["__heap_base", "__data_end", "_start", "_initialize"].contains(&e.name)
+    })?];
+    let producers = stats.producers.as_ref().and_then(|producers| {
+        if let Some(tool) = producers.processed_by.iter().find(|t| t.name == "clang") {
+            Some(format!("producers processed-by {}", tool.name))
+        } else {
+            let tool = producers.sdk.iter().find(|t| t.name == "wasi-sdk")?;
+            Some(format!("producers sdk {}", tool.name))
+        }
+    });
+    let mangled = name(module, |name| name.starts_with("_Z"));
+    let libc = name(module, |name| {
+        LIBC_SYMBOLS.contains(&name) || name.starts_with("__wasilibc_")
+    });
+    let language = if mangled.is_some() {
+        Language::CppClang
+    } else {
+        Language::CClang
+    };
+    let c_evidence: Vec<_> = vec![producers, mangled, libc]
+        .into_iter()
+        .flatten()
+        .collect();
+    let confidence = if c_evidence.is_empty() {
+        Confidence::Low
+    } else {
+        Confidence::Medium
+    };
+    evidence.extend(c_evidence);
+    Inference::new(language, confidence, evidence)
+}
+
+/// Strings that toolchains leave in the data section, such as panic messages
+/// and source paths, which survive minification of imports and exports.
+///
+/// Only available when the data is scanned, and weaker than the other rules
+/// since e.g. an Emscripten build can embed a Rust library.
+fn data_strings(module: &ModuleInfo, _: &Stats) -> Option<Inference> {
+    const PATTERNS: &[(Language, &str)] = &[
+        (Language::Rust, "src/libcore/"),
+        (Language::Rust, "library/core/src/"),
+        (Language::Go, "runtime.gopanic"),
+        (Language::Emscripten, "emscripten_"),
+    ];
+    PATTERNS.iter().find_map(|&(language, pattern)| {
+        let evidence = data(module, pattern)?;
+        Inference::new(language, Confidence::Medium, vec![evidence])
+    })
+}
+
+fn likely_emscripten(module: &ModuleInfo, _: &Stats) -> Option<Inference> {
+    let pair = |module_name: &str| {
+        Some(vec![
+            import(module, |i| i.module == module_name && i.name == "a")?,
+            import(module, |i| i.module == module_name && i.name == "b")?,
+        ])
+    };
+
+    // Many of the wasm modules have been compressed with this very distinctive pattern. From looking at a number of wasm modules
+    // and inspecting their contents, or the page that hosts them, it seems quite likely this is Emscripten. For example:
+    //
+    // https://tweet2doom.github.io/t2d-explorer.wasm
+    //   => https://github.com/tweet2doom/tweet2doom.github.io - strong evidence of Emscripten
+    //
+    // https://graphonline.ru/script/Graphoffline.Emscripten.wasm - the clue is in the filename!
+    //
+    // https://wsr-starfinder.com/js/stellarium-web-engine.06229ae9.wasm
+    //  => https://github.com/Stellarium/stellarium-web-engine - code makes reference to using Emscripten
+    let evidence = pair("a")
+        // another distinctive pattern, again, evidence suggests Emscripten
+        // https://tx.me/
+        // => https://github.com/Samsung/rlottie/blob/master/src/wasm/rlottiewasm.cpp - this is a cool project ;-)
+        //
+        // https://demo.harmonicvision.com - Emscripten mentioned in the page source
+        //
+        // https://webcamera.io - uses FFMpeg, which is an Emscripten project
+        .or_else(|| pair("env"))?;
+    Inference::new(Language::LikelyEmscripten, Confidence::Low, evidence)
+}
+
+/// The rules, in order of priority.
+const RULES: &[Rule] = &[
+    producers,
+    assemblyscript,
+    kotlin,
+    dotnet,
+    unity,
+    emscripten,
+    go,
+    rust,
+    clang,
+    data_strings,
+    likely_emscripten,
+];
+
+/// Infers the source language, trusting the producers section when it names
+/// a recognised toolchain and otherwise using whichever imports, exports and
+/// names could be decoded.
+///
+/// Every rule is tried, since e.g. Blazor and Unity are both built on
+/// Emscripten, and the matches are returned in order of priority.
+pub fn infer_language(module: &ModuleInfo, stats: &Stats) -> Vec<Inference> {
+    RULES
+        .iter()
+        .filter_map(|rule| rule(module, stats))
+        .collect()
+}
+
+/// Whether a symbol name belongs to a family.
+type SymbolTest = fn(&str) -> bool;
+
+/// The families of symbol names that [`symbol_origins`] counts, in the
+/// order they're trusted in: Rust and Go symbols only come from their own
+/// compilers, allocator names point at Emscripten's libc whatever the source
+/// language, and C++ libraries end up in modules of every kind.
+const SYMBOL_FAMILIES: &[(&str, Language, SymbolTest)] = &[
+    ("rust", Language::Rust, is_rust_mangled),
+    ("go", Language::Go, is_go_symbol),
+    (
+        "emscripten_allocator",
+        Language::Emscripten,
+        is_emscripten_allocator,
+    ),
+    ("cpp", Language::CppClang, is_cpp_mangled),
+];
+
+/// Go names functions by package path, and its runtime's type helpers
+/// start with `type..`.
+fn is_go_symbol(name: &str) -> bool {
+    ["go.", "go:", "type..", "runtime."]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Emscripten's default allocator, and the `sbrk` it grows memory with.
+fn is_emscripten_allocator(name: &str) -> bool {
+    name.starts_with("dlmalloc")
+        || name.starts_with("emscripten_builtin_")
+        || matches!(name, "sbrk" | "dlfree" | "dlrealloc" | "dlcalloc")
+}
+
+/// Itanium mangling, as used by clang for C++, unless it's Rust's legacy
+/// mangling, which looks the same apart from its hash.
+fn is_cpp_mangled(name: &str) -> bool {
+    name.starts_with("_Z") && !is_rust_mangled(name)
+}
+
+/// Counts the function names in the name section by the family their
+/// mangling or prefix belongs to, with the rest counted as `other`.
+pub fn symbol_origins(module: &ModuleInfo) -> BTreeMap<String, usize> {
+    let mut origins = BTreeMap::new();
+    for (_, name) in &module.function_names {
+        let family = SYMBOL_FAMILIES
+            .iter()
+            .find(|(_, _, is_family)| is_family(name))
+            .map_or("other", |(family, _, _)| family);
+        *origins.entry(family.to_owned()).or_insert(0) += 1;
+    }
+    origins
+}
+
+/// A last resort for modules that no rule matched: the most trusted family
+/// in `origins`, with its first name as evidence.
+pub fn infer_from_symbols(
+    module: &ModuleInfo,
+    origins: &BTreeMap<String, usize>,
+) -> Option<Inference> {
+    let &(_, language, is_family) = SYMBOL_FAMILIES
+        .iter()
+        .find(|(family, _, _)| origins.contains_key(*family))?;
+    let evidence = name(module, is_family)?;
+    Inference::new(language, Confidence::Low, vec![evidence])
+}
+
+/// Markers that wasm-bindgen and Emscripten leave next to their version.
+const VERSION_MARKERS: &[&str] = &["wasm-bindgen ", "emscripten "];
+
+/// The leading `major.minor…` version number of `s`, if it has one.
+fn leading_version(s: &[u8]) -> Option<String> {
+    let len = s
+        .iter()
+        .position(|b| !b.is_ascii_digit() && *b != b'.')
+        .unwrap_or(s.len());
+    let version = std::str::from_utf8(&s[..len]).ok()?.trim_end_matches('.');
+    let mut parts = version.split('.');
+    if parts.clone().count() < 2 || parts.any(str::is_empty) {
+        return None;
+    }
+    Some(version.to_owned())
+}
+
+/// Whether wasm-bindgen's CLI has processed the module, going by the module
+/// its imports come from: rustc emits imports from
+/// `__wbindgen_placeholder__`, which the CLI renames to `wbg`, or to the
+/// generated `./*_bg.js` file in older versions.
+pub fn rust_packaging(module: &ModuleInfo) -> Option<RustPackaging> {
+    let modules = || module.imports.iter().map(|i| i.module);
+    if modules().any(|m| m == "__wbindgen_placeholder__") {
+        Some(RustPackaging::Raw)
+    } else if modules().any(|m| m == "wbg" || m.ends_with("_bg.js")) {
+        Some(RustPackaging::BindgenProcessed)
+    } else {
+        None
+    }
+}
+
+/// Counts the imports from Go's `wasm_exec.js` by package. These are the
+/// imports the `go` rule already matched on, so they come straight from
+/// [`ModuleInfo::imports`].
+pub fn go_stats(module: &ModuleInfo) -> GoStats {
+    let mut stats = GoStats::default();
+    for import in &module.imports {
+        if import.module != "go" && import.module != "gojs" {
+            continue;
+        }
+        stats.imports += 1;
+        if import.name.starts_with("syscall/js.") {
+            stats.syscall_js += 1;
+        } else if import.name.starts_with("runtime.") {
+            stats.runtime += 1;
+        }
+    }
+    stats
+}
+
+/// Finds the wasm-bindgen or Emscripten version, preferring the producers
+/// section and otherwise searching unrecognised custom sections and any
+/// scanned data for version strings. Conflicting versions give `None`.
+pub fn toolchain_version(module: &ModuleInfo, stats: &Stats) -> Option<String> {
+    let mut versions: Vec<String> = match &stats.producers {
+        Some(producers) => producers
+            .processed_by
+            .iter()
+            .filter(|tool| tool.name == "wasm-bindgen" || tool.name == "Emscripten")
+            .filter_map(|tool| leading_version(tool.version.as_bytes()))
+            .collect(),
+        None => Vec::new(),
+    };
+    if versions.is_empty() {
+        for haystack in module.custom.iter().chain(&module.data) {
+            for marker in VERSION_MARKERS {
+                let marker = marker.as_bytes();
+                let found = haystack
+                    .windows(marker.len())
+                    .enumerate()
+                    .filter(|(_, w)| w.eq_ignore_ascii_case(marker))
+                    .filter_map(|(i, _)| leading_version(&haystack[i + marker.len()..]));
+                versions.extend(found);
+            }
+        }
+    }
+    versions.sort();
+    versions.dedup();
+    match &versions[..] {
+        [version] => Some(version.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,6426 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Statistics about WebAssembly modules, as used for the Web Almanac.
+//!
+//! The main entry point is [`get_stats`], which decodes a module and returns
+//! a [`Stats`] summary that serializes to the same JSON the CLI emits.
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    time::Instant,
+};
+use wasmbin::sections::{Kind, Section};
+use wasmparser::{
+    BinaryReader, BlockType, CodeSectionReader, CompositeInnerType, ConstExpr, CustomSectionReader,
+    Data, DataKind, Dylink0SectionReader, Dylink0Subsection, Element, ElementItems, ElementKind,
+    Export, ExternalKind, FromReader, FuncType, FunctionBody, Import, ImportSectionReader, MemArg,
+    MemoryType, Name, NameSectionReader, Operator, ProducersSectionReader, RecGroup,
+    SectionLimited, Table, TypeRef, ValType, Validator, WasmFeatures,
+};
+
+mod assets;
+mod component;
+mod js_api;
+mod language;
+mod libraries;
+mod sections;
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProposalStats {
+    pub atomics: usize,
+    pub ref_types: usize,
+    pub simd: usize,
+    pub tail_calls: usize,
+    pub bulk: usize,
+    /// `multi_value_types` and `multi_value_blocks` together.
+    pub multi_value: usize,
+    /// Function types with more than one result.
+    pub multi_value_types: usize,
+    /// Blocks, loops and `if`s whose type has parameters or more than one
+    /// result.
+    pub multi_value_blocks: usize,
+    pub non_trapping_conv: usize,
+    pub sign_extend: usize,
+    pub mutable_externals: usize,
+    pub bigint_externals: usize,
+    pub exception_handling: usize,
+    pub gc: usize,
+    pub memory64: usize,
+    pub relaxed_simd: usize,
+    pub multi_memory: usize,
+    pub extended_const: usize,
+    pub function_references: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Hash, Debug, Default, Clone, Copy)]
+#[non_exhaustive]
+pub enum Language {
+    Rust,
+    Emscripten,
+    // a category for WebAssembly modules where there is some evidence that
+    // it is Emscripten, but the methods used are not terribly reliable.
+    LikelyEmscripten,
+    AssemblyScript,
+    Blazor,
+    #[default]
+    Unknown,
+    Go,
+    Kotlin,
+    // C compiled with plain clang, e.g. the WASI SDK, rather than Emscripten.
+    CClang,
+    // As above, but with C++ (Itanium-mangled) symbol names.
+    CppClang,
+    // Unity WebGL builds, which are Emscripten builds of IL2CPP output.
+    Unity,
+}
+
+/// How much to trust the inferred [`Language`].
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug, Default)]
+#[non_exhaustive]
+pub enum Confidence {
+    /// The toolchain named itself, or left an unmistakable import.
+    High,
+    /// A combination of fingerprints that other toolchains are unlikely to
+    /// produce.
+    Medium,
+    /// A pattern that has been seen in the wild, but isn't conclusive.
+    #[default]
+    Low,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct InstructionCategoryStats {
+    pub load_store: usize,
+    pub local_var: usize,
+    pub global_var: usize,
+    pub table: usize,
+    pub memory: usize,
+    pub control_flow: usize,
+    pub direct_calls: usize,
+    pub indirect_calls: usize,
+    pub constants: usize,
+    pub wait_notify: usize,
+    pub gc: usize,
+    /// Scalar integer and float arithmetic and bitwise operations.
+    pub arithmetic: usize,
+    /// Scalar `eqz`, `eq`, `ne`, `lt`, `gt`, `le` and `ge`.
+    pub comparison: usize,
+    /// Scalar wrap, extend, trunc, convert, demote, promote and reinterpret.
+    pub conversion: usize,
+    pub other: usize,
+}
+
+/// A field of [`InstructionCategoryStats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InstrCategory {
+    LoadStore,
+    LocalVar,
+    GlobalVar,
+    Table,
+    Memory,
+    ControlFlow,
+    DirectCalls,
+    IndirectCalls,
+    Constants,
+    WaitNotify,
+    Gc,
+    Arithmetic,
+    Comparison,
+    Conversion,
+    Other,
+}
+
+impl InstructionCategoryStats {
+    fn count_mut(&mut self, category: InstrCategory) -> &mut usize {
+        match category {
+            InstrCategory::LoadStore => &mut self.load_store,
+            InstrCategory::LocalVar => &mut self.local_var,
+            InstrCategory::GlobalVar => &mut self.global_var,
+            InstrCategory::Table => &mut self.table,
+            InstrCategory::Memory => &mut self.memory,
+            InstrCategory::ControlFlow => &mut self.control_flow,
+            InstrCategory::DirectCalls => &mut self.direct_calls,
+            InstrCategory::IndirectCalls => &mut self.indirect_calls,
+            InstrCategory::Constants => &mut self.constants,
+            InstrCategory::WaitNotify => &mut self.wait_notify,
+            InstrCategory::Gc => &mut self.gc,
+            InstrCategory::Arithmetic => &mut self.arithmetic,
+            InstrCategory::Comparison => &mut self.comparison,
+            InstrCategory::Conversion => &mut self.conversion,
+            InstrCategory::Other => &mut self.other,
+        }
+    }
+}
+
+/// Instruction counts by mnemonic (e.g. `i32.add`), most frequent first.
+///
+/// Serialized as a map, in that order.
+#[derive(Default, Debug, PartialEq)]
+pub struct OpcodeCounts(pub Vec<(String, usize)>);
+
+impl OpcodeCounts {
+    /// Collects counts keyed by wasmparser's `visit_*` method names.
+    fn from_visit_names(counts: HashMap<&str, usize>) -> Self {
+        Self::sorted(
+            counts
+                .into_iter()
+                .map(|(visit, count)| (mnemonic(visit), count)),
+        )
+    }
+
+    fn sorted(counts: impl Iterator<Item = (String, usize)>) -> Self {
+        let mut counts: Vec<_> = counts.collect();
+        counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        OpcodeCounts(counts)
+    }
+}
+
+impl Serialize for OpcodeCounts {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(name, count)| (name, count)))
+    }
+}
+
+impl<'de> Deserialize<'de> for OpcodeCounts {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let counts = BTreeMap::<String, usize>::deserialize(deserializer)?;
+        Ok(Self::sorted(counts.into_iter()))
+    }
+}
+
+impl JsonSchema for OpcodeCounts {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "OpcodeCounts".into()
+    }
+
+    fn json_schema(gen: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        BTreeMap::<String, usize>::json_schema(gen)
+    }
+}
+
+/// Turns a wasmparser visitor method name into the instruction's text format
+/// mnemonic, e.g. `visit_i32_atomic_rmw8_add_u` into `i32.atomic.rmw8.add_u`.
+fn mnemonic(visit: &str) -> String {
+    const NAMESPACES: &[&str] = &[
+        "i32", "i64", "f32", "f64", "v128", "i8x16", "i16x8", "i32x4", "i64x2", "f32x4", "f64x2",
+        "local", "global", "table", "memory", "data", "elem", "ref", "struct", "array", "any",
+        "extern", "i31", "atomic",
+    ];
+    let name = visit.strip_prefix("visit_").unwrap_or(visit);
+    let (namespace, rest) = match name.split_once('_') {
+        Some((namespace, rest)) if NAMESPACES.contains(&namespace) => (namespace, rest),
+        _ => return name.to_owned(),
+    };
+    let mut mnemonic = format!("{}.", namespace);
+    let rest = match rest.strip_prefix("atomic_") {
+        Some(rest) => {
+            mnemonic.push_str("atomic.");
+            rest
+        }
+        None => rest,
+    };
+    // Read-modify-write atomics have another level, e.g. `rmw16.xchg_u`.
+    match rest.split_once('_') {
+        Some((rmw, op)) if rmw.starts_with("rmw") => {
+            mnemonic.push_str(rmw);
+            mnemonic.push('.');
+            mnemonic.push_str(op);
+        }
+        _ => mnemonic.push_str(rest),
+    }
+    mnemonic
+}
+
+/// Name of the wasmparser visitor method for an operator, which identifies
+/// it uniquely.
+fn visit_name_of(op: &Operator) -> &'static str {
+    macro_rules! define_visit_name_of {
+        ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*))*) => {
+            match op {
+                $(Operator::$op { .. } => stringify!($visit),)*
+                _ => "unknown",
+            }
+        };
+    }
+    wasmparser::for_each_operator!(define_visit_name_of)
+}
+
+/// Families of numeric operand types, for [`InstructionStats::int_ops`] and
+/// its siblings.
+#[derive(Clone, Copy)]
+enum OperandFamily {
+    Int,
+    Float,
+    Simd,
+}
+
+/// The operand family of a numeric instruction, from the type that prefixes
+/// its mnemonic (e.g. `f32` in `f32.load`, or `i32x4` in `i32x4.add`).
+fn operand_family(op: &Operator) -> Option<OperandFamily> {
+    const FAMILIES: &[(&str, OperandFamily)] = &[
+        ("i32", OperandFamily::Int),
+        ("i64", OperandFamily::Int),
+        ("f32", OperandFamily::Float),
+        ("f64", OperandFamily::Float),
+        ("v128", OperandFamily::Simd),
+        ("i8x16", OperandFamily::Simd),
+        ("i16x8", OperandFamily::Simd),
+        ("i32x4", OperandFamily::Simd),
+        ("i64x2", OperandFamily::Simd),
+        ("f32x4", OperandFamily::Simd),
+        ("f64x2", OperandFamily::Simd),
+    ];
+    let name = visit_name_of(op).strip_prefix("visit_")?;
+    let (prefix, _) = name.split_once('_')?;
+    FAMILIES
+        .iter()
+        .find(|(name, _)| *name == prefix)
+        .map(|&(_, family)| family)
+}
+
+/// Breakdown of the threads proposal's instructions that `categories` counts
+/// as `other`; waits and notifies, and atomic loads and stores, have
+/// categories of their own.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct AtomicDetail {
+    /// Read-modify-write operations, e.g. `i32.atomic.rmw.add`, including
+    /// compare-exchanges.
+    pub rmw: usize,
+    /// `atomic.fence` instructions.
+    pub fence: usize,
+    /// Every instruction from the proposal, which the record reports as
+    /// [`ThreadingStats::atomic_instructions`].
+    #[serde(skip)]
+    instructions: usize,
+}
+
+/// Alignment hints of the instructions that access memory, i.e. loads,
+/// stores and atomics, including SIMD ones.
+///
+/// The hint is a power of two, counted here by its exponent, so `align_2` is
+/// 4-byte alignment.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct MemAccessStats {
+    pub align_0: usize,
+    pub align_1: usize,
+    pub align_2: usize,
+    pub align_3: usize,
+    pub align_4_or_more: usize,
+    /// Accesses whose hint is less than the width they access, e.g.
+    /// `i32.load align=1`, which toolchains emit when they can't prove the
+    /// address is aligned.
+    pub below_natural: usize,
+}
+
+impl MemAccessStats {
+    fn add(&mut self, memarg: &MemArg) {
+        let count = match memarg.align {
+            0 => &mut self.align_0,
+            1 => &mut self.align_1,
+            2 => &mut self.align_2,
+            3 => &mut self.align_3,
+            _ => &mut self.align_4_or_more,
+        };
+        *count += 1;
+        if memarg.align < memarg.max_align {
+            self.below_natural += 1;
+        }
+    }
+}
+
+/// `br_table` instructions, which switch statements and interpreter loops
+/// compile to, and how many labels they choose between.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct BrTableStats {
+    pub count: usize,
+    /// Most labels in a single `br_table`, including its default.
+    pub max_targets: usize,
+    pub mean_targets: f64,
+}
+
+/// Locals declared by function bodies, not counting parameters.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct LocalStats {
+    pub total: usize,
+    pub i32: usize,
+    pub i64: usize,
+    pub f32: usize,
+    pub f64: usize,
+    pub v128: usize,
+    /// Reference types of any kind.
+    #[serde(rename = "ref")]
+    pub refs: usize,
+    /// Most locals declared by a single function.
+    pub max_per_function: usize,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct InstructionStats {
+    pub total: usize,
+    pub proposals: ProposalStats,
+    pub categories: InstructionCategoryStats,
+    /// Encoded size in bytes of the instructions in each category, including
+    /// their immediates. Tail calls count towards two categories, as above.
+    pub category_bytes: InstructionCategoryStats,
+    pub atomics: AtomicDetail,
+    pub mem_access: MemAccessStats,
+    pub br_table: BrTableStats,
+    pub locals: LocalStats,
+    /// Deepest block nesting in any function, where the function body itself
+    /// is depth 1.
+    pub max_nesting_depth: usize,
+    /// Average over the functions of their deepest block nesting.
+    pub mean_nesting_depth: f64,
+    /// Instructions operating on scalar integers, including their loads,
+    /// stores and constants.
+    pub int_ops: usize,
+    /// As above, for scalar floats.
+    pub float_ops: usize,
+    /// As above, for `v128` values.
+    pub simd_ops: usize,
+    /// Count of every instruction by mnemonic, if [`Options::opcodes`] is set.
+    pub opcodes: Option<OpcodeCounts>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct SizeStats {
+    pub code: usize,
+    /// The element and data sections together; see `elem` and `data`.
+    pub init: usize,
+    pub elem: usize,
+    pub data: usize,
+    pub externals: usize,
+    pub types: usize,
+    pub custom: usize,
+    pub descriptors: usize,
+    /// Everything not counted above: the header, each section's id, and
+    /// sections that are unmeasured or failed to decode. The fields above
+    /// (with `init` standing for `elem` and `data`) add up to `total`.
+    pub other: usize,
+    pub total: usize,
+    /// Size of the input before decompression, when it was compressed.
+    pub compressed: Option<usize>,
+    /// Size of the module compressed with gzip at level 9, when estimated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gzip: Option<usize>,
+    /// As above, for Brotli.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brotli: Option<usize>,
+    /// Bytes that stripping would remove: the name, producers,
+    /// `sourceMappingURL` and `.debug_*` sections, ids included.
+    pub strippable: usize,
+    /// `total` less `strippable`.
+    pub stripped_total: usize,
+}
+
+/// Whether a custom section only holds metadata that tools like
+/// `wasm-strip` remove without changing what the module does.
+fn is_strippable(name: &str) -> bool {
+    matches!(name, "name" | "producers" | "sourceMappingURL") || name.starts_with(".debug_")
+}
+
+impl SizeStats {
+    /// Sets `other` to whatever the other fields don't account for.
+    fn fill_other(&mut self) {
+        let counted =
+            self.code + self.init + self.externals + self.types + self.custom + self.descriptors;
+        self.other = self.total.saturating_sub(counted);
+    }
+}
+
+/// Distribution of function body sizes, in bytes, excluding the length that
+/// precedes each body.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct FuncSizeStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub median: usize,
+    pub p90: usize,
+    pub p99: usize,
+    /// Number of bodies larger than [`LARGE_FUNC_SIZE`].
+    pub large: usize,
+}
+
+/// Shape of the direct call graph between functions.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct CallGraphStats {
+    /// Defined functions that are never called (except by themselves),
+    /// exported, used as the start function, or referenced from a table
+    /// element or `ref.func`.
+    pub potentially_dead: usize,
+    /// Most distinct functions called directly by a single function.
+    pub max_fan_out: usize,
+    /// Whether any function calls itself directly.
+    pub has_direct_recursion: bool,
+}
+
+/// Function references gathered while walking the module, which become
+/// [`CallGraphStats`] once every section has been seen.
+#[derive(Default)]
+struct CallGraph {
+    /// Number of imported functions, which come first in the index space.
+    imported_funcs: u32,
+    referenced: HashSet<u32>,
+    max_fan_out: usize,
+    has_direct_recursion: bool,
+}
+
+impl CallGraph {
+    /// Records the calls and references made by the function at `index`
+    /// in the code section.
+    fn add_body(&mut self, index: u32, ops: &[Operator]) {
+        let this = self.imported_funcs.saturating_add(index);
+        let mut callees = HashSet::new();
+        for op in ops {
+            match op {
+                Operator::Call { function_index } | Operator::ReturnCall { function_index } => {
+                    callees.insert(*function_index);
+                }
+                Operator::RefFunc { function_index } => {
+                    self.referenced.insert(*function_index);
+                }
+                _ => {}
+            }
+        }
+        self.max_fan_out = self.max_fan_out.max(callees.len());
+        if callees.remove(&this) {
+            self.has_direct_recursion = true;
+        }
+        self.referenced.extend(callees);
+    }
+
+    fn stats(&self, funcs: usize) -> CallGraphStats {
+        let first = self.imported_funcs as usize;
+        CallGraphStats {
+            potentially_dead: (first..first + funcs)
+                .filter(|&index| {
+                    u32::try_from(index).map_or(true, |index| !self.referenced.contains(&index))
+                })
+                .count(),
+            max_fan_out: self.max_fan_out,
+            has_direct_recursion: self.has_direct_recursion,
+        }
+    }
+}
+
+/// Functions exported by Binaryen's Asyncify transform.
+const ASYNCIFY_EXPORTS: &[&str] = &[
+    "asyncify_start_unwind",
+    "asyncify_stop_unwind",
+    "asyncify_start_rewind",
+    "asyncify_stop_rewind",
+    "asyncify_get_state",
+];
+
+/// Parts of import and export names that only Emscripten's pthreads support
+/// uses, e.g. `_emscripten_thread_init` or `__pthread_create_js`.
+const EMSCRIPTEN_PTHREAD_MARKERS: &[&str] = &[
+    "emscripten_thread_init",
+    "emscripten_check_blocking_allowed",
+    "emscripten_thread_free_data",
+    "pthread_create_js",
+];
+
+/// As above, for Rust threads on wasm-bindgen, e.g. with the `wasm_thread`
+/// crate.
+const WASM_BINDGEN_THREAD_MARKERS: &[&str] = &["__wbindgen_thread_id", "wasm_thread"];
+
+/// Which globals the function bodies use, gathered during the code pass for
+/// measuring Asyncify's instrumentation.
+#[derive(Default)]
+struct GlobalUses {
+    /// Number of functions reading or writing each global.
+    funcs: HashMap<u32, usize>,
+    /// Code section index of the function exported as `asyncify_get_state`.
+    get_state: Option<u32>,
+    /// The global read by `asyncify_get_state`, which holds Asyncify's state.
+    state: Option<u32>,
+}
+
+impl GlobalUses {
+    fn add_body(&mut self, index: u32, ops: &[Operator]) {
+        let mut globals = HashSet::new();
+        for op in ops {
+            match op {
+                Operator::GlobalGet { global_index } | Operator::GlobalSet { global_index } => {
+                    globals.insert(*global_index);
+                }
+                _ => {}
+            }
+        }
+        if self.get_state == Some(index) {
+            self.state = ops.iter().find_map(|op| match op {
+                Operator::GlobalGet { global_index } => Some(*global_index),
+                _ => None,
+            });
+        }
+        for global in globals {
+            *self.funcs.entry(global).or_default() += 1;
+        }
+    }
+}
+
+/// The library whose thread support was found in the imports and exports.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ThreadRuntime {
+    /// Emscripten's pthreads, run on Web Workers.
+    EmscriptenPthreads,
+    /// wasm-bindgen's thread support, as used by `wasm_thread` and
+    /// `wasm-bindgen-rayon`.
+    WasmBindgen,
+}
+
+/// Whether a module runs threads, rather than only using atomics.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ThreadingStats {
+    /// Whether any memory, imported or defined, is shared.
+    pub shared_memory: bool,
+    /// Instructions from the threads proposal, including waits and notifies.
+    pub atomic_instructions: usize,
+    pub wait_notify: usize,
+    /// The library that starts the threads, if its machinery was found.
+    pub runtime: Option<ThreadRuntime>,
+}
+
+impl ThreadingStats {
+    fn new(module: &ModuleInfo, stats: &Stats) -> Self {
+        let names = || {
+            module
+                .imports
+                .iter()
+                .map(|i| i.name)
+                .chain(module.exports.iter().map(|e| e.name))
+        };
+        let has_marker =
+            |markers: &[&str]| names().any(|name| markers.iter().any(|m| name.contains(m)));
+        let runtime = if has_marker(EMSCRIPTEN_PTHREAD_MARKERS) {
+            Some(ThreadRuntime::EmscriptenPthreads)
+        } else if has_marker(WASM_BINDGEN_THREAD_MARKERS) {
+            Some(ThreadRuntime::WasmBindgen)
+        } else {
+            None
+        };
+        ThreadingStats {
+            shared_memory: stats.memories.iter().any(|memory| memory.shared),
+            atomic_instructions: stats.instr.atomics.instructions,
+            wait_notify: stats.instr.categories.wait_notify,
+            runtime,
+        }
+    }
+}
+
+/// A memory, whether imported or defined, in index order.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct MemoryStats {
+    /// Initial size in 64 KiB pages.
+    pub initial: u64,
+    pub maximum: Option<u64>,
+    pub shared: bool,
+    pub memory64: bool,
+    /// `module.name` of the import, for imported memories.
+    pub import: Option<String>,
+    /// Names the memory is exported under.
+    pub exports: Vec<String>,
+}
+
+impl MemoryStats {
+    fn new(ty: &MemoryType, import: Option<String>) -> Self {
+        MemoryStats {
+            initial: ty.initial,
+            maximum: ty.maximum,
+            shared: ty.shared,
+            memory64: ty.memory64,
+            import,
+            exports: Vec::new(),
+        }
+    }
+}
+
+/// Data segments, from the data section.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct DataStats {
+    pub segments: usize,
+    /// Total size of the segment contents.
+    pub bytes: usize,
+    pub passive: usize,
+    /// Size of the largest segment's contents.
+    pub largest: usize,
+    /// Indices of the memories that active segments initialise.
+    pub memories: Vec<u32>,
+}
+
+/// Shannon entropy in bits per byte, from 0 for a single repeated byte to 8
+/// for uniformly random bytes, rounded to 3 decimal places. `None` when there
+/// are no bytes to measure.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct EntropyStats {
+    /// Of the code section's contents.
+    pub code: Option<f64>,
+    /// Of the data segments' contents, taken together.
+    pub data: Option<f64>,
+}
+
+/// Counts bytes for [`EntropyStats`].
+struct ByteHistogram {
+    counts: [usize; 256],
+    total: usize,
+}
+
+impl Default for ByteHistogram {
+    fn default() -> Self {
+        ByteHistogram {
+            counts: [0; 256],
+            total: 0,
+        }
+    }
+}
+
+impl ByteHistogram {
+    fn add(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.counts[usize::from(byte)] += 1;
+        }
+        self.total += bytes.len();
+    }
+
+    fn entropy(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+        let total = self.total as f64;
+        let bits: f64 = self
+            .counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+        // Rounding also turns the -0.0 of a single repeated byte into 0.
+        Some((bits * 1000.0).round() / 1000.0 + 0.0)
+    }
+}
+
+/// How much of the data segments is text, if [`Options::scan_data`] is set.
+/// Only the bytes that it scans are counted.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct DataStrings {
+    pub scanned_bytes: usize,
+    /// Printable ASCII bytes, tabs and newlines included.
+    pub printable_bytes: usize,
+    /// The longest run of printable bytes within a segment.
+    pub longest_run: usize,
+    /// The fraction of scanned bytes that decode as UTF-8 characters other
+    /// than control characters, so that zero padding doesn't count as text.
+    pub utf8_valid_fraction: f64,
+}
+
+impl DataStrings {
+    fn new(segments: &[&[u8]]) -> Self {
+        let mut strings = DataStrings::default();
+        let mut utf8_bytes = 0;
+        for segment in segments {
+            strings.scanned_bytes += segment.len();
+            let mut run = 0;
+            for &byte in *segment {
+                if byte.is_ascii_graphic() || matches!(byte, b' ' | b'\t' | b'\n' | b'\r') {
+                    strings.printable_bytes += 1;
+                    run += 1;
+                    strings.longest_run = strings.longest_run.max(run);
+                } else {
+                    run = 0;
+                }
+            }
+            utf8_bytes += segment
+                .utf8_chunks()
+                .flat_map(|chunk| chunk.valid().chars())
+                .filter(|c| !c.is_control() || c.is_ascii_whitespace())
+                .map(char::len_utf8)
+                .sum::<usize>();
+        }
+        if strings.scanned_bytes > 0 {
+            strings.utf8_valid_fraction = utf8_bytes as f64 / strings.scanned_bytes as f64;
+        }
+        strings
+    }
+}
+
+/// The file formats that [`Options::data_contents`] recognises.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum AssetFormat {
+    Png,
+    Jpeg,
+    Woff,
+    Woff2,
+    Zlib,
+    Gzip,
+    /// A nested module or component.
+    Wasm,
+}
+
+/// A file found in a data segment by its magic number.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct EmbeddedAsset {
+    pub format: AssetFormat,
+    /// Index of the data segment.
+    pub segment: usize,
+    /// Offset of the magic number within the segment's contents.
+    pub offset: usize,
+    /// Size as given by the asset's own header or structure where it has
+    /// one, otherwise up to the end of the segment.
+    pub approx_size: usize,
+}
+
+/// A table, whether imported or defined, in index order.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TableInfo {
+    /// In the text format, e.g. `funcref` or `externref`.
+    pub element_type: String,
+    pub initial: u64,
+    pub maximum: Option<u64>,
+    pub imported: bool,
+}
+
+/// Tables, and the element segments that initialise them.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct TableStats {
+    pub tables: Vec<TableInfo>,
+    pub active_segments: usize,
+    pub passive_segments: usize,
+    pub declared_segments: usize,
+    /// Function references in all the element segments.
+    pub function_refs: usize,
+}
+
+impl TableInfo {
+    fn new(ty: &wasmparser::TableType, imported: bool) -> Self {
+        TableInfo {
+            element_type: ty.element_type.to_string(),
+            initial: ty.initial,
+            maximum: ty.maximum,
+            imported,
+        }
+    }
+}
+
+/// Globals, whether imported or defined.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct GlobalStats {
+    pub total: usize,
+    pub imported: usize,
+    pub mutable: usize,
+    pub i32: usize,
+    pub i64: usize,
+    pub f32: usize,
+    pub f64: usize,
+    pub v128: usize,
+    /// Reference types of any kind.
+    #[serde(rename = "ref")]
+    pub refs: usize,
+    /// Defined globals initialised with `global.get`, i.e. from an import.
+    pub init_from_global: usize,
+    /// Defined globals initialised with a single constant.
+    pub init_from_const: usize,
+}
+
+/// Imported functions by the kind of Web API their names suggest they call,
+/// such as `__wbg_fetch_…` or `emscripten_webgl_create_context`.
+///
+/// WASI imports aren't counted, since they aren't Web APIs.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct JsApiStats {
+    /// Documents, elements, events and animation frames.
+    pub dom: usize,
+    /// WebGL and WebGPU.
+    pub gpu: usize,
+    /// `fetch` and `XMLHttpRequest`.
+    pub fetch: usize,
+    /// Web Audio and OpenAL.
+    pub audio: usize,
+    pub canvas2d: usize,
+    pub crypto: usize,
+    /// Workers, threads and `postMessage`.
+    pub workers: usize,
+    /// Imports that don't match any of the above, including minified names.
+    pub uncategorized: usize,
+}
+
+/// Imports from the Go runtime's JavaScript glue, `wasm_exec.js`, which says
+/// whether a Go module talks to the page or only computes.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct GoStats {
+    /// Imports from the `go` module, or `gojs` since Go 1.21.
+    pub imports: usize,
+    /// `syscall/js.*` imports, used for calls into JavaScript and the DOM.
+    pub syscall_js: usize,
+    /// `runtime.*` imports, such as timers, randomness and `wasmExit`.
+    pub runtime: usize,
+}
+
+/// How many export names are listed by default, which is enough to see what a
+/// module is for without a module with thousands of exports bloating its
+/// record.
+pub const DEFAULT_EXPORT_NAMES: usize = 100;
+
+/// The names of a module's exports, alongside the counts in [`Stats::exports`].
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ExportDetails {
+    /// The first [`Options::export_names`] names, in the order they're
+    /// declared.
+    pub names: Vec<String>,
+    /// Mean length of all the names, in bytes.
+    pub mean_name_length: f64,
+    /// Whether most names are just one or two characters long, as left by
+    /// minifiers like Emscripten's in optimised builds.
+    pub looks_minified: bool,
+}
+
+impl ExportDetails {
+    fn new<'a>(names: impl ExactSizeIterator<Item = &'a str>, limit: usize) -> Self {
+        let count = names.len();
+        let mut details = ExportDetails::default();
+        let (mut total_length, mut short) = (0, 0);
+        for name in names {
+            total_length += name.len();
+            if name.len() <= 2 {
+                short += 1;
+            }
+            if details.names.len() < limit {
+                details.names.push(name.to_owned());
+            }
+        }
+        if count > 0 {
+            details.mean_name_length = total_length as f64 / count as f64;
+            details.looks_minified = short * 2 > count;
+        }
+        details
+    }
+}
+
+/// One of the [`Stats::import_names`].
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ImportName {
+    pub module: String,
+    pub name: String,
+}
+
+/// One of the [`Stats::top_functions`].
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct FunctionSize {
+    /// Index in the function index space, which starts with the imports.
+    pub index: u32,
+    /// From the name section, or failing that an export.
+    pub name: Option<String>,
+    /// Size of the body, as in [`FuncSizeStats`].
+    pub size: usize,
+    /// Counted the same way as [`InstructionStats::total`].
+    pub instructions: usize,
+}
+
+/// Bodies over 100 KB are counted in [`FuncSizeStats::large`].
+pub const LARGE_FUNC_SIZE: usize = 100 * 1024;
+
+impl FuncSizeStats {
+    fn from_sizes(mut sizes: Vec<usize>) -> Self {
+        if sizes.is_empty() {
+            return Self::default();
+        }
+        sizes.sort_unstable();
+        // Nearest-rank percentiles.
+        let percentile = |p: usize| sizes[(sizes.len() * p).div_ceil(100).max(1) - 1];
+        FuncSizeStats {
+            min: sizes[0],
+            max: sizes[sizes.len() - 1],
+            mean: sizes.iter().sum::<usize>() as f64 / sizes.len() as f64,
+            median: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            large: sizes.iter().filter(|&&size| size > LARGE_FUNC_SIZE).count(),
+        }
+    }
+}
+
+/// Function types by number of parameters or results.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ArityStats {
+    pub zero: usize,
+    pub one: usize,
+    pub two: usize,
+    pub three: usize,
+    pub four: usize,
+    pub five_or_more: usize,
+}
+
+impl ArityStats {
+    fn add(&mut self, arity: usize) {
+        let count = match arity {
+            0 => &mut self.zero,
+            1 => &mut self.one,
+            2 => &mut self.two,
+            3 => &mut self.three,
+            4 => &mut self.four,
+            _ => &mut self.five_or_more,
+        };
+        *count += 1;
+    }
+
+    fn more_than_one(&self) -> usize {
+        self.two + self.three + self.four + self.five_or_more
+    }
+}
+
+/// Summary of the function types in the type section.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct SignatureStats {
+    pub params: ArityStats,
+    pub results: ArityStats,
+    /// Number of different function types, since the type section can
+    /// repeat one, e.g. in separate recursion groups.
+    pub distinct: usize,
+    /// The signature of the most imported and defined functions, e.g.
+    /// `(i32, i32) -> i32`, taking the first in the type section on a tie.
+    pub most_common: Option<String>,
+}
+
+impl SignatureStats {
+    /// Summarises `types`, given the type index of every function.
+    fn new(types: &[Option<FuncType>], funcs: impl Iterator<Item = u32>) -> Self {
+        let mut stats = SignatureStats::default();
+        let mut first_index = HashMap::new();
+        for (index, ty) in types.iter().enumerate() {
+            if let Some(ty) = ty {
+                stats.params.add(ty.params().len());
+                stats.results.add(ty.results().len());
+                first_index.entry(ty).or_insert(index);
+            }
+        }
+        stats.distinct = first_index.len();
+        let mut uses = HashMap::new();
+        for ty in funcs.filter_map(|index| types.get(index as usize)?.as_ref()) {
+            *uses.entry(ty).or_insert(0usize) += 1;
+        }
+        stats.most_common = uses
+            .into_iter()
+            .max_by_key(|&(ty, count)| (count, std::cmp::Reverse(first_index[ty])))
+            .map(|(ty, _)| signature(ty));
+        stats
+    }
+}
+
+/// Renders a function type as e.g. `(i32, i32) -> i32` or `() -> ()`.
+fn signature(ty: &FuncType) -> String {
+    let list = |types: &[ValType]| {
+        let types: Vec<_> = types.iter().map(ValType::to_string).collect();
+        format!("({})", types.join(", "))
+    };
+    let results = match ty.results() {
+        [result] => result.to_string(),
+        results => list(results),
+    };
+    format!("{} -> {}", list(ty.params()), results)
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ExternalStats {
+    pub funcs: usize,
+    pub memories: usize,
+    pub globals: usize,
+    pub tables: usize,
+    /// Exception tags, from the exception handling proposal.
+    pub tags: usize,
+    /// Kinds not covered above.
+    pub other: usize,
+}
+
+/// Version of the [`Stats`] layout, bumped whenever the JSON shape changes
+/// (fields added, removed, renamed or retyped) so that consumers can tell
+/// records from different versions apart. The current version's schema is
+/// kept in `schema/`, and [`schema`] returns it; `CHANGELOG.md` lists what
+/// each version changed.
+pub const SCHEMA_VERSION: u32 = 32;
+
+/// Returns the JSON Schema of [`Stats`] records for the current
+/// [`SCHEMA_VERSION`], e.g. to generate a table definition from.
+pub fn schema() -> serde_json::Value {
+    schemars::schema_for!(Stats).to_value()
+}
+
+/// How a .NET module runs the application's code.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DotnetMode {
+    /// The Mono runtime interpreting IL loaded separately.
+    Interpreter,
+    /// The application compiled ahead of time into the module.
+    Aot,
+}
+
+/// How a wasm-bindgen module has been packaged.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum RustPackaging {
+    /// Straight from rustc, with imports from `__wbindgen_placeholder__`.
+    Raw,
+    /// Post-processed by the wasm-bindgen CLI (or wasm-pack, which runs it),
+    /// with imports from `wbg` or a `./*_bg.js` file.
+    BindgenProcessed,
+}
+
+/// The newest graphics API a module's imports call.
+#[derive(
+    Serialize, Deserialize, JsonSchema, Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug,
+)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum GraphicsApi {
+    Webgl,
+    Webgl2,
+    Webgpu,
+}
+
+/// The module's start function.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct StartInfo {
+    /// Index in the function index space, which starts with the imports.
+    pub function: u32,
+    /// Whether the start function is an imported one.
+    pub imported: bool,
+}
+
+/// How the module expects to be started.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum Entrypoint {
+    /// Exports `_start`, to be run once like a program.
+    WasiCommand,
+    /// Exports `_initialize`, to be set up once and then have its exports
+    /// called.
+    WasiReactor,
+    /// Has neither, but has a start function that runs on instantiation.
+    Custom,
+}
+
+/// Which decoder a module's sections were read with.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Decoder {
+    /// wasmbin decoded every section it knows the id of.
+    #[default]
+    Wasmbin,
+    /// wasmbin rejected at least one section, which was read with wasmparser
+    /// instead. Only with the `wasmparser-fallback` feature.
+    Fallback,
+}
+
+/// Whether the input is a core module or a component-model component.
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum BinaryKind {
+    #[default]
+    Module,
+    Component,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ComponentSection {
+    pub kind: String,
+    pub size: usize,
+}
+
+/// Outline of a component; empty for core modules.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ComponentStats {
+    /// Core modules embedded in the component or its nested components.
+    pub core_modules: usize,
+    /// Top-level sections of the component, in order.
+    pub sections: Vec<ComponentSection>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProducerTool {
+    pub name: String,
+    pub version: String,
+}
+
+/// Toolchain metadata from the `producers` custom section.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ProducersInfo {
+    pub language: Vec<ProducerTool>,
+    #[serde(rename = "processed-by")]
+    pub processed_by: Vec<ProducerTool>,
+    pub sdk: Vec<ProducerTool>,
+}
+
+/// Dynamic linking requirements of an Emscripten side module, from the
+/// `dylink.0` (or legacy `dylink`) custom section.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct DylinkInfo {
+    pub memory_size: u32,
+    pub memory_alignment: u32,
+    pub table_size: u32,
+    pub table_alignment: u32,
+    /// Dynamic libraries that must be loaded first.
+    pub needed: Vec<String>,
+}
+
+/// Summary of the `name` custom section.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct NameStats {
+    pub module_name: bool,
+    pub functions: usize,
+    pub locals: usize,
+    /// Size of the name subsections, not including the section header.
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct CustomSection {
+    pub name: String,
+    /// Size of the section, including its header but not the section id.
+    pub size: usize,
+}
+
+/// The first error found by [`wasmparser::Validator`].
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ValidationError {
+    pub message: String,
+    /// Byte offset of the error within the module.
+    pub offset: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct UnknownSection {
+    pub id: u8,
+    /// Size of the section, including its header but not the section id.
+    pub size: usize,
+}
+
+/// Microseconds spent in each stage of the analysis, for
+/// [`Options::timings`].
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct Timings {
+    /// Reading the sections, apart from the function bodies.
+    pub decode: u64,
+    /// Walking the instructions of the function bodies.
+    pub instructions: u64,
+    /// Working out `size` and `func_sizes`.
+    pub sizes: u64,
+    /// Inferring the language and toolchain version.
+    pub language: u64,
+    /// Full validation, if [`Options::validate`] is set.
+    pub validation: u64,
+    /// The whole analysis, including the stages above.
+    pub total: u64,
+}
+
+/// Microseconds since `start`.
+fn micros_since(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX)
+}
+
+/// Debug payloads shipped with the module.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct DebugInfoStats {
+    /// Whether there are any `.debug_*` (DWARF) sections.
+    pub dwarf: bool,
+    /// Total size of the `.debug_*` sections.
+    pub dwarf_size: usize,
+    /// URL from the `sourceMappingURL` section.
+    pub source_map_url: Option<String>,
+    pub build_id: bool,
+    pub external_debug_info: bool,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema)]
+#[non_exhaustive]
+pub struct Stats {
+    /// The [`SCHEMA_VERSION`] this record was produced with.
+    #[schemars(extend("const" = SCHEMA_VERSION))]
+    pub schema_version: u32,
+    pub binary_kind: BinaryKind,
+    /// The version word from the header, which is 1 for every core module so
+    /// far.
+    pub wasm_version: u32,
+    /// Whether the stats could all be read with wasmbin, as they always were
+    /// before the fallback, or are partly wasmparser's. Components, of which
+    /// only the outer sections are measured, count as `wasmbin`.
+    pub decoder: Decoder,
+    /// Functions defined in the module, not counting imports; the same as
+    /// `funcs_defined`, which it predates.
+    pub funcs: usize,
+    /// Functions with a body in the code section.
+    pub funcs_defined: usize,
+    /// The whole function index space: imported and defined functions.
+    pub funcs_total: usize,
+    /// Function exports, as in `exports.funcs`.
+    pub funcs_exported: usize,
+    pub language: Language,
+    pub language_confidence: Confidence,
+    /// The imports, exports, names or sections that `language` was inferred
+    /// from, e.g. `import env.emscripten_asm_const_i`.
+    pub language_evidence: Vec<String>,
+    /// Every language whose heuristics matched, in order of priority;
+    /// `language` is the first of these.
+    pub language_candidates: Vec<Language>,
+    /// When no other heuristic matched, the number of function names in the
+    /// name section from each family of mangling schemes and prefixes:
+    /// `rust`, `go`, `emscripten_allocator`, `cpp` and `other`.
+    pub symbol_origins: Option<BTreeMap<String, usize>>,
+    /// For .NET modules, whether they are the interpreter or AOT compiled.
+    pub dotnet_mode: Option<DotnetMode>,
+    /// For Go modules, what they import from the Go runtime.
+    pub go_details: Option<GoStats>,
+    /// Version of the wasm-bindgen or Emscripten release that built the
+    /// module, if exactly one could be found.
+    pub toolchain_version: Option<String>,
+    /// For wasm-bindgen modules, whether the wasm-bindgen CLI has processed
+    /// them.
+    pub rust_packaging: Option<RustPackaging>,
+    /// The WASI version targeted by the imports: `preview1` or `preview2`.
+    pub wasi: Option<String>,
+    /// Number of imports from WASI modules.
+    pub wasi_imports: usize,
+    pub instr: InstructionStats,
+    pub size: SizeStats,
+    pub func_sizes: FuncSizeStats,
+    /// The largest function bodies, if [`Options::top_functions`] is set.
+    pub top_functions: Vec<FunctionSize>,
+    pub signatures: SignatureStats,
+    pub imports: ExternalStats,
+    /// Number of imports from each module namespace, e.g. `env` or `wbg`.
+    pub import_modules: BTreeMap<String, usize>,
+    /// The module and name of every import, if [`Options::import_names`] is
+    /// set.
+    pub import_names: Option<Vec<ImportName>>,
+    pub js_api_usage: JsApiStats,
+    /// The newest graphics API called by an imported function, going by its
+    /// name.
+    pub graphics: Option<GraphicsApi>,
+    /// Number of imported functions that belong to a graphics API.
+    pub graphics_imports: usize,
+    pub exports: ExternalStats,
+    pub export_details: ExportDetails,
+    /// Number of Emscripten `dynCall_*` exports, which call into the table.
+    pub dyncall_exports: usize,
+    pub memories: Vec<MemoryStats>,
+    pub threading: ThreadingStats,
+    pub data: DataStats,
+    pub entropy: EntropyStats,
+    /// How much of the data segments is text, if [`Options::scan_data`] is
+    /// set.
+    pub data_strings: Option<DataStrings>,
+    /// Files embedded in data segments, if [`Options::data_contents`] is set.
+    pub data_contents: Option<Vec<EmbeddedAsset>>,
+    /// Total `approx_size` of `data_contents`.
+    pub data_contents_bytes: Option<usize>,
+    pub tables: TableStats,
+    pub globals: GlobalStats,
+    /// Custom sections in the order they appear, including duplicates.
+    pub custom_sections: Vec<CustomSection>,
+    /// Sections whose id isn't known to either decoder, which are skipped.
+    pub unknown_sections: Vec<UnknownSection>,
+    /// Every section in file order: its kind, e.g. `type` or `data_count`,
+    /// `custom:` and the name for custom sections, or `unknown:` and the id.
+    pub section_order: Vec<String>,
+    /// Whether known sections are out of the order the spec requires, or a
+    /// section other than a custom one appears more than once.
+    pub nonstandard_section_order: bool,
+    /// Decoded contents of the `producers` section, if there is one.
+    pub producers: Option<ProducersInfo>,
+    /// Whether wasm-opt (Binaryen) has processed the module, according to the
+    /// producers section, or `None` without one. A `target_features` section
+    /// isn't enough by itself, since LLVM emits one too.
+    pub optimized_with_binaryen: Option<bool>,
+    /// Whether Binaryen's Asyncify transform has been applied, going by its
+    /// exports or the name of its state global.
+    pub asyncify: bool,
+    /// With Asyncify, roughly how many functions it instrumented: those that
+    /// use its state global, if that could be identified.
+    pub asyncify_instrumented_funcs: Option<usize>,
+    pub dylink: Option<DylinkInfo>,
+    /// Decoded summary of the `name` section, if there is one.
+    pub names: Option<NameStats>,
+    /// Well-known libraries linked into the module, such as `zlib` or
+    /// `sqlite`, going by its function names and exports.
+    pub detected_libraries: Vec<String>,
+    /// Whether the module has neither a name section nor DWARF debug info.
+    pub is_stripped: bool,
+    pub debug_info: DebugInfoStats,
+    /// Features from the `target_features` section, e.g. `+simd128`.
+    pub target_features: Vec<String>,
+    /// Features declared with `+` in `target_features` whose proposal
+    /// counter is zero.
+    pub declared_but_unused_features: Vec<String>,
+    /// Whether there's a start function; see [`Stats::start`] for details.
+    pub has_start: bool,
+    pub start: Option<StartInfo>,
+    pub entrypoint: Option<Entrypoint>,
+    pub call_graph: CallGraphStats,
+    pub component: ComponentStats,
+    /// Whether the module passed validation, if [`Options::validate`] is set.
+    pub valid: Option<bool>,
+    /// Why validation failed.
+    pub validation_error: Option<ValidationError>,
+    /// Sections (or functions) that failed to decode and were left out of the
+    /// other stats, each with the section kind and byte offset.
+    pub decode_errors: Vec<String>,
+    /// How long each stage of the analysis took, if [`Options::timings`] is
+    /// set. Left out of the record entirely otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+}
+
+/// Decodes the `producers` section. Fields other than `language`,
+/// `processed-by` and `sdk` are ignored.
+fn read_producers(section: &CustomSectionReader) -> wasmparser::Result<ProducersInfo> {
+    let mut info = ProducersInfo::default();
+    for field in ProducersSectionReader::new(section.data_reader())? {
+        let field = field?;
+        let tools = match field.name {
+            "language" => &mut info.language,
+            "processed-by" => &mut info.processed_by,
+            "sdk" => &mut info.sdk,
+            _ => continue,
+        };
+        for value in field.values {
+            let value = value?;
+            tools.push(ProducerTool {
+                name: value.name.to_owned(),
+                version: value.version.to_owned(),
+            });
+        }
+    }
+    Ok(info)
+}
+
+/// Reads the `target_features` section, keeping the `+`, `-` or `=` prefix.
+fn read_target_features(section: &CustomSectionReader) -> wasmparser::Result<Vec<String>> {
+    let mut reader = section.data_reader();
+    let mut features = Vec::new();
+    for _ in 0..reader.read_var_u32()? {
+        let prefix = char::from(reader.read_u8()?);
+        features.push(format!("{}{}", prefix, reader.read_string()?));
+    }
+    Ok(features)
+}
+
+/// The proposal counter that shows whether a `target_features` feature is
+/// used, for the features that we have a counter for.
+fn feature_usage(proposals: &ProposalStats, feature: &str) -> Option<usize> {
+    Some(match feature {
+        "atomics" => proposals.atomics,
+        "bulk-memory" => proposals.bulk,
+        "exception-handling" => proposals.exception_handling,
+        "extended-const" => proposals.extended_const,
+        "gc" => proposals.gc,
+        "memory64" => proposals.memory64,
+        "multimemory" => proposals.multi_memory,
+        "multivalue" => proposals.multi_value,
+        "mutable-globals" => proposals.mutable_externals,
+        "nontrapping-fptoint" => proposals.non_trapping_conv,
+        "reference-types" => proposals.ref_types,
+        "relaxed-simd" => proposals.relaxed_simd,
+        "sign-ext" => proposals.sign_extend,
+        "simd128" => proposals.simd,
+        "tail-call" => proposals.tail_calls,
+        "typed-function-references" => proposals.function_references,
+        _ => return None,
+    })
+}
+
+/// Decodes a `dylink.0` section, or a legacy `dylink` section, which has the
+/// same fields without the subsection framing.
+fn read_dylink(section: &CustomSectionReader) -> wasmparser::Result<DylinkInfo> {
+    let mut info = DylinkInfo::default();
+    if section.name() == "dylink" {
+        let mut reader = section.data_reader();
+        info.memory_size = reader.read_var_u32()?;
+        info.memory_alignment = reader.read_var_u32()?;
+        info.table_size = reader.read_var_u32()?;
+        info.table_alignment = reader.read_var_u32()?;
+        for _ in 0..reader.read_var_u32()? {
+            info.needed.push(reader.read_string()?.to_owned());
+        }
+        return Ok(info);
+    }
+    for subsection in Dylink0SectionReader::new(section.data_reader()) {
+        match subsection? {
+            Dylink0Subsection::MemInfo(mem_info) => {
+                info.memory_size = mem_info.memory_size;
+                info.memory_alignment = mem_info.memory_alignment;
+                info.table_size = mem_info.table_size;
+                info.table_alignment = mem_info.table_alignment;
+            }
+            Dylink0Subsection::Needed(needed) => {
+                info.needed.extend(needed.into_iter().map(str::to_owned));
+            }
+            _ => {}
+        }
+    }
+    Ok(info)
+}
+
+/// Counts the names in the `name` section, collecting the function names.
+fn read_names<'a>(
+    section: &CustomSectionReader<'a>,
+    function_names: &mut Vec<(u32, &'a str)>,
+    global_names: &mut Vec<(u32, &'a str)>,
+) -> wasmparser::Result<NameStats> {
+    let mut stats = NameStats {
+        size: section.data().len(),
+        ..Default::default()
+    };
+    for name in NameSectionReader::new(section.data_reader()) {
+        match name? {
+            Name::Module { .. } => stats.module_name = true,
+            Name::Function(names) => {
+                for naming in names {
+                    let naming = naming?;
+                    function_names.push((naming.index, naming.name));
+                    stats.functions += 1;
+                }
+            }
+            Name::Global(names) => {
+                for naming in names {
+                    let naming = naming?;
+                    global_names.push((naming.index, naming.name));
+                }
+            }
+            Name::Local(funcs) => {
+                for func in funcs {
+                    stats.locals += func?.names.collect::<wasmparser::Result<Vec<_>>>()?.len();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(stats)
+}
+
+/// Whether a value type is a non-nullable reference, which is only available
+/// with typed function references.
+fn is_non_nullable(ty: &ValType) -> bool {
+    matches!(ty, ValType::Ref(ty) if !ty.is_nullable())
+}
+
+/// Whether a type definition mentions a non-nullable reference.
+fn has_non_nullable(ty: &CompositeInnerType) -> bool {
+    match ty {
+        CompositeInnerType::Func(ty) => ty.params().iter().chain(ty.results()).any(is_non_nullable),
+        CompositeInnerType::Array(ty) => is_non_nullable(&ty.0.element_type.unpack()),
+        CompositeInnerType::Struct(ty) => ty
+            .fields
+            .iter()
+            .any(|field| is_non_nullable(&field.element_type.unpack())),
+        _ => false,
+    }
+}
+
+/// Reads all the items in a section with wasmparser.
+fn read_items<'a, T: FromReader<'a>>(raw: &sections::RawSection<'a>) -> wasmparser::Result<Vec<T>> {
+    SectionLimited::new(raw.reader())?.into_iter().collect()
+}
+
+/// Name of the proposal an operator was introduced by, as grouped by
+/// wasmparser (e.g. `mvp`, `simd` or `legacy_exceptions`).
+fn proposal_of(op: &Operator) -> &'static str {
+    macro_rules! define_proposal_of {
+        ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*))*) => {
+            match op {
+                $(Operator::$op { .. } => stringify!($proposal),)*
+                _ => "unknown",
+            }
+        };
+    }
+    wasmparser::for_each_operator!(define_proposal_of)
+}
+
+/// Whether a block type needs the multi-value proposal, i.e. refers to a
+/// function type with parameters or more than one result. Types that can't
+/// be found are left for validation to complain about.
+fn is_multi_value_block(ty: &BlockType, types: &[Option<FuncType>]) -> bool {
+    match ty {
+        BlockType::FuncType(index) => types
+            .get(*index as usize)
+            .and_then(Option::as_ref)
+            .is_some_and(|ty| !ty.params().is_empty() || ty.results().len() > 1),
+        BlockType::Empty | BlockType::Type(_) => false,
+    }
+}
+
+/// The memory immediate of a load, store or atomic operator.
+#[allow(unused_variables)]
+fn memarg_of<'a>(op: &'a Operator) -> Option<&'a MemArg> {
+    // Picks out the `memarg` argument, if the operator has one. Each name is
+    // passed twice so that the binding from the pattern can be returned.
+    macro_rules! find_memarg {
+        (memarg $memarg:ident $($rest:ident)*) => { Some($memarg) };
+        ($other:ident $_other:ident $($rest:ident)*) => { find_memarg!($($rest)*) };
+        () => { None };
+    }
+    macro_rules! define_memarg_of {
+        ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident ($($ann:tt)*))*) => {
+            match op {
+                $(Operator::$op $({ $($arg),* })? => find_memarg!($($($arg $arg)*)?),)*
+                _ => None,
+            }
+        };
+    }
+    wasmparser::for_each_operator!(define_memarg_of)
+}
+
+/// Reads the operators of a single function body, not including the `end`
+/// that closes the body itself, along with the encoded size of each.
+fn read_operators<'a>(
+    body: &'a [u8],
+    offset: usize,
+    locals: &mut LocalStats,
+) -> wasmparser::Result<(Vec<Operator<'a>>, Vec<usize>)> {
+    let end = (offset + body.len()) as u64;
+    let body = FunctionBody::new(BinaryReader::new(body, offset as u64));
+    let mut declared = 0usize;
+    for group in body.get_locals_reader()? {
+        let (count, ty) = group?;
+        let count = count as usize;
+        let by_type = match ty {
+            ValType::I32 => &mut locals.i32,
+            ValType::I64 => &mut locals.i64,
+            ValType::F32 => &mut locals.f32,
+            ValType::F64 => &mut locals.f64,
+            ValType::V128 => &mut locals.v128,
+            ValType::Ref(_) => &mut locals.refs,
+        };
+        *by_type = by_type.saturating_add(count);
+        declared = declared.saturating_add(count);
+    }
+    locals.total = locals.total.saturating_add(declared);
+    locals.max_per_function = locals.max_per_function.max(declared);
+    let (mut ops, offsets): (Vec<_>, Vec<_>) = body
+        .get_operators_reader()?
+        .into_iter_with_offsets()
+        .collect::<wasmparser::Result<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    // Each operator runs up to the start of the next one.
+    let mut sizes: Vec<_> = offsets
+        .iter()
+        .zip(offsets.iter().skip(1).chain([&end]))
+        .map(|(start, next)| (next - start) as usize)
+        .collect();
+    if let Some(Operator::End) = ops.last() {
+        ops.pop();
+        sizes.pop();
+    }
+    Ok((ops, sizes))
+}
+
+/// Whether an init expression needs the extended-const proposal, i.e. is
+/// anything more than a single constant, `ref.null`, `ref.func` or
+/// `global.get`.
+fn is_extended_const(expr: &ConstExpr) -> wasmparser::Result<bool> {
+    use Operator as O;
+
+    let ops = expr
+        .get_operators_reader()
+        .into_iter()
+        .collect::<wasmparser::Result<Vec<_>>>()?;
+    Ok(!matches!(
+        ops[..],
+        [
+            O::I32Const { .. }
+                | O::I64Const { .. }
+                | O::F32Const { .. }
+                | O::F64Const { .. }
+                | O::V128Const { .. }
+                | O::RefNull { .. }
+                | O::RefFunc { .. }
+                | O::GlobalGet { .. },
+            O::End
+        ]
+    ))
+}
+
+/// The number of element segments that need the extended-const proposal,
+/// and the functions they reference, or the first thing in the section that
+/// fails to decode.
+fn element_refs(section: &[Element]) -> wasmparser::Result<(usize, Vec<u32>)> {
+    let mut extended = 0;
+    let mut refs = Vec::new();
+    for element in section {
+        if let ElementKind::Active { offset_expr, .. } = &element.kind {
+            extended += usize::from(is_extended_const(offset_expr)?);
+        }
+        match &element.items {
+            ElementItems::Functions(indices) => {
+                for index in indices.clone() {
+                    refs.push(index?);
+                }
+            }
+            ElementItems::Expressions(_, items) => {
+                for item in items.clone() {
+                    let item = item?;
+                    extended += usize::from(is_extended_const(&item)?);
+                    for op in item.get_operators_reader() {
+                        if let Operator::RefFunc { function_index } = op? {
+                            refs.push(function_index);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok((extended, refs))
+}
+
+/// Counts instructions across the function bodies in the code section,
+/// adding each body that decodes to `bodies`.
+///
+/// Bodies that fail to decode are skipped and described in `decode_errors`.
+fn get_instruction_stats(
+    code: &sections::RawSection,
+    stats: &mut InstructionStats,
+    bodies: &mut Vec<FunctionSize>,
+    call_graph: &mut CallGraph,
+    globals: &mut GlobalUses,
+    types: &[Option<FuncType>],
+    decode_errors: &mut Vec<String>,
+) {
+    use Operator as O;
+
+    let payload = code.payload;
+    let mut pos = 0;
+    let count = match sections::read_u32(payload, &mut pos) {
+        Some(count) => count,
+        None => {
+            decode_errors.push("invalid function count".to_owned());
+            return;
+        }
+    };
+    let (mut depth_sum, mut decoded) = (0usize, 0usize);
+    let mut br_table_targets = 0;
+    let mut opcodes = stats.opcodes.is_some().then(HashMap::new);
+    for index in 0..count {
+        let func_offset = code.payload_offset + pos;
+        let body = sections::read_u32(payload, &mut pos)
+            .and_then(|len| payload.get(pos..pos.checked_add(len as usize)?));
+        let body = match body {
+            Some(body) => body,
+            None => {
+                decode_errors.push(format!(
+                    "function {} at offset {}: body runs past the end of the section",
+                    index, func_offset
+                ));
+                break;
+            }
+        };
+        let (ops, sizes) = match read_operators(body, code.payload_offset + pos, &mut stats.locals)
+        {
+            Ok(read) => read,
+            Err(e) => {
+                decode_errors.push(format!(
+                    "function {} at offset {}: {}",
+                    index, func_offset, e
+                ));
+                pos += body.len();
+                continue;
+            }
+        };
+        bodies.push(FunctionSize {
+            index,
+            size: body.len(),
+            instructions: ops.len(),
+            ..Default::default()
+        });
+        pos += body.len();
+        stats.total += ops.len();
+        for (i, &size) in ops.iter().zip(&sizes) {
+            if let Some(opcodes) = &mut opcodes {
+                *opcodes.entry(visit_name_of(i)).or_insert(0) += 1;
+            }
+            match operand_family(i) {
+                Some(OperandFamily::Int) => stats.int_ops += 1,
+                Some(OperandFamily::Float) => stats.float_ops += 1,
+                Some(OperandFamily::Simd) => stats.simd_ops += 1,
+                None => {}
+            }
+            let memarg = memarg_of(i);
+            if let Some(memarg) = memarg {
+                stats.mem_access.add(memarg);
+                // Offsets that don't fit in 32 bits are only valid for 64-bit
+                // memories.
+                if memarg.offset > u64::from(u32::MAX) {
+                    stats.proposals.memory64 += 1;
+                }
+            }
+            let uses_other_memory = match i {
+                O::MemorySize { mem }
+                | O::MemoryGrow { mem }
+                | O::MemoryInit { mem, .. }
+                | O::MemoryFill { mem }
+                | O::MemoryDiscard { mem } => *mem != 0,
+                O::MemoryCopy { dst_mem, src_mem } => *dst_mem != 0 || *src_mem != 0,
+                _ => memarg.is_some_and(|memarg| memarg.memory != 0),
+            };
+            if uses_other_memory {
+                stats.proposals.multi_memory += 1;
+            }
+            match i {
+                O::Block { blockty } | O::Loop { blockty } | O::If { blockty }
+                    if is_multi_value_block(blockty, types) =>
+                {
+                    stats.proposals.multi_value_blocks += 1;
+                }
+                O::BrTable { targets } => {
+                    let labels = targets.len() as usize + 1;
+                    stats.br_table.count += 1;
+                    stats.br_table.max_targets = stats.br_table.max_targets.max(labels);
+                    br_table_targets += labels;
+                }
+                _ => {}
+            }
+            let categories = categorize(i, &mut stats.proposals, &mut stats.atomics);
+            for &category in categories {
+                *stats.categories.count_mut(category) += 1;
+                *stats.category_bytes.count_mut(category) += size;
+            }
+        }
+        call_graph.add_body(index, &ops);
+        globals.add_body(index, &ops);
+        let depth = nesting_depth(&ops);
+        stats.max_nesting_depth = stats.max_nesting_depth.max(depth);
+        depth_sum += depth;
+        decoded += 1;
+    }
+    if decoded > 0 {
+        stats.mean_nesting_depth = depth_sum as f64 / decoded as f64;
+    }
+    if stats.br_table.count > 0 {
+        stats.br_table.mean_targets = br_table_targets as f64 / stats.br_table.count as f64;
+    }
+    if let Some(opcodes) = opcodes {
+        stats.opcodes = Some(OpcodeCounts::from_visit_names(opcodes));
+    }
+}
+
+/// The categories an instruction is counted under: usually just one, but tail
+/// calls count as both control flow and calls. Also counts the proposal the
+/// instruction belongs to.
+fn categorize(
+    i: &Operator,
+    proposals: &mut ProposalStats,
+    atomics: &mut AtomicDetail,
+) -> &'static [InstrCategory] {
+    use InstrCategory as C;
+    use Operator as O;
+
+    match proposal_of(i) {
+        "simd" => {
+            proposals.simd += 1;
+            match i {
+                O::V128Load { .. }
+                | O::V128Load8x8S { .. }
+                | O::V128Load8x8U { .. }
+                | O::V128Load16x4S { .. }
+                | O::V128Load16x4U { .. }
+                | O::V128Load32x2S { .. }
+                | O::V128Load32x2U { .. }
+                | O::V128Load8Splat { .. }
+                | O::V128Load16Splat { .. }
+                | O::V128Load32Splat { .. }
+                | O::V128Load64Splat { .. }
+                | O::V128Load32Zero { .. }
+                | O::V128Load64Zero { .. }
+                | O::V128Store { .. }
+                | O::V128Load8Lane { .. }
+                | O::V128Load16Lane { .. }
+                | O::V128Load32Lane { .. }
+                | O::V128Load64Lane { .. }
+                | O::V128Store8Lane { .. }
+                | O::V128Store16Lane { .. }
+                | O::V128Store32Lane { .. }
+                | O::V128Store64Lane { .. } => &[C::LoadStore],
+                O::V128Const { .. } => &[C::Constants],
+                _ => &[C::Other],
+            }
+        }
+        // None of the relaxed SIMD instructions access memory.
+        "relaxed_simd" => {
+            proposals.relaxed_simd += 1;
+            &[C::Other]
+        }
+        "threads" => {
+            proposals.atomics += 1;
+            atomics.instructions += 1;
+            match i {
+                O::MemoryAtomicNotify { .. }
+                | O::MemoryAtomicWait32 { .. }
+                | O::MemoryAtomicWait64 { .. } => &[C::WaitNotify],
+                O::I32AtomicLoad { .. }
+                | O::I64AtomicLoad { .. }
+                | O::I32AtomicLoad8U { .. }
+                | O::I32AtomicLoad16U { .. }
+                | O::I64AtomicLoad8U { .. }
+                | O::I64AtomicLoad16U { .. }
+                | O::I64AtomicLoad32U { .. }
+                | O::I32AtomicStore { .. }
+                | O::I64AtomicStore { .. }
+                | O::I32AtomicStore8 { .. }
+                | O::I32AtomicStore16 { .. }
+                | O::I64AtomicStore8 { .. }
+                | O::I64AtomicStore16 { .. }
+                | O::I64AtomicStore32 { .. } => &[C::LoadStore],
+                O::AtomicFence => {
+                    atomics.fence += 1;
+                    &[C::Other]
+                }
+                // Everything else is a read-modify-write.
+                _ => {
+                    atomics.rmw += 1;
+                    &[C::Other]
+                }
+            }
+        }
+        "gc" => {
+            proposals.gc += 1;
+            &[C::Gc]
+        }
+        "function_references" => {
+            proposals.function_references += 1;
+            match i {
+                O::CallRef { .. } => &[C::IndirectCalls],
+                O::ReturnCallRef { .. } => {
+                    proposals.tail_calls += 1;
+                    &[C::ControlFlow, C::IndirectCalls]
+                }
+                O::BrOnNull { .. } | O::BrOnNonNull { .. } => &[C::ControlFlow],
+                _ => &[C::Other],
+            }
+        }
+        "exceptions" | "legacy_exceptions" => {
+            proposals.exception_handling += 1;
+            &[C::ControlFlow]
+        }
+        _ => match i {
+            O::Block { .. }
+            | O::Loop { .. }
+            | O::If { .. }
+            | O::Else
+            | O::End
+            | O::Unreachable
+            | O::Br { .. }
+            | O::BrIf { .. }
+            | O::BrTable { .. }
+            | O::Return
+            | O::Select
+            | O::Nop
+            | O::Drop => &[C::ControlFlow],
+            // The typed encoding came with reference types.
+            O::TypedSelect { .. } => {
+                proposals.ref_types += 1;
+                &[C::ControlFlow]
+            }
+            O::RefFunc { .. } | O::RefIsNull | O::RefNull { .. } => {
+                proposals.ref_types += 1;
+                match i {
+                    O::RefIsNull => &[C::Other],
+                    _ => &[C::Constants],
+                }
+            }
+            O::MemoryInit { .. }
+            | O::MemoryCopy { .. }
+            | O::MemoryFill { .. }
+            | O::DataDrop { .. } => {
+                proposals.bulk += 1;
+                &[C::Memory]
+            }
+            O::TableInit { .. }
+            | O::TableCopy { .. }
+            | O::TableFill { .. }
+            | O::ElemDrop { .. } => {
+                proposals.bulk += 1;
+                &[C::Table]
+            }
+            O::TableGrow { .. } | O::TableSize { .. } => {
+                proposals.ref_types += 1;
+                &[C::Table]
+            }
+            O::I32TruncSatF32S
+            | O::I32TruncSatF32U
+            | O::I32TruncSatF64S
+            | O::I32TruncSatF64U
+            | O::I64TruncSatF32S
+            | O::I64TruncSatF32U
+            | O::I64TruncSatF64S
+            | O::I64TruncSatF64U => {
+                proposals.non_trapping_conv += 1;
+                &[C::Conversion]
+            }
+            O::Call { .. } => &[C::DirectCalls],
+            O::CallIndirect { .. } => &[C::IndirectCalls],
+            O::ReturnCall { .. } => {
+                proposals.tail_calls += 1;
+                &[C::ControlFlow, C::DirectCalls]
+            }
+            O::ReturnCallIndirect { .. } => {
+                proposals.tail_calls += 1;
+                &[C::ControlFlow, C::IndirectCalls]
+            }
+            O::I32Const { .. } | O::I64Const { .. } | O::F32Const { .. } | O::F64Const { .. } => {
+                &[C::Constants]
+            }
+            O::LocalGet { .. } | O::LocalSet { .. } | O::LocalTee { .. } => &[C::LocalVar],
+            O::GlobalGet { .. } | O::GlobalSet { .. } => &[C::GlobalVar],
+            O::TableGet { .. } | O::TableSet { .. } => {
+                proposals.ref_types += 1;
+                &[C::Table]
+            }
+            O::I32Load { .. }
+            | O::I64Load { .. }
+            | O::F32Load { .. }
+            | O::F64Load { .. }
+            | O::I32Load8S { .. }
+            | O::I32Load8U { .. }
+            | O::I32Load16S { .. }
+            | O::I32Load16U { .. }
+            | O::I64Load8S { .. }
+            | O::I64Load8U { .. }
+            | O::I64Load16S { .. }
+            | O::I64Load16U { .. }
+            | O::I64Load32S { .. }
+            | O::I64Load32U { .. }
+            | O::I32Store { .. }
+            | O::I64Store { .. }
+            | O::F32Store { .. }
+            | O::F64Store { .. }
+            | O::I32Store8 { .. }
+            | O::I32Store16 { .. }
+            | O::I64Store8 { .. }
+            | O::I64Store16 { .. }
+            | O::I64Store32 { .. } => &[C::LoadStore],
+            O::MemorySize { .. } | O::MemoryGrow { .. } => &[C::Memory],
+            O::I32Extend8S
+            | O::I32Extend16S
+            | O::I64Extend8S
+            | O::I64Extend16S
+            | O::I64Extend32S => {
+                proposals.sign_extend += 1;
+                &[C::Conversion]
+            }
+            O::I32Clz
+            | O::I32Ctz
+            | O::I32Popcnt
+            | O::I32Add
+            | O::I32Sub
+            | O::I32Mul
+            | O::I32DivS
+            | O::I32DivU
+            | O::I32RemS
+            | O::I32RemU
+            | O::I32And
+            | O::I32Or
+            | O::I32Xor
+            | O::I32Shl
+            | O::I32ShrS
+            | O::I32ShrU
+            | O::I32Rotl
+            | O::I32Rotr
+            | O::I64Clz
+            | O::I64Ctz
+            | O::I64Popcnt
+            | O::I64Add
+            | O::I64Sub
+            | O::I64Mul
+            | O::I64DivS
+            | O::I64DivU
+            | O::I64RemS
+            | O::I64RemU
+            | O::I64And
+            | O::I64Or
+            | O::I64Xor
+            | O::I64Shl
+            | O::I64ShrS
+            | O::I64ShrU
+            | O::I64Rotl
+            | O::I64Rotr
+            | O::F32Abs
+            | O::F32Neg
+            | O::F32Ceil
+            | O::F32Floor
+            | O::F32Trunc
+            | O::F32Nearest
+            | O::F32Sqrt
+            | O::F32Add
+            | O::F32Sub
+            | O::F32Mul
+            | O::F32Div
+            | O::F32Min
+            | O::F32Max
+            | O::F32Copysign
+            | O::F64Abs
+            | O::F64Neg
+            | O::F64Ceil
+            | O::F64Floor
+            | O::F64Trunc
+            | O::F64Nearest
+            | O::F64Sqrt
+            | O::F64Add
+            | O::F64Sub
+            | O::F64Mul
+            | O::F64Div
+            | O::F64Min
+            | O::F64Max
+            | O::F64Copysign => &[C::Arithmetic],
+            O::I32Eqz
+            | O::I32Eq
+            | O::I32Ne
+            | O::I32LtS
+            | O::I32LtU
+            | O::I32GtS
+            | O::I32GtU
+            | O::I32LeS
+            | O::I32LeU
+            | O::I32GeS
+            | O::I32GeU
+            | O::I64Eqz
+            | O::I64Eq
+            | O::I64Ne
+            | O::I64LtS
+            | O::I64LtU
+            | O::I64GtS
+            | O::I64GtU
+            | O::I64LeS
+            | O::I64LeU
+            | O::I64GeS
+            | O::I64GeU
+            | O::F32Eq
+            | O::F32Ne
+            | O::F32Lt
+            | O::F32Gt
+            | O::F32Le
+            | O::F32Ge
+            | O::F64Eq
+            | O::F64Ne
+            | O::F64Lt
+            | O::F64Gt
+            | O::F64Le
+            | O::F64Ge => &[C::Comparison],
+            O::I32WrapI64
+            | O::I32TruncF32S
+            | O::I32TruncF32U
+            | O::I32TruncF64S
+            | O::I32TruncF64U
+            | O::I64ExtendI32S
+            | O::I64ExtendI32U
+            | O::I64TruncF32S
+            | O::I64TruncF32U
+            | O::I64TruncF64S
+            | O::I64TruncF64U
+            | O::F32ConvertI32S
+            | O::F32ConvertI32U
+            | O::F32ConvertI64S
+            | O::F32ConvertI64U
+            | O::F32DemoteF64
+            | O::F64ConvertI32S
+            | O::F64ConvertI32U
+            | O::F64ConvertI64S
+            | O::F64ConvertI64U
+            | O::F64PromoteF32
+            | O::I32ReinterpretF32
+            | O::I64ReinterpretF64
+            | O::F32ReinterpretI32
+            | O::F64ReinterpretI64 => &[C::Conversion],
+            _ => &[C::Other],
+        },
+    }
+}
+
+/// Deepest block nesting in a function body, given its operators without the
+/// final `end`. The body counts as the first block.
+fn nesting_depth(ops: &[Operator]) -> usize {
+    use Operator as O;
+
+    let mut depth = 1usize;
+    let mut max = depth;
+    for op in ops {
+        match op {
+            O::Block { .. }
+            | O::Loop { .. }
+            | O::If { .. }
+            | O::Try { .. }
+            | O::TryTable { .. } => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            // `delegate` ends a legacy `try` block in place of `end`.
+            O::End | O::Delegate { .. } => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+macro_rules! get_external_stats {
+    ($externals:expr, $field:ident, $ns:path) => {{
+        use $ns::*;
+
+        let mut stats = ExternalStats::default();
+
+        for external in $externals {
+            match external.$field {
+                Func { .. } | FuncExact { .. } => stats.funcs += 1,
+                Global { .. } => stats.globals += 1,
+                Memory { .. } => stats.memories += 1,
+                Table { .. } => stats.tables += 1,
+                Tag { .. } => stats.tags += 1,
+                // Kinds added by future proposals, once wasmparser knows them.
+                #[allow(unreachable_patterns)]
+                _ => stats.other += 1,
+            }
+        }
+
+        stats
+    }};
+}
+
+struct MaybeExternal<T> {
+    pub value: T,
+    pub is_external: bool,
+}
+
+impl<T> MaybeExternal<T> {
+    fn external(self) -> Option<T> {
+        if self.is_external {
+            Some(self.value)
+        } else {
+            None
+        }
+    }
+}
+
+/// The WASI version that an import module belongs to, if any.
+fn wasi_version(module: &str) -> Option<&'static str> {
+    match module {
+        "wasi_snapshot_preview1" | "wasi_unstable" => Some("preview1"),
+        _ if module.starts_with("wasi:") => Some("preview2"),
+        _ => None,
+    }
+}
+
+/// The parts of a module that are kept around after decoding, for use once
+/// all the sections have been seen.
+#[derive(Default)]
+struct ModuleInfo<'a> {
+    imports: Vec<Import<'a>>,
+    exports: Vec<Export<'a>>,
+    /// Types by index, or `None` for types other than function types.
+    types: Vec<Option<FuncType>>,
+    /// Function names from the name section, by function index.
+    function_names: Vec<(u32, &'a str)>,
+    /// Global names from the name section, by global index.
+    global_names: Vec<(u32, &'a str)>,
+    /// Contents of the data segments, if [`Options::scan_data`] is set,
+    /// truncated to its limit.
+    data: Vec<&'a [u8]>,
+    /// Contents of custom sections that aren't otherwise recognised.
+    custom: Vec<&'a [u8]>,
+}
+
+impl ModuleInfo<'_> {
+    /// A function's name from the name section, or failing that an export.
+    fn function_name(&self, index: u32) -> Option<&str> {
+        let named = self.function_names.iter().find(|(i, _)| *i == index);
+        let exported = || {
+            self.exports.iter().find(|e| {
+                matches!(e.kind, ExternalKind::Func | ExternalKind::FuncExact) && e.index == index
+            })
+        };
+        named
+            .map(|(_, name)| *name)
+            .or_else(|| exported().map(|e| e.name))
+    }
+
+    /// The signature of an imported function, if it could be decoded.
+    fn import_type(&self, import: &Import) -> Option<&FuncType> {
+        match import.ty {
+            TypeRef::Func(type_id) | TypeRef::FuncExact(type_id) => {
+                self.types.get(type_id as usize)?.as_ref()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Optional, more expensive parts of the analysis.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Options {
+    /// Search up to this many bytes of each data segment for strings that
+    /// identify the source language, and measure how much of them is text
+    /// in [`Stats::data_strings`]. Off by default, since it keeps the
+    /// segments around until the whole module has been decoded.
+    pub scan_data: Option<usize>,
+    /// Report this many of the largest functions in [`Stats::top_functions`].
+    pub top_functions: usize,
+    /// Count every instruction in [`InstructionStats::opcodes`].
+    pub opcodes: bool,
+    /// List every import in [`Stats::import_names`].
+    pub import_names: bool,
+    /// List up to this many export names in [`ExportDetails::names`].
+    pub export_names: usize,
+    /// Look for files embedded in data segments, starting in up to this many
+    /// bytes of each, as reported in [`Stats::data_contents`].
+    pub data_contents: Option<usize>,
+    /// Run full validation, as reported in [`Stats::valid`]. Off by default,
+    /// since it takes about as long as the rest of the analysis.
+    pub validate: bool,
+    /// Time each stage of the analysis, as reported in [`Stats::timings`].
+    pub timings: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scan_data: None,
+            top_functions: 0,
+            opcodes: false,
+            import_names: false,
+            export_names: DEFAULT_EXPORT_NAMES,
+            data_contents: None,
+            validate: false,
+            timings: false,
+        }
+    }
+}
+
+/// Why an input was rejected before any of its sections were looked at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderError {
+    /// The input is this many bytes long, too short for the header.
+    Truncated(usize),
+    /// The input doesn't start with `\0asm`.
+    InvalidMagic,
+}
+
+impl std::fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HeaderError::Truncated(len) => write!(
+                f,
+                "input is {} bytes, too short for the {}-byte header",
+                len,
+                sections::HEADER_SIZE
+            ),
+            HeaderError::InvalidMagic => f.write_str("invalid magic number"),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// Decodes a WebAssembly module and collects statistics about it.
+///
+/// Only a bad or missing header is fatal, returning a [`HeaderError`]:
+/// sections that fail to decode, or are cut short, are described in
+/// [`Stats::decode_errors`] and the stats are collected from the rest of the
+/// module. Versions other than 1 are read as if they were 1, and sections
+/// with unknown ids are skipped. Sections that wasmbin can't decode count as
+/// failing to decode, unless the `wasmparser-fallback` feature is enabled,
+/// in which case they're read anyway and [`Stats::decoder`] is
+/// [`Decoder::Fallback`].
+///
+/// Components are recognised by their header, and only their outer sections
+/// are measured, in [`Stats::component`].
+pub fn get_stats(wasm: &[u8]) -> Result<Stats> {
+    get_stats_with(wasm, &Options::default())
+}
+
+/// Like [`get_stats`], with the given [`Options`].
+pub fn get_stats_with(wasm: &[u8], options: &Options) -> Result<Stats> {
+    let start = Instant::now();
+    let mut timings = Timings::default();
+    if component::is_component(wasm) {
+        let mut stats = component::get_stats(wasm);
+        timings.decode = micros_since(start);
+        if options.validate {
+            let start = Instant::now();
+            validate(wasm, &mut stats);
+            timings.validation = micros_since(start);
+        }
+        if options.timings {
+            timings.total = micros_since(start);
+            stats.timings = Some(timings);
+        }
+        return Ok(stats);
+    }
+    let wasm_version = sections::version(wasm)?;
+    let mut stats = Stats {
+        schema_version: SCHEMA_VERSION,
+        wasm_version,
+        size: SizeStats {
+            total: wasm.len(),
+            ..Default::default()
+        },
+        import_names: options.import_names.then(Vec::new),
+        data_contents: options.data_contents.map(|_| Vec::new()),
+        ..Default::default()
+    };
+    let (raw_sections, truncated) = sections::split(wasm);
+    stats.section_order = raw_sections.iter().map(|raw| raw.label()).collect();
+    stats.nonstandard_section_order = !sections::canonical_order(&raw_sections);
+
+    // Unwraps the decoded contents of a section, or records the error and
+    // moves on to the next section.
+    macro_rules! contents {
+        ($raw:expr, $result:expr) => {
+            match $result {
+                Ok(contents) => contents,
+                Err(e) => {
+                    stats.decode_errors.push($raw.error(e));
+                    continue;
+                }
+            }
+        };
+    }
+
+    let mut module = ModuleInfo::default();
+    let mut bodies = Vec::new();
+    let mut call_graph = CallGraph::default();
+    let mut global_uses = GlobalUses::default();
+    let mut global_types = Vec::new();
+    let mut data_bytes = ByteHistogram::default();
+    let mut func_types = Vec::new();
+    let decode = Instant::now();
+    // Sections whose encoding has been extended by newer proposals are read
+    // with wasmparser, the rest with wasmbin.
+    for raw in &raw_sections {
+        // wasmbin is the decoder of record, so that the stats stay comparable
+        // with earlier ones: anything it can't decode needs the fallback.
+        if Kind::try_from(raw.id).is_ok() || raw.id == sections::TAG {
+            if let Err(e) = raw.check_wasmbin() {
+                if cfg!(feature = "wasmparser-fallback") {
+                    stats.decoder = Decoder::Fallback;
+                } else {
+                    stats.decode_errors.push(raw.error(e));
+                    continue;
+                }
+            }
+        }
+        match Kind::try_from(raw.id) {
+            Ok(Kind::Custom) => {
+                let section = contents!(raw, CustomSectionReader::new(raw.reader()));
+                stats.size.custom += raw.size();
+                stats.custom_sections.push(CustomSection {
+                    name: section.name().to_owned(),
+                    size: raw.size(),
+                });
+                if is_strippable(section.name()) {
+                    stats.size.strippable += raw.size() + 1;
+                }
+                // Malformed name and producers sections are common, so they
+                // don't stop the section from being counted.
+                match section.name() {
+                    "producers" => match read_producers(&section) {
+                        Ok(producers) => stats.producers = Some(producers),
+                        Err(e) => stats.decode_errors.push(raw.error(e)),
+                    },
+                    "name" => match read_names(
+                        &section,
+                        &mut module.function_names,
+                        &mut module.global_names,
+                    ) {
+                        Ok(names) => stats.names = Some(names),
+                        Err(e) => stats.decode_errors.push(raw.error(e)),
+                    },
+                    "sourceMappingURL" => match section.data_reader().read_string() {
+                        Ok(url) => stats.debug_info.source_map_url = Some(url.to_owned()),
+                        Err(e) => stats.decode_errors.push(raw.error(e)),
+                    },
+                    "target_features" => match read_target_features(&section) {
+                        Ok(features) => stats.target_features = features,
+                        Err(e) => stats.decode_errors.push(raw.error(e)),
+                    },
+                    "dylink" | "dylink.0" => match read_dylink(&section) {
+                        Ok(dylink) => stats.dylink = Some(dylink),
+                        Err(e) => stats.decode_errors.push(raw.error(e)),
+                    },
+                    "build_id" => stats.debug_info.build_id = true,
+                    "external_debug_info" => stats.debug_info.external_debug_info = true,
+                    name if name.starts_with(".debug_") => {
+                        stats.debug_info.dwarf = true;
+                        stats.debug_info.dwarf_size += raw.size();
+                    }
+                    _ => module.custom.push(section.data()),
+                }
+            }
+            Ok(Kind::Type) => {
+                let section = contents!(raw, read_items::<RecGroup>(raw));
+                stats.size.types += raw.size();
+                for group in section {
+                    if group.is_explicit_rec_group() {
+                        stats.instr.proposals.gc += 1;
+                    }
+                    for ty in group.into_types() {
+                        // Subtyping is only available with GC.
+                        if !ty.is_final || !ty.supertype_idxs.is_empty() {
+                            stats.instr.proposals.gc += 1;
+                        }
+                        if has_non_nullable(&ty.composite_type.inner) {
+                            stats.instr.proposals.function_references += 1;
+                        }
+                        match ty.composite_type.inner {
+                            CompositeInnerType::Func(ty) => {
+                                if ty
+                                    .params()
+                                    .iter()
+                                    .chain(ty.results())
+                                    .any(|ty| matches!(ty, ValType::Ref(_)))
+                                {
+                                    stats.instr.proposals.ref_types += 1;
+                                }
+                                module.types.push(Some(ty));
+                            }
+                            CompositeInnerType::Struct(_) | CompositeInnerType::Array(_) => {
+                                stats.instr.proposals.gc += 1;
+                                module.types.push(None);
+                            }
+                            _ => module.types.push(None),
+                        }
+                    }
+                }
+            }
+            Ok(Kind::Import) => {
+                let section = contents!(
+                    raw,
+                    ImportSectionReader::new(raw.reader()).and_then(|reader| reader
+                        .into_imports()
+                        .collect::<wasmparser::Result<Vec<_>>>())
+                );
+                stats.size.externals += raw.size();
+                stats.imports = get_external_stats!(&section, ty, TypeRef);
+                for item in &section {
+                    *stats
+                        .import_modules
+                        .entry(item.module.to_owned())
+                        .or_default() += 1;
+                    if let Some(names) = &mut stats.import_names {
+                        names.push(ImportName {
+                            module: item.module.to_owned(),
+                            name: item.name.to_owned(),
+                        });
+                    }
+                    if let Some(version) = wasi_version(item.module) {
+                        // Modules adapted from preview1 import from both, and
+                        // count as preview2 whatever order the imports are in.
+                        if stats.wasi.as_deref() != Some("preview2") {
+                            stats.wasi = Some(version.to_owned());
+                        }
+                        stats.wasi_imports += 1;
+                    }
+                    match item.ty {
+                        TypeRef::Global(ty) => {
+                            stats.globals.imported += 1;
+                            global_types.push(MaybeExternal {
+                                value: ty,
+                                is_external: true,
+                            });
+                        }
+                        TypeRef::Func(type_id) | TypeRef::FuncExact(type_id) => {
+                            if wasi_version(item.module).is_none() {
+                                stats.js_api_usage.add(item.name);
+                                if let Some(api) = js_api::graphics_api(item.name) {
+                                    stats.graphics = stats.graphics.max(Some(api));
+                                    stats.graphics_imports += 1;
+                                }
+                            }
+                            func_types.push(MaybeExternal {
+                                value: type_id,
+                                is_external: true,
+                            });
+                        }
+                        TypeRef::Tag(_) => stats.instr.proposals.exception_handling += 1,
+                        TypeRef::Table(ty) => stats.tables.tables.push(TableInfo::new(&ty, true)),
+                        TypeRef::Memory(ty) => {
+                            let import = format!("{}.{}", item.module, item.name);
+                            stats.memories.push(MemoryStats::new(&ty, Some(import)));
+                            if ty.memory64 {
+                                stats.instr.proposals.memory64 += 1;
+                            }
+                        }
+                    }
+                }
+                module.imports.extend(section);
+            }
+            Ok(Kind::Table) => {
+                let section = contents!(raw, read_items::<Table>(raw));
+                stats.size.descriptors += raw.size();
+                stats
+                    .tables
+                    .tables
+                    .extend(section.iter().map(|table| TableInfo::new(&table.ty, false)));
+                stats.instr.proposals.function_references += section
+                    .iter()
+                    .filter(|table| !table.ty.element_type.is_nullable())
+                    .count();
+            }
+            Ok(Kind::Memory) => {
+                let section = contents!(raw, read_items::<MemoryType>(raw));
+                stats.size.descriptors += raw.size();
+                for ty in section {
+                    stats.memories.push(MemoryStats::new(&ty, None));
+                    if ty.shared {
+                        stats.instr.proposals.atomics += 1;
+                    }
+                    if ty.memory64 {
+                        stats.instr.proposals.memory64 += 1;
+                    }
+                }
+            }
+            Ok(Kind::Global) => {
+                let section = contents!(raw, read_items::<wasmparser::Global>(raw));
+                let extended = contents!(
+                    raw,
+                    section
+                        .iter()
+                        .map(|global| is_extended_const(&global.init_expr))
+                        .collect::<wasmparser::Result<Vec<_>>>()
+                );
+                stats.size.descriptors += raw.size();
+                stats.instr.proposals.function_references += section
+                    .iter()
+                    .filter(|global| is_non_nullable(&global.ty.content_type))
+                    .count();
+                stats.instr.proposals.extended_const +=
+                    extended.into_iter().filter(|&extended| extended).count();
+                for global in &section {
+                    let mut ops = global.init_expr.get_operators_reader().into_iter();
+                    match (ops.next(), ops.next()) {
+                        (Some(Ok(Operator::GlobalGet { .. })), Some(Ok(Operator::End))) => {
+                            stats.globals.init_from_global += 1;
+                        }
+                        (
+                            Some(Ok(
+                                Operator::I32Const { .. }
+                                | Operator::I64Const { .. }
+                                | Operator::F32Const { .. }
+                                | Operator::F64Const { .. }
+                                | Operator::V128Const { .. }
+                                | Operator::RefNull { .. }
+                                | Operator::RefFunc { .. },
+                            )),
+                            Some(Ok(Operator::End)),
+                        ) => stats.globals.init_from_const += 1,
+                        _ => {}
+                    }
+                }
+                global_types.extend(section.iter().map(|global| MaybeExternal {
+                    value: global.ty,
+                    is_external: false,
+                }));
+            }
+            Ok(Kind::Export) => {
+                let section = contents!(raw, read_items::<Export>(raw));
+                stats.size.externals += raw.size();
+                stats.exports = get_external_stats!(&section, kind, ExternalKind);
+                stats.export_details =
+                    ExportDetails::new(section.iter().map(|e| e.name), options.export_names);
+                stats.dyncall_exports += section
+                    .iter()
+                    .filter(|e| e.name.starts_with("dynCall_"))
+                    .count();
+                for item in &section {
+                    // The referenced item may be missing if the module is
+                    // malformed, or an earlier section failed to decode.
+                    let missing = |kind: &str, count: usize| {
+                        raw.error(format!(
+                            "export \"{}\" refers to {} {}, but there are only {}",
+                            item.name, kind, item.index, count
+                        ))
+                    };
+                    let index = item.index as usize;
+                    match item.kind {
+                        ExternalKind::Global => match global_types.get_mut(index) {
+                            Some(global) => global.is_external = true,
+                            None => stats
+                                .decode_errors
+                                .push(missing("global", global_types.len())),
+                        },
+                        ExternalKind::Func | ExternalKind::FuncExact => {
+                            match func_types.get_mut(index) {
+                                Some(func) => func.is_external = true,
+                                None => stats
+                                    .decode_errors
+                                    .push(missing("function", func_types.len())),
+                            }
+                        }
+                        ExternalKind::Memory => match stats.memories.get_mut(index) {
+                            Some(memory) => memory.exports.push(item.name.to_owned()),
+                            None => {
+                                let error = missing("memory", stats.memories.len());
+                                stats.decode_errors.push(error);
+                            }
+                        },
+                        ExternalKind::Tag => stats.instr.proposals.exception_handling += 1,
+                        _ => {}
+                    }
+                }
+                module.exports.extend(section);
+            }
+            Ok(Kind::Element) => {
+                let section = contents!(raw, read_items::<Element>(raw));
+                // Read in full before anything is counted, so that a section
+                // that fails to decode is left out of the stats entirely.
+                let (extended, refs) = contents!(raw, element_refs(&section));
+                for element in &section {
+                    match &element.kind {
+                        ElementKind::Active { .. } => stats.tables.active_segments += 1,
+                        ElementKind::Passive => {
+                            stats.tables.passive_segments += 1;
+                            stats.instr.proposals.bulk += 1;
+                        }
+                        ElementKind::Declared => {
+                            stats.tables.declared_segments += 1;
+                            stats.instr.proposals.ref_types += 1;
+                        }
+                    }
+                }
+                stats.tables.function_refs += refs.len();
+                call_graph.referenced.extend(refs);
+                stats.size.init += raw.size();
+                stats.size.elem += raw.size();
+                stats.instr.proposals.extended_const += extended;
+            }
+            Ok(Kind::Data) => {
+                let section = contents!(raw, read_items::<Data>(raw));
+                let extended = contents!(
+                    raw,
+                    section
+                        .iter()
+                        .map(|data| match &data.kind {
+                            DataKind::Active { offset_expr, .. } => is_extended_const(offset_expr),
+                            DataKind::Passive => Ok(false),
+                        })
+                        .collect::<wasmparser::Result<Vec<_>>>()
+                );
+                for data in section {
+                    stats.data.segments += 1;
+                    stats.data.bytes += data.data.len();
+                    data_bytes.add(data.data);
+                    stats.data.largest = stats.data.largest.max(data.data.len());
+                    match &data.kind {
+                        DataKind::Passive => stats.data.passive += 1,
+                        DataKind::Active { memory_index, .. } => {
+                            if !stats.data.memories.contains(memory_index) {
+                                stats.data.memories.push(*memory_index);
+                            }
+                        }
+                    }
+                    if let Some(limit) = options.scan_data {
+                        module.data.push(&data.data[..data.data.len().min(limit)]);
+                    }
+                    if let (Some(limit), Some(assets)) =
+                        (options.data_contents, &mut stats.data_contents)
+                    {
+                        assets::scan(stats.data.segments - 1, data.data, limit, assets);
+                    }
+                }
+                stats.size.init += raw.size();
+                stats.size.data += raw.size();
+                stats.instr.proposals.extended_const +=
+                    extended.into_iter().filter(|&extended| extended).count();
+                stats.data.memories.sort_unstable();
+            }
+            Ok(Kind::Code) => {
+                contents!(raw, CodeSectionReader::new(raw.reader()));
+                stats.size.code = raw.size();
+                let mut code_bytes = ByteHistogram::default();
+                code_bytes.add(raw.payload);
+                stats.entropy.code = code_bytes.entropy();
+                let mut errors = Vec::new();
+                if options.opcodes {
+                    stats.instr.opcodes = Some(OpcodeCounts::default());
+                }
+                call_graph.imported_funcs = u32::try_from(stats.imports.funcs).unwrap_or(u32::MAX);
+                global_uses.get_state = module
+                    .exports
+                    .iter()
+                    .find(|e| {
+                        e.name == "asyncify_get_state"
+                            && matches!(e.kind, ExternalKind::Func | ExternalKind::FuncExact)
+                    })
+                    .and_then(|e| e.index.checked_sub(call_graph.imported_funcs));
+                let walk = Instant::now();
+                let decoded = bodies.len();
+                get_instruction_stats(
+                    raw,
+                    &mut stats.instr,
+                    &mut bodies,
+                    &mut call_graph,
+                    &mut global_uses,
+                    &module.types,
+                    &mut errors,
+                );
+                timings.instructions += micros_since(walk);
+                // Only the bodies that were read count, however many the
+                // section says it has.
+                stats.funcs = bodies.len() - decoded;
+                stats
+                    .decode_errors
+                    .extend(errors.into_iter().map(|e| raw.error(e)));
+            }
+            // wasmbin doesn't know about the exception handling proposal's
+            // tag section yet, so without the fallback it's left out above.
+            #[cfg(feature = "wasmparser-fallback")]
+            Err(sections::TAG) => {
+                let tags = contents!(raw, wasmparser::TagSectionReader::new(raw.reader()));
+                stats.size.descriptors += raw.size();
+                stats.instr.proposals.exception_handling += tags.count() as usize;
+            }
+            // Newer than both decoders, so all that can be said is its size.
+            Err(id) => stats.unknown_sections.push(UnknownSection {
+                id,
+                size: raw.size(),
+            }),
+            Ok(_) => match contents!(raw, raw.decode()) {
+                Section::Function(section) => {
+                    let section_contents = contents!(raw, section.try_contents());
+                    stats.size.descriptors += raw.size();
+                    func_types.extend(section_contents.iter().map(|type_id| MaybeExternal {
+                        value: type_id.index,
+                        is_external: false,
+                    }));
+                }
+                Section::Start(section) => {
+                    let start = contents!(raw, section.try_contents());
+                    call_graph.referenced.insert(start.index);
+                    stats.has_start = true;
+                    stats.start = Some(StartInfo {
+                        function: start.index,
+                        imported: (start.index as usize) < stats.imports.funcs,
+                    });
+                }
+                Section::DataCount(section) => {
+                    contents!(raw, section.try_contents());
+                    stats.instr.proposals.bulk += 1;
+                }
+                // Read with wasmparser above.
+                Section::Custom(_)
+                | Section::Type(_)
+                | Section::Import(_)
+                | Section::Table(_)
+                | Section::Memory(_)
+                | Section::Global(_)
+                | Section::Export(_)
+                | Section::Element(_)
+                | Section::Code(_)
+                | Section::Data(_) => {}
+            },
+        }
+    }
+    stats.decode_errors.extend(truncated);
+    stats.entropy.data = data_bytes.entropy();
+    stats.data_contents_bytes = stats
+        .data_contents
+        .as_ref()
+        .map(|assets| assets.iter().map(|asset| asset.approx_size).sum());
+    stats.funcs_defined = stats.funcs;
+    stats.funcs_total = stats.imports.funcs + stats.funcs;
+    stats.funcs_exported = stats.exports.funcs;
+    timings.decode = micros_since(decode).saturating_sub(timings.instructions);
+    let sizes = Instant::now();
+    calculate_sizes(&mut stats, &bodies);
+    timings.sizes = micros_since(sizes);
+    stats.signatures = SignatureStats::new(&module.types, func_types.iter().map(|ty| ty.value));
+    let proposals = &mut stats.instr.proposals;
+    proposals.multi_value_types = stats.signatures.results.more_than_one();
+    proposals.multi_value = proposals.multi_value_types + proposals.multi_value_blocks;
+    stats.declared_but_unused_features = stats
+        .target_features
+        .iter()
+        .filter_map(|feature| feature.strip_prefix('+'))
+        .filter(|feature| feature_usage(&stats.instr.proposals, feature) == Some(0))
+        .map(str::to_owned)
+        .collect();
+    // A malformed name section still counts, so look at the section names.
+    stats.is_stripped = !stats.debug_info.dwarf
+        && !stats
+            .custom_sections
+            .iter()
+            .any(|section| section.name == "name");
+    // Binaryen adds itself to an existing producers section, and only strips
+    // it with the rest when asked to.
+    stats.optimized_with_binaryen = stats.producers.as_ref().map(|producers| {
+        producers
+            .processed_by
+            .iter()
+            .any(|tool| tool.name == "Binaryen")
+    });
+    if stats.memories.len() > 1 {
+        stats.instr.proposals.multi_memory += 1;
+    }
+    stats.threading = ThreadingStats::new(&module, &stats);
+    stats.detected_libraries = libraries::detect(
+        module
+            .function_names
+            .iter()
+            .map(|(_, name)| *name)
+            .chain(module.exports.iter().map(|e| e.name)),
+    );
+    if options.scan_data.is_some() {
+        stats.data_strings = Some(DataStrings::new(&module.data));
+    }
+    let inference = Instant::now();
+    infer_language(&module, &mut stats);
+    timings.language = micros_since(inference);
+    call_graph.referenced.extend(
+        module
+            .exports
+            .iter()
+            .filter(|e| matches!(e.kind, ExternalKind::Func | ExternalKind::FuncExact))
+            .map(|e| e.index),
+    );
+    stats.call_graph = call_graph.stats(stats.funcs);
+    let exports_func = |name| {
+        module.exports.iter().any(|e| {
+            e.name == name && matches!(e.kind, ExternalKind::Func | ExternalKind::FuncExact)
+        })
+    };
+    stats.entrypoint = if exports_func("_start") {
+        Some(Entrypoint::WasiCommand)
+    } else if exports_func("_initialize") {
+        Some(Entrypoint::WasiReactor)
+    } else if stats.start.is_some() {
+        Some(Entrypoint::Custom)
+    } else {
+        None
+    };
+    let asyncify_global = module
+        .global_names
+        .iter()
+        .find(|(_, name)| *name == "__asyncify_state")
+        .map(|(index, _)| *index);
+    stats.asyncify =
+        asyncify_global.is_some() || ASYNCIFY_EXPORTS.iter().any(|name| exports_func(name));
+    if stats.asyncify {
+        stats.asyncify_instrumented_funcs = asyncify_global
+            .or(global_uses.state)
+            .map(|global| global_uses.funcs.get(&global).copied().unwrap_or(0));
+    }
+    if options.top_functions > 0 {
+        // Sorting is stable, so equally sized functions stay in index order.
+        bodies.sort_by_key(|body| std::cmp::Reverse(body.size));
+        bodies.truncate(options.top_functions);
+        let imported_funcs = u32::try_from(stats.imports.funcs).unwrap_or(u32::MAX);
+        for body in &mut bodies {
+            body.index = body.index.saturating_add(imported_funcs);
+            body.name = module.function_name(body.index).map(str::to_owned);
+        }
+        stats.top_functions = bodies;
+    }
+    if stats.language == Language::Blazor {
+        stats.dotnet_mode = Some(if stats.funcs >= language::DOTNET_AOT_MIN_FUNCS {
+            DotnetMode::Aot
+        } else {
+            DotnetMode::Interpreter
+        });
+    }
+    for global in &global_types {
+        let ty = &global.value;
+        stats.globals.total += 1;
+        if ty.mutable {
+            stats.globals.mutable += 1;
+        }
+        match ty.content_type {
+            ValType::I32 => stats.globals.i32 += 1,
+            ValType::I64 => stats.globals.i64 += 1,
+            ValType::F32 => stats.globals.f32 += 1,
+            ValType::F64 => stats.globals.f64 += 1,
+            ValType::V128 => stats.globals.v128 += 1,
+            ValType::Ref(_) => {
+                stats.globals.refs += 1;
+                stats.instr.proposals.ref_types += 1;
+            }
+        }
+    }
+    // Tables of anything but `funcref` need reference types.
+    stats.instr.proposals.ref_types += stats
+        .tables
+        .tables
+        .iter()
+        .filter(|table| table.element_type != "funcref")
+        .count();
+    global_types
+        .into_iter()
+        .filter_map(MaybeExternal::external)
+        .for_each(|ty| {
+            if ty.mutable {
+                stats.instr.proposals.mutable_externals += 1;
+            }
+            if let ValType::I64 = ty.content_type {
+                stats.instr.proposals.bigint_externals += 1;
+            }
+        });
+    func_types
+        .into_iter()
+        .filter_map(MaybeExternal::external)
+        .filter_map(|type_id| module.types.get(type_id as usize)?.as_ref())
+        .for_each(|ty| {
+            stats.instr.proposals.bigint_externals += ty
+                .params()
+                .iter()
+                .chain(ty.results())
+                .filter(|&&ty| ty == ValType::I64)
+                .count();
+        });
+    if options.validate {
+        let validation = Instant::now();
+        validate(wasm, &mut stats);
+        timings.validation = micros_since(validation);
+    }
+    if options.timings {
+        timings.total = micros_since(start);
+        stats.timings = Some(timings);
+    }
+    Ok(stats)
+}
+
+/// Fills in [`Stats::size`] and [`Stats::func_sizes`], once every section
+/// has been measured and every body read.
+fn calculate_sizes(stats: &mut Stats, bodies: &[FunctionSize]) {
+    stats.size.fill_other();
+    stats.size.stripped_total = stats.size.total.saturating_sub(stats.size.strippable);
+    stats.func_sizes = FuncSizeStats::from_sizes(bodies.iter().map(|body| body.size).collect());
+}
+
+/// Fills in the language fields and the details that depend on the language
+/// or toolchain, which depend on most of the other stats in turn.
+fn infer_language(module: &ModuleInfo, stats: &mut Stats) {
+    let mut inferences = language::infer_language(module, stats).into_iter();
+    if let Some(inference) = inferences.next() {
+        stats.language = inference.language;
+        stats.language_confidence = inference.confidence;
+        stats.language_evidence = inference.evidence;
+        stats.language_candidates.push(stats.language);
+    }
+    for inference in inferences {
+        if !stats.language_candidates.contains(&inference.language) {
+            stats.language_candidates.push(inference.language);
+        }
+    }
+    // Classifying every name is slower than the rules, so it's only done
+    // when they came up empty.
+    if stats.language == Language::Unknown && !module.function_names.is_empty() {
+        let origins = language::symbol_origins(module);
+        if let Some(inference) = language::infer_from_symbols(module, &origins) {
+            stats.language = inference.language;
+            stats.language_confidence = inference.confidence;
+            stats.language_evidence = inference.evidence;
+            stats.language_candidates.push(stats.language);
+        }
+        stats.symbol_origins = Some(origins);
+    }
+    stats.toolchain_version = language::toolchain_version(module, stats);
+    stats.rust_packaging = language::rust_packaging(module);
+    if stats.language == Language::Go {
+        stats.go_details = Some(language::go_stats(module));
+    }
+}
+
+/// Fills in [`Stats::valid`] and [`Stats::validation_error`], accepting
+/// every proposal wasmparser supports.
+fn validate(wasm: &[u8], stats: &mut Stats) {
+    let result = Validator::new_with_features(WasmFeatures::all()).validate_all(wasm);
+    stats.valid = Some(result.is_ok());
+    stats.validation_error = result.err().map(|e| ValidationError {
+        message: e.message().to_owned(),
+        offset: e.offset(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stats_from_wat(wat: &str) -> Result<Stats> {
+        let binary = wat::parse_str(wat)?;
+        get_stats(&binary[..])
+    }
+
+    /// Appends `n` as an unsigned LEB128, for building binaries by hand.
+    fn write_leb(out: &mut Vec<u8>, mut n: usize) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn stats_from_wat_scanning_data(wat: &str, limit: usize) -> Result<Stats> {
+        let binary = wat::parse_str(wat)?;
+        let options = Options {
+            scan_data: Some(limit),
+            ..Default::default()
+        };
+        get_stats_with(&binary[..], &options)
+    }
+
+    fn custom_section_names(stats: &Stats) -> Vec<&str> {
+        stats
+            .custom_sections
+            .iter()
+            .map(|section| section.name.as_str())
+            .collect()
+    }
+
+    /// Encodes a custom section, to be appended to a module.
+    fn custom_section(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut payload = vec![name.len() as u8];
+        payload.extend(name.as_bytes());
+        payload.extend(data);
+        let mut section = vec![0, payload.len() as u8];
+        section.extend(payload);
+        section
+    }
+
+    /// Encodes the contents of a producers section.
+    fn producers(fields: &[(&str, &[(&str, &str)])]) -> Vec<u8> {
+        let mut data = vec![fields.len() as u8];
+        for (field, values) in fields {
+            data.push(field.len() as u8);
+            data.extend(field.as_bytes());
+            data.push(values.len() as u8);
+            for (name, version) in *values {
+                data.push(name.len() as u8);
+                data.extend(name.as_bytes());
+                data.push(version.len() as u8);
+                data.extend(version.as_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn func_sizes() -> Result<()> {
+        let nops = |n: usize| "nop ".repeat(n);
+        let stats = stats_from_wat(&format!(
+            "(module (func) (func nop) (func {}) (func {}) (func nop nop))",
+            nops(1000),
+            nops(200_000)
+        ))?;
+        // Each body is its instructions, plus the locals count and `end`.
+        let sizes = &stats.func_sizes;
+        assert_eq!(sizes.min, 2);
+        assert_eq!(sizes.max, 200_002);
+        assert_eq!(sizes.mean, 201_013.0 / 5.0);
+        assert_eq!(sizes.median, 4);
+        assert_eq!(sizes.p90, 200_002);
+        assert_eq!(sizes.p99, 200_002);
+        assert_eq!(sizes.large, 1);
+
+        let sizes = stats_from_wat("(module)")?.func_sizes;
+        assert_eq!((sizes.min, sizes.max, sizes.large), (0, 0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn signatures() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type (func))
+            (type (func (param i32 i32) (result i32)))
+            (type (func (param i32 i32) (result i32)))
+            (type (func (param f64) (result i32 i64)))
+            (type (func (param i32 i32 i32 i32 i32 i32)))
+            (import "env" "f" (func (type 0)))
+            (func (type 0))
+            (func (type 1) local.get 0)
+            (func (type 2) local.get 1)
+            (func (type 1) local.get 0)
+        )
+        "#,
+        )?;
+        let signatures = &stats.signatures;
+        assert_eq!(signatures.params.zero, 1);
+        assert_eq!(signatures.params.one, 1);
+        assert_eq!(signatures.params.two, 2);
+        assert_eq!(signatures.params.five_or_more, 1);
+        assert_eq!(signatures.results.zero, 2);
+        assert_eq!(signatures.results.one, 2);
+        assert_eq!(signatures.results.two, 1);
+        assert_eq!(signatures.distinct, 4);
+        // Three functions use the repeated type, against two for `() -> ()`.
+        assert_eq!(signatures.most_common.as_deref(), Some("(i32, i32) -> i32"));
+        assert_eq!(stats.instr.proposals.multi_value, 1);
+
+        let stats = stats_from_wat("(module (type (func (param i64) (result f32 f64))))")?;
+        assert_eq!(stats.signatures.most_common, None);
+        assert_eq!(
+            signature(&FuncType::new([ValType::I64], [ValType::F32, ValType::F64])),
+            "(i64) -> (f32, f64)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn memories() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "memory" (memory 1))
+            (memory (export "mem") (export "alias") 2 10 shared)
+        )
+        "#,
+        )?;
+        let memories: Vec<_> = stats
+            .memories
+            .iter()
+            .map(|m| {
+                let exports: Vec<_> = m.exports.iter().map(String::as_str).collect();
+                (m.initial, m.maximum, m.shared, m.import.as_deref(), exports)
+            })
+            .collect();
+        assert_eq!(
+            memories,
+            [
+                (1, None, false, Some("env.memory"), vec![]),
+                (2, Some(10), true, None, vec!["mem", "alias"]),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn section_proposals_survive_code() -> Result<()> {
+        // The memory and type sections come before the code section, whose
+        // instruction counts are added to theirs.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1 1 shared)
+            (func (result i32 i32) (i32.const 0) (i32.const 1))
+            (func (param v128) (result v128)
+                (i32x4.add (local.get 0) (local.get 0)))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.instr.proposals.atomics, 1);
+        assert_eq!(stats.instr.proposals.multi_value, 1);
+        assert_eq!(stats.instr.proposals.simd, 1);
+
+        // As does the data count section.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1)
+            (data "passive")
+            (func (data.drop 0))
+        )
+        "#,
+        )?;
+        // The data count section, and `data.drop`.
+        assert_eq!(stats.instr.proposals.bulk, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn multi_value() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (result i32)
+                (block (result i32 i32)
+                    i32.const 1
+                    i32.const 2)
+                i32.add
+                (loop (param i32) (result i32))
+                (block (result i32) i32.const 3)
+                drop)
+        )
+        "#,
+        )?;
+        let proposals = &stats.instr.proposals;
+        // The text format adds a type for each block type with an index.
+        assert_eq!(proposals.multi_value_types, 1);
+        assert_eq!(proposals.multi_value_blocks, 2);
+        assert_eq!(proposals.multi_value, 3);
+
+        // A multi-result type that's declared but never used.
+        let stats = stats_from_wat("(module (type (func (result i64 i64))))")?;
+        let proposals = &stats.instr.proposals;
+        assert_eq!(proposals.multi_value_types, 1);
+        assert_eq!(proposals.multi_value_blocks, 0);
+        assert_eq!(proposals.multi_value, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn globals() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "base" (global $base i32))
+            (global (mut i32) (i32.const 0))
+            (global (mut i32) (global.get $base))
+            (global f64 (f64.const 1.5))
+            (global i32 (i32.add (global.get $base) (i32.const 8)))
+        )
+        "#,
+        )?;
+        let globals = &stats.globals;
+        assert_eq!(globals.total, 5);
+        assert_eq!(globals.imported, 1);
+        assert_eq!(globals.mutable, 2);
+        assert_eq!((globals.i32, globals.f64, globals.i64), (4, 1, 0));
+        assert_eq!(globals.init_from_global, 1);
+        // The extended-const initialiser is neither.
+        assert_eq!(globals.init_from_const, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn tables() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "table" (table 1 externref))
+            (table $funcs 4 8 funcref)
+            (func $a)
+            (func $b)
+            (elem (table $funcs) (i32.const 0) func $a $b $a)
+            (elem func $b)
+            (elem declare func $a)
+        )
+        "#,
+        )?;
+        let tables = &stats.tables;
+        let described: Vec<_> = tables
+            .tables
+            .iter()
+            .map(|t| (t.element_type.as_str(), t.initial, t.maximum, t.imported))
+            .collect();
+        assert_eq!(
+            described,
+            [("externref", 1, None, true), ("funcref", 4, Some(8), false)]
+        );
+        assert_eq!(tables.active_segments, 1);
+        assert_eq!(tables.passive_segments, 1);
+        assert_eq!(tables.declared_segments, 1);
+        assert_eq!(tables.function_refs, 5);
+        assert_eq!(stats.instr.proposals.bulk, 1);
+        // The declared segment, and the externref table.
+        assert_eq!(stats.instr.proposals.ref_types, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn data_segments() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1)
+            (memory $other 1)
+            (data (i32.const 0) "hello")
+            (data (memory $other) (i32.const 8) "wasm!!!")
+            (data "passive")
+        )
+        "#,
+        )?;
+        let data = &stats.data;
+        assert_eq!(data.segments, 3);
+        assert_eq!(data.bytes, 5 + 7 + 7);
+        assert_eq!(data.passive, 1);
+        assert_eq!(data.largest, 7);
+        assert_eq!(data.memories, [0, 1]);
+        assert_eq!(stats.size.elem, 0);
+        assert_eq!(stats.size.data, stats.size.init);
+        Ok(())
+    }
+
+    #[test]
+    fn numeric_categories() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (param i64 f64) (result i32)
+                (i32.add
+                    (i32.wrap_i64 (local.get 0))
+                    (f64.lt (local.get 1) (f64.const 1))))
+            (func (param i32) (result i64)
+                (i64.extend_i32_u (i32.extend8_s (local.get 0))))
+            (func (param f32) (drop (i32.trunc_sat_f32_s (local.get 0))))
+        )
+        "#,
+        )?;
+        let categories = &stats.instr.categories;
+        assert_eq!(categories.arithmetic, 1);
+        assert_eq!(categories.comparison, 1);
+        assert_eq!(categories.conversion, 4);
+        assert_eq!(categories.other, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn category_bytes() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func
+                (drop (v128.const i64x2 0 0))
+                (drop (i64.const 0x7fffffffffffffff))
+                (drop (f64.const 1)))
+        )
+        "#,
+        )?;
+        let (counts, bytes) = (&stats.instr.categories, &stats.instr.category_bytes);
+        assert_eq!(counts.constants, 3);
+        assert_eq!(counts.control_flow, 3);
+        // Each has its opcode (two bytes for `v128.const`) and immediate: 16
+        // bytes, a 10-byte signed LEB128 and an 8-byte float.
+        assert_eq!(bytes.constants, 18 + 11 + 9);
+        assert_eq!(bytes.control_flow, 3);
+        assert!(bytes.constants > 10 * bytes.control_flow);
+        Ok(())
+    }
+
+    /// Every section of the module, and how long measuring each of them
+    /// took, from its raw bytes and by encoding it again.
+    fn measure_sections(wasm: &[u8]) -> Result<(Duration, Duration)> {
+        let (raw_sections, truncated) = sections::split(wasm);
+        assert!(truncated.is_none());
+        let (mut fast, mut encoded) = (Duration::ZERO, Duration::ZERO);
+        for raw in &raw_sections {
+            let start = Instant::now();
+            let size = raw.size();
+            fast += start.elapsed();
+            let start = Instant::now();
+            let encoded_size = raw.encoded_size()?;
+            encoded += start.elapsed();
+            assert_eq!(size, encoded_size, "section {}", raw.label());
+        }
+        Ok((fast, encoded))
+    }
+
+    #[test]
+    fn size_matches_encoded_size() -> Result<()> {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (type (func (param i32)))
+                (import "env" "log" (func (type 0)))
+                (import "env" "mem" (memory 1))
+                (table 1 funcref)
+                (global (mut i32) (i32.const 0))
+                (export "f" (func 1))
+                (elem (i32.const 0) 0)
+                (data (i32.const 0) "hello")
+                (data "passive")
+                (start 1)
+                (func (data.drop 1))
+                (func (param i32) (result i32)
+                    (block (result i32)
+                        (i32.add (local.get 0) (i32.const 1))))
+                (@custom "extra" "stuff")
+            )
+            "#,
+        )?;
+        measure_sections(&wasm)?;
+        Ok(())
+    }
+
+    /// Times the two ways of measuring sections on a module of a few
+    /// megabytes. Run with `cargo test --release -- --ignored --nocapture
+    /// size_benchmark`.
+    #[test]
+    #[ignore]
+    fn size_benchmark() -> Result<()> {
+        let mut wat = String::from("(module (memory 64) (global $g (mut i32) (i32.const 0))\n");
+        for i in 0..20_000 {
+            wat.push_str(&format!(
+                "(func (export \"f{}\") (param i32) (result i32) {})\n",
+                i,
+                "(global.set $g (i32.add (global.get $g) (i32.load (local.get 0)))) ".repeat(10)
+                    + "(local.get 0)"
+            ));
+        }
+        wat.push_str(&format!(
+            "(data (i32.const 0) \"{}\"))",
+            "\\00\\01\\02\\03".repeat(256 * 1024)
+        ));
+        let wasm = wat::parse_str(&wat)?;
+        assert!(wasm.len() > 2 << 20, "{} bytes", wasm.len());
+
+        let (fast, encoded) = measure_sections(&wasm)?;
+        println!(
+            "{} bytes: {:?} from the raw bytes, {:?} encoding again",
+            wasm.len(),
+            fast,
+            encoded
+        );
+        assert!(fast < encoded);
+        Ok(())
+    }
+
+    #[test]
+    fn size_components_add_up() -> Result<()> {
+        let fixtures = [
+            "(module)",
+            r#"(module (func (export "f") (result i32) i32.const 0))"#,
+            r#"
+            (module
+                (type (func (param i32)))
+                (import "env" "log" (func (type 0)))
+                (import "env" "mem" (memory 1))
+                (table 1 funcref)
+                (global (mut i32) (i32.const 0))
+                (elem (i32.const 0) 0)
+                (data (i32.const 0) "hello")
+                (data "passive")
+                (start 1)
+                (func (data.drop 1))
+                (@custom "extra" "stuff")
+            )
+            "#,
+        ];
+        for wat in fixtures {
+            let size = stats_from_wat(wat)?.size;
+            let counted = size.code
+                + size.init
+                + size.externals
+                + size.types
+                + size.custom
+                + size.descriptors
+                + size.other;
+            assert_eq!(counted, size.total, "{}", wat);
+            assert_eq!(size.init, size.elem + size.data, "{}", wat);
+        }
+        // Just the header.
+        assert_eq!(stats_from_wat("(module)")?.size.other, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn export_out_of_range() -> Result<()> {
+        let stats =
+            stats_from_wat(r#"(module (func) (export "f" (func 5)) (export "g" (global 0)))"#)?;
+        assert_eq!(
+            stats.decode_errors,
+            [
+                "Export section at offset 18: export \"f\" refers to function 5, but there are only 1",
+                "Export section at offset 18: export \"g\" refers to global 0, but there are only 0",
+            ]
+        );
+        assert_eq!(stats.exports.funcs, 1);
+        assert_eq!(stats.funcs, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn ref_types() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (param externref externref i32) (result externref)
+                (select (result externref) (local.get 0) (local.get 1) (local.get 2)))
+        )
+        "#,
+        )?;
+        // The function type and the typed `select`.
+        assert_eq!(stats.instr.proposals.ref_types, 2);
+
+        let stats = stats_from_wat("(module (global externref (ref.null extern)))")?;
+        // Just the global, as initialisers aren't counted as instructions.
+        assert_eq!(stats.instr.proposals.ref_types, 1);
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (table $t 1 externref)
+            (table 1 funcref)
+            (func (param i32) (result externref) (table.get $t (local.get 0)))
+        )
+        "#,
+        )?;
+        // The function type, the externref table and `table.get`.
+        assert_eq!(stats.instr.proposals.ref_types, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn sign_extend() -> Result<()> {
+        // `i64.extend_i32_u` is in the MVP.
+        let stats = stats_from_wat(
+            "(module (func (param i32) (result i64) (i64.extend_i32_u (local.get 0))))",
+        )?;
+        assert_eq!(stats.instr.proposals.sign_extend, 0);
+        assert_eq!(stats.instr.categories.conversion, 1);
+
+        let stats = stats_from_wat(
+            "(module (func (param i32) (result i32) (i32.extend8_s (local.get 0))))",
+        )?;
+        assert_eq!(stats.instr.proposals.sign_extend, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn operand_families() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1)
+            (func (param f32 f64)
+                (drop (i32.add (i32.const 1) (i32.const 2)))
+                (drop (f32.mul (local.get 0) (f32.load (i32.const 0))))
+                (drop (f64.sqrt (local.get 1)))
+                (drop (i32x4.add (v128.const i32x4 0 0 0 0) (v128.const i32x4 1 1 1 1))))
+        )
+        "#,
+        )?;
+        // The loads and constants count, but not `local.get` or `drop`.
+        assert_eq!(stats.instr.int_ops, 4);
+        assert_eq!(stats.instr.float_ops, 3);
+        assert_eq!(stats.instr.simd_ops, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn opcodes() -> Result<()> {
+        let binary = wat::parse_str(
+            r#"
+        (module
+            (memory 1 1 shared)
+            (func (param i32) (result i32)
+                (i32.add (local.get 0) (i32.add (local.get 0) (i32.const 1))))
+            (func (param i32)
+                (drop (i32.atomic.rmw8.add_u (local.get 0) (i32.const 1))))
+        )
+        "#,
+        )?;
+        let options = Options {
+            opcodes: true,
+            ..Default::default()
+        };
+        let opcodes = get_stats_with(&binary, &options)?.instr.opcodes.unwrap();
+        assert_eq!(
+            opcodes.0,
+            [
+                ("local.get".to_owned(), 3),
+                ("i32.add".to_owned(), 2),
+                ("i32.const".to_owned(), 2),
+                ("drop".to_owned(), 1),
+                ("i32.atomic.rmw8.add_u".to_owned(), 1),
+            ]
+        );
+        // Most frequent first, then by name.
+        assert_eq!(
+            serde_json::to_string(&opcodes)?,
+            r#"{"local.get":3,"i32.add":2,"i32.const":2,"drop":1,"i32.atomic.rmw8.add_u":1}"#
+        );
+
+        assert_eq!(get_stats(&binary)?.instr.opcodes, None);
+        Ok(())
+    }
+
+    #[test]
+    fn call_graph() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "log" (func $log (param i32)))
+            (table 1 funcref)
+            (elem (i32.const 0) $in_table)
+            (start $init)
+            (func $init)
+            (func $in_table)
+            (func $unused (call $log (i32.const 0)))
+            (func $recursive (param i32)
+                (call $recursive (local.get 0)))
+            (func $helper)
+            (func (export "main")
+                (call $log (i32.const 1))
+                (call $helper)
+                (call $helper)
+                (call $recursive (i32.const 2)))
+        )
+        "#,
+        )?;
+        let call_graph = &stats.call_graph;
+        // Only $unused, since main calls $recursive too.
+        assert_eq!(call_graph.potentially_dead, 1);
+        assert_eq!(call_graph.max_fan_out, 3);
+        assert!(call_graph.has_direct_recursion);
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func $a (call $b))
+            (func $b (call $a))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.call_graph.potentially_dead, 0);
+        assert!(!stats.call_graph.has_direct_recursion);
+        Ok(())
+    }
+
+    #[test]
+    fn nesting_depth() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func)
+            (func (block (block (loop (br 0)))))
+            (func (if (i32.const 0) (then (block)) (else (block (block))))
+                (block))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.instr.max_nesting_depth, 4);
+        // 1, 4 and 4: `else` stays at the depth of its `if`.
+        assert_eq!(stats.instr.mean_nesting_depth, 3.0);
+
+        assert_eq!(
+            stats_from_wat("(module (func))")?.instr.max_nesting_depth,
+            1
+        );
+        assert_eq!(stats_from_wat("(module)")?.instr.max_nesting_depth, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn locals() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (param i64) (local i32 i32 f64))
+            (func (local i64) (local v128 externref funcref) (local f32))
+            (func)
+        )
+        "#,
+        )?;
+        let locals = &stats.instr.locals;
+        assert_eq!(locals.total, 8);
+        assert_eq!(
+            (
+                locals.i32,
+                locals.i64,
+                locals.f32,
+                locals.f64,
+                locals.v128,
+                locals.refs
+            ),
+            (2, 1, 1, 1, 1, 2)
+        );
+        assert_eq!(locals.max_per_function, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn top_functions() -> Result<()> {
+        let binary = wat::parse_str(
+            r#"
+        (module
+            (import "env" "log" (func $log (param i32)))
+            (func $small)
+            (func $large (export "large") i32.const 1 call $log i32.const 2 call $log)
+            (func $medium nop nop)
+            (func (export "exported") i32.const 3 call $log)
+            (func)
+        )
+        "#,
+        )?;
+        let options = Options {
+            top_functions: 3,
+            ..Default::default()
+        };
+        let stats = get_stats_with(&binary, &options)?;
+        let top: Vec<_> = stats
+            .top_functions
+            .iter()
+            .map(|f| (f.index, f.name.as_deref(), f.size, f.instructions))
+            .collect();
+        assert_eq!(
+            top,
+            [
+                (2, Some("large"), 10, 4),
+                (4, Some("exported"), 6, 2),
+                (3, Some("medium"), 4, 2),
+            ]
+        );
+
+        // Only reported when asked for.
+        assert!(get_stats(&binary)?.top_functions.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn get_stats_funcs() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "baz" (func))
+            (func $foo)
+            (func (export "bar")
+                call $foo
+            )
+        )
+        "#,
+        )?;
+        assert_eq!(stats.funcs, 2);
+        assert_eq!(stats.funcs_defined, 2);
+        assert_eq!(stats.funcs_total, 3);
+        assert_eq!(stats.funcs_exported, 1);
+        Ok(())
+    }
+
+    const PARTIAL_WAT: &str = r#"
+        (module
+            (type (func (param i32)))
+            (import "env" "f" (func (type 0)))
+            (func (export "g") (param i32) local.get 0 call 0)
+            (func (export "h"))
+        )
+        "#;
+
+    #[test]
+    fn get_stats_truncated() -> Result<()> {
+        let wasm = wat::parse_str(PARTIAL_WAT)?;
+        let (raw_sections, _) = sections::split(&wasm);
+        let kinds: Vec<_> = raw_sections.iter().map(|raw| raw.id).collect();
+        // type, import, function, export, code
+        assert_eq!(kinds, [1, 2, 3, 7, 10]);
+
+        for (i, raw) in raw_sections.iter().enumerate() {
+            // Cut each section in half, keeping everything before it.
+            let cut = raw.payload_offset + raw.payload.len() / 2;
+            let stats = get_stats(&wasm[..cut])?;
+            assert_eq!(stats.decode_errors.len(), 1, "{:?}", stats.decode_errors);
+            assert!(
+                stats.decode_errors[0].contains(&format!("at offset {}", raw.offset)),
+                "{:?}",
+                stats.decode_errors
+            );
+            assert_eq!(stats.size.total, cut);
+            assert_eq!(stats.size.types > 0, i > 0);
+            assert_eq!(stats.imports.funcs, if i > 1 { 1 } else { 0 });
+            assert_eq!(stats.exports.funcs, if i > 3 { 2 } else { 0 });
+            assert_eq!(stats.funcs, 0);
+            assert_eq!(stats.size.code, 0);
+        }
+
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty());
+        assert_eq!(stats.funcs, 2);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn get_stats_bad_function_body() -> Result<()> {
+        let mut wasm = wat::parse_str(PARTIAL_WAT)?;
+        // The last function body is `0x02 0x00 0x0b`: replace its `end` with
+        // an opcode that doesn't exist.
+        let last = wasm.len() - 1;
+        assert_eq!(wasm[last], 0x0b);
+        wasm[last] = 0xff;
+
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.decode_errors.len(), 1);
+        let offset = wasm.len() - 3;
+        assert!(
+            stats.decode_errors[0].starts_with("Code section at offset"),
+            "{}",
+            stats.decode_errors[0]
+        );
+        assert!(
+            stats.decode_errors[0].contains(&format!("function 1 at offset {}", offset)),
+            "{}",
+            stats.decode_errors[0]
+        );
+        // Only the other function is counted.
+        assert_eq!(stats.funcs, 1);
+        assert_eq!(stats.instr.categories.direct_calls, 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn get_stats_missing_function_bodies() -> Result<()> {
+        let mut wasm = wat::parse_str(PARTIAL_WAT)?;
+        // Claim a third function body that the code section doesn't have.
+        let (raw_sections, _) = sections::split(&wasm);
+        let count = raw_sections[4].payload_offset;
+        assert_eq!(wasm[count], 2);
+        wasm[count] = 3;
+
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.decode_errors.len(), 1, "{:?}", stats.decode_errors);
+        assert_eq!(stats.funcs, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn get_instruction_stats_bad_count() {
+        // A code section whose function count is an unterminated LEB128.
+        let wasm = b"\0asm\x01\0\0\0\x0a\x01\x80";
+        let (raw_sections, truncated) = sections::split(wasm);
+        assert!(truncated.is_none());
+        let mut errors = Vec::new();
+        let mut bodies = Vec::new();
+        get_instruction_stats(
+            &raw_sections[0],
+            &mut InstructionStats::default(),
+            &mut bodies,
+            &mut CallGraph::default(),
+            &mut GlobalUses::default(),
+            &[],
+            &mut errors,
+        );
+        assert_eq!(errors, ["invalid function count"]);
+        assert!(bodies.is_empty());
+
+        let stats = get_stats(wasm).unwrap();
+        assert_eq!(stats.decode_errors.len(), 1, "{:?}", stats.decode_errors);
+        assert!(stats.decode_errors[0].starts_with("Code section at offset 8"));
+        assert_eq!(stats.size.code, 0);
+        assert_eq!(stats.funcs, 0);
+    }
+
+    #[test]
+    fn get_stats_bad_segments() -> Result<()> {
+        let wasm = wat::parse_str(
+            r#"
+        (module
+            (table 4 funcref)
+            (memory 1)
+            (func $f)
+            (elem (i32.const 0) $f $f)
+            (elem (i32.const 2) $f)
+            (data (i32.const 0) "a")
+            (data (i32.const 1) "b")
+        )
+        "#,
+        )?;
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.tables.function_refs, 3);
+        assert_eq!(stats.data.segments, 2);
+
+        for id in [9, 11] {
+            // Replace the second segment's `i32.const` with an opcode that
+            // doesn't exist.
+            let mut wasm = wasm.clone();
+            let (raw_sections, _) = sections::split(&wasm);
+            let raw = raw_sections.iter().find(|raw| raw.id == id).unwrap();
+            let offset = raw.offset;
+            let second = raw.payload_offset
+                + raw
+                    .payload
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &byte)| byte == 0x41)
+                    .nth(1)
+                    .unwrap()
+                    .0;
+            wasm[second] = 0xff;
+
+            // The whole section is left out, not just the broken segment.
+            let stats = get_stats(&wasm)?;
+            assert_eq!(stats.decode_errors.len(), 1, "{:?}", stats.decode_errors);
+            assert!(
+                stats.decode_errors[0].contains(&format!("at offset {}", offset)),
+                "{:?}",
+                stats.decode_errors
+            );
+            if id == 9 {
+                assert_eq!(stats.size.elem, 0);
+                assert_eq!(stats.tables.active_segments, 0);
+                assert_eq!(stats.tables.function_refs, 0);
+                assert_eq!(stats.data.segments, 2);
+            } else {
+                assert_eq!(stats.size.data, 0);
+                assert_eq!(stats.data.segments, 0);
+                assert_eq!(stats.tables.function_refs, 3);
+            }
+            assert_eq!(stats.size.init, stats.size.elem + stats.size.data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validation() -> Result<()> {
+        let options = Options {
+            validate: true,
+            ..Default::default()
+        };
+        let mut wasm = wat::parse_str("(module (func (result i32) i32.const 0))")?;
+        assert_eq!(get_stats(&wasm)?.valid, None);
+        let stats = get_stats_with(&wasm, &options)?;
+        assert_eq!(stats.valid, Some(true));
+        assert!(stats.validation_error.is_none());
+
+        // Declaring an i64 result still decodes, but the body returns an i32.
+        let ty = [0x60, 0x00, 0x01, 0x7f];
+        let i = wasm.windows(ty.len()).position(|w| w == ty).unwrap();
+        wasm[i + 3] = 0x7e;
+        let stats = get_stats_with(&wasm, &options)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.funcs, 1);
+        assert_eq!(stats.valid, Some(false));
+        let error = stats.validation_error.unwrap();
+        assert!(error.message.contains("type mismatch"), "{}", error.message);
+        assert!(error.offset > i as u64, "{}", error.offset);
+        Ok(())
+    }
+
+    #[test]
+    fn timings() -> Result<()> {
+        let wasm = wat::parse_str("(module (func (result i32) i32.const 0))")?;
+        let stats = get_stats(&wasm)?;
+        assert!(stats.timings.is_none());
+        assert!(serde_json::to_value(&stats)?.get("timings").is_none());
+
+        let options = Options {
+            timings: true,
+            validate: true,
+            ..Default::default()
+        };
+        let stats = get_stats_with(&wasm, &options)?;
+        let timings = serde_json::to_value(&stats)?["timings"].take();
+        let timings = timings.as_object().unwrap();
+        for stage in [
+            "decode",
+            "instructions",
+            "sizes",
+            "language",
+            "validation",
+            "total",
+        ] {
+            assert!(timings[stage].is_u64(), "{}: {}", stage, timings[stage]);
+        }
+        let total = timings["total"].as_u64().unwrap();
+        let stages: u64 = timings
+            .iter()
+            .filter(|(stage, _)| *stage != "total")
+            .map(|(_, micros)| micros.as_u64().unwrap())
+            .sum();
+        assert!(stages <= total, "{} > {}", stages, total);
+        Ok(())
+    }
+
+    #[test]
+    fn mutated_modules_dont_panic() -> Result<()> {
+        let wasm = wat::parse_str(
+            r#"
+        (module
+            (type $t (func (param i32) (result i32)))
+            (import "env" "f" (func $f (type $t)))
+            (import "env" "memory" (memory 1))
+            (import "env" "g" (global $g i32))
+            (table 2 funcref)
+            (global $h (mut i64) (i64.const 0))
+            (elem (i32.const 0) $f $main)
+            (data (i32.const 8) "rustc")
+            (func $main (export "main") (type $t)
+                (block (result i32 i32)
+                    (call $f (local.get 0))
+                    (call_indirect (type $t) (i32.const 1) (i32.const 0)))
+                i32.add
+                (if (result i32) (i32.eqz (global.get $g))
+                    (then (i32.load offset=4 (i32.const 0)))
+                    (else (memory.size)))
+                i32.add)
+            (func (drop (v128.const i64x2 1 2)) (global.set $h (i64.const 1)))
+            (start 2)
+            (@custom "producers" "\01\08language\01\04Rust\00")
+        )
+        "#,
+        )?;
+        let options = Options {
+            scan_data: Some(64),
+            top_functions: 2,
+            opcodes: true,
+            import_names: true,
+            validate: true,
+            ..Default::default()
+        };
+        let stats = get_stats_with(&wasm, &options)?;
+        assert_eq!(stats.valid, Some(true), "{:?}", stats.validation_error);
+        assert_eq!(stats.language, Language::Rust);
+
+        // A component with the module above, and a nested component with
+        // another copy of it, so that the component path is covered too.
+        let mut inner = b"\0asm\x0d\0\x01\0".to_vec();
+        inner.push(1);
+        write_leb(&mut inner, wasm.len());
+        inner.extend(&wasm);
+        let mut component = inner.clone();
+        component.push(4);
+        write_leb(&mut component, inner.len());
+        component.extend(&inner);
+        component.extend([11, 1, 0]);
+        let stats = get_stats_with(&component, &options)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.component.core_modules, 2);
+
+        let analyse = |wasm: &[u8]| {
+            // Errors are fine; only panics fail the test.
+            let _ = get_stats_with(wasm, &options);
+        };
+        for seed in [&wasm, &component] {
+            for len in 0..seed.len() {
+                analyse(&seed[..len]);
+            }
+        }
+        // A fixed xorshift sequence, so that failures can be reproduced.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for round in 0..8000 {
+            let mut mutated = if round % 2 == 0 {
+                wasm.clone()
+            } else {
+                component.clone()
+            };
+            if next() % 4 == 0 {
+                // Change the version or layer, which decides whether the
+                // rest is read as a module or a component.
+                let i = 4 + next() % 4;
+                mutated[i] = match next() % 3 {
+                    0 => next() as u8,
+                    1 => mutated[i] ^ 1,
+                    _ => [0x01, 0x00, 0x0d, 0x01][next() % 4],
+                };
+            }
+            for _ in 0..1 + next() % 4 {
+                // Leave the header alone, so that the sections get decoded.
+                let i = sections::HEADER_SIZE + next() % (mutated.len() - sections::HEADER_SIZE);
+                mutated[i] = match next() % 3 {
+                    0 => next() as u8,
+                    1 => mutated[i] ^ 0x80,
+                    _ => 0xff,
+                };
+            }
+            if next() % 4 == 0 {
+                mutated.truncate(
+                    sections::HEADER_SIZE + next() % (mutated.len() - sections::HEADER_SIZE),
+                );
+            }
+            analyse(&mutated);
+        }
+        Ok(())
+    }
+
+    /// A module whose memory section wasmbin can't decode, since it doesn't
+    /// support memory64 yet.
+    const MEMORY64_WAT: &str = r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") i64 1)
+            (global (export "__heap_base") i32 (i32.const 1024))
+            (func (export "_start"))
+        )
+        "#;
+
+    #[test]
+    fn decoder() -> Result<()> {
+        let stats = stats_from_wat("(module (memory 1) (func (export \"f\")))")?;
+        assert_eq!(stats.decoder, Decoder::Wasmbin);
+
+        let wasm = wat::parse_str(MEMORY64_WAT)?;
+        let (raw_sections, _) = sections::split(&wasm);
+        let memory = raw_sections.iter().find(|raw| raw.id == 5).unwrap();
+        assert!(memory.check_wasmbin().is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn decoder_fallback() -> Result<()> {
+        let stats = stats_from_wat(MEMORY64_WAT)?;
+        assert_eq!(stats.decoder, Decoder::Fallback);
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert!(stats.memories[0].memory64);
+        assert!(stats.size.descriptors > 0);
+        assert_eq!(
+            stats.section_order,
+            ["type", "import", "function", "memory", "global", "export", "code"]
+        );
+        assert_eq!(stats.import_modules["wasi_snapshot_preview1"], 1);
+        assert_eq!(
+            stats.export_details.names,
+            ["memory", "__heap_base", "_start"]
+        );
+        assert_eq!(stats.language, Language::CClang);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasmparser-fallback"))]
+    fn decoder_without_fallback() -> Result<()> {
+        // The memory section is left out, so the memory export refers to
+        // nothing, but everything else is read.
+        let stats = stats_from_wat(MEMORY64_WAT)?;
+        assert_eq!(stats.decoder, Decoder::Wasmbin);
+        assert_eq!(stats.decode_errors.len(), 2, "{:?}", stats.decode_errors);
+        assert!(stats.decode_errors[0].starts_with("Memory section at offset"));
+        assert!(stats.decode_errors[1].contains("refers to memory 0"));
+        assert!(stats.memories.is_empty());
+        assert_eq!(stats.section_order.len(), 7);
+        assert_eq!(stats.import_modules["wasi_snapshot_preview1"], 1);
+        assert_eq!(
+            stats.export_details.names,
+            ["memory", "__heap_base", "_start"]
+        );
+        assert_eq!(stats.language, Language::CClang);
+        Ok(())
+    }
+
+    #[test]
+    fn get_stats_bad_magic() {
+        let err = get_stats(b"\0wasm\x01\0\0").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<HeaderError>(),
+            Some(&HeaderError::InvalidMagic)
+        );
+        assert!(err.to_string().contains("magic"), "{}", err);
+
+        let err = get_stats(b"\0asm\x01").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<HeaderError>(),
+            Some(&HeaderError::Truncated(5))
+        );
+    }
+
+    #[test]
+    fn wasm_version_and_unknown_sections() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        assert_eq!(get_stats(&wasm)?.wasm_version, 1);
+        assert!(get_stats(&wasm)?.unknown_sections.is_empty());
+
+        // A section with id 42 and a three-byte payload, then version 2.
+        wasm.extend([42, 3, 1, 2, 3]);
+        wasm[4] = 2;
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.wasm_version, 2);
+        assert_eq!(stats.unknown_sections.len(), 1);
+        assert_eq!(stats.unknown_sections[0].id, 42);
+        assert_eq!(stats.unknown_sections[0].size, 4);
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        // The sections around it are still read.
+        assert_eq!(stats.funcs, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn entropy() -> Result<()> {
+        let stats = stats_from_wat("(module)")?;
+        assert_eq!(stats.entropy.code, None);
+        assert_eq!(stats.entropy.data, None);
+        let json = serde_json::to_value(&stats.entropy)?;
+        assert_eq!(json, serde_json::json!({ "code": null, "data": null }));
+
+        let zeros = stats_from_wat(&format!(
+            r#"(module (memory 1) (func) (data (i32.const 0) "{}"))"#,
+            "\\00".repeat(4096)
+        ))?;
+        assert_eq!(zeros.entropy.data, Some(0.0));
+        let code = zeros.entropy.code.unwrap();
+        assert!(code > 0.0 && code < 8.0, "{}", code);
+
+        // A xorshift sequence, which is close enough to uniform.
+        let mut state = 0x2545_f491_u32;
+        let random: String = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                format!("\\{:02x}", state as u8)
+            })
+            .collect();
+        let stats = stats_from_wat(&format!(
+            r#"(module (memory 1) (data (i32.const 0) "{}"))"#,
+            random
+        ))?;
+        let data = stats.entropy.data.unwrap();
+        assert!(data > 7.9 && data <= 8.0, "{}", data);
+        // Rounded to 3 decimal places.
+        assert_eq!(data, (data * 1000.0).round() / 1000.0);
+        Ok(())
+    }
+
+    #[test]
+    fn data_strings() -> Result<()> {
+        let wat = r#"
+        (module
+            (memory 1)
+            (data (i32.const 0) "hello world\00\00\00\00\00")
+            (data (i32.const 16) "\c3\a9ab\ff\ff\ff\ff\ff\ff\ff\ff\ff\ff\ff\ff")
+        )
+        "#;
+        let strings = stats_from_wat_scanning_data(wat, 1024)?
+            .data_strings
+            .unwrap();
+        assert_eq!(strings.scanned_bytes, 32);
+        assert_eq!(strings.printable_bytes, 13);
+        assert_eq!(strings.longest_run, 11);
+        // "hello world", "é" and "ab".
+        assert_eq!(strings.utf8_valid_fraction, 15.0 / 32.0);
+
+        // Only the first 8 bytes of each segment are scanned.
+        let strings = stats_from_wat_scanning_data(wat, 8)?.data_strings.unwrap();
+        assert_eq!(strings.scanned_bytes, 16);
+        assert_eq!(strings.printable_bytes, 10);
+        assert_eq!(strings.longest_run, 8);
+        assert_eq!(strings.utf8_valid_fraction, 12.0 / 16.0);
+
+        assert!(stats_from_wat(wat)?.data_strings.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn data_contents() -> Result<()> {
+        let wat = r#"
+        (module
+            (memory 1)
+            ;; A PNG signature, a 13-byte IHDR chunk and IEND.
+            (data (i32.const 0)
+                "padding"
+                "\89PNG\0d\0a\1a\0a"
+                "\00\00\00\0dIHDR\00\00\00\01\00\00\00\01\08\06\00\00\00\1f\15\c4\89"
+                "\00\00\00\00IEND\ae\42\60\82"
+                "trailer")
+            ;; A module with an empty type section.
+            (data (i32.const 100) "\00asm\01\00\00\00\01\01\00")
+        )
+        "#;
+        let binary = wat::parse_str(wat)?;
+        let options = Options {
+            data_contents: Some(1024),
+            ..Default::default()
+        };
+        let stats = get_stats_with(&binary, &options)?;
+        let assets: Vec<_> = stats
+            .data_contents
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|asset| (asset.format, asset.segment, asset.offset, asset.approx_size))
+            .collect();
+        assert_eq!(
+            assets,
+            [(AssetFormat::Png, 0, 7, 45), (AssetFormat::Wasm, 1, 0, 11)]
+        );
+        assert_eq!(stats.data_contents_bytes, Some(56));
+
+        // Nothing is looked for past the limit.
+        let options = Options {
+            data_contents: Some(4),
+            ..Default::default()
+        };
+        let stats = get_stats_with(&binary, &options)?;
+        let formats: Vec<_> = stats
+            .data_contents
+            .unwrap()
+            .iter()
+            .map(|a| a.format)
+            .collect();
+        assert_eq!(formats, [AssetFormat::Wasm]);
+
+        let stats = get_stats(&binary)?;
+        assert!(stats.data_contents.is_none());
+        assert!(stats.data_contents_bytes.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn strippable() -> Result<()> {
+        let stats = stats_from_wat("(module (func))")?;
+        assert_eq!(stats.size.strippable, 0);
+        assert_eq!(stats.size.stripped_total, stats.size.total);
+
+        let debug_info = "x".repeat(200);
+        let stats = stats_from_wat(&format!(
+            r#"
+        (module
+            (func $f)
+            (@custom ".debug_info" "{}")
+            (@custom "sourceMappingURL" "\0c/main.wasm.map")
+            (@custom "producers" "\00")
+            (@custom "build_id" "\01")
+        )
+        "#,
+            debug_info
+        ))?;
+        let size = &stats.size;
+        let section = |name: &str| {
+            let section = stats.custom_sections.iter().find(|s| s.name == name);
+            section.unwrap().size + 1
+        };
+        assert_eq!(
+            size.strippable,
+            section(".debug_info")
+                + section("sourceMappingURL")
+                + section("producers")
+                + section("name")
+        );
+        // More than half of the module is debug info.
+        assert!(size.strippable * 2 > size.total, "{:?}", size);
+        assert_eq!(size.stripped_total, size.total - size.strippable);
+        // The build id, along with the function, stays.
+        assert!(size.stripped_total > section("build_id") + size.code);
+        Ok(())
+    }
+
+    #[test]
+    fn section_order() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (@custom "a" (before first) "")
+            (import "env" "f" (func))
+            (memory 1)
+            (func (export "g"))
+            (@custom "a" (after code) "")
+            (data (i32.const 0) "x")
+        )
+        "#,
+        )?;
+        assert_eq!(
+            stats.section_order,
+            [
+                "custom:a", "type", "import", "function", "memory", "export", "code", "custom:a",
+                "data"
+            ]
+        );
+        assert!(!stats.nonstandard_section_order);
+
+        // A memory section, then two empty type sections.
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend([5, 3, 1, 0, 1]);
+        wasm.extend([1, 1, 0]);
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.section_order, ["memory", "type"]);
+        assert!(stats.nonstandard_section_order);
+        wasm.truncate(wasm.len() - 3);
+        assert!(!get_stats(&wasm)?.nonstandard_section_order);
+        wasm.extend([5, 3, 1, 0, 1]);
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.section_order, ["memory", "memory"]);
+        assert!(stats.nonstandard_section_order);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn exception_handling_legacy() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "emscripten_longjmp" (func $longjmp (param i32 i32)))
+            (import "env" "__cpp_exception" (tag $cpp (param i32)))
+            (tag $e (param i32))
+            (export "e" (tag $e))
+            (func (export "f")
+                try
+                    i32.const 1
+                    throw $e
+                catch $e
+                    drop
+                catch_all
+                    rethrow 0
+                end
+                try
+                    nop
+                delegate 0
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        // try, throw, catch, catch_all, rethrow, try, delegate, plus the tag
+        // import, definition and export.
+        assert_eq!(stats.instr.proposals.exception_handling, 10);
+        // The instructions above, plus `drop`, `end` and `nop`.
+        assert_eq!(stats.instr.categories.control_flow, 10);
+        assert_eq!(stats.imports.funcs, 1);
+        assert_eq!(stats.exports.funcs, 1);
+        assert_eq!(stats.language, Language::Emscripten);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn gc() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $point (struct (field $x i32) (field $y (mut i32))))
+            (type $bytes (array (mut i8)))
+            (rec
+                (type $a (struct (field (ref null $b))))
+                (type $b (struct))
+            )
+            (global (ref null $point) (ref.null $point))
+            (func (export "f") (result i32)
+                (struct.get $point $x (struct.new $point (i32.const 1) (i32.const 2)))
+                (array.get_u $bytes (array.new_default $bytes (i32.const 4)) (i32.const 0))
+                i32.add
+                (i31.get_s (ref.i31 (i32.const 5)))
+                i32.add
+            )
+            (func (param anyref) (result (ref null $point))
+                (block $l (result (ref $point))
+                    (br_on_cast $l anyref (ref $point) (local.get 0))
+                    (ref.cast (ref null $point))
+                    return
+                )
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.funcs, 2);
+        assert_eq!(stats.exports.funcs, 1);
+        // struct.new, struct.get, array.new_default, array.get_u, ref.i31,
+        // i31.get_s, br_on_cast and ref.cast.
+        assert_eq!(stats.instr.categories.gc, 8);
+        // The instructions above, plus four struct and array types and the
+        // rec group.
+        assert_eq!(stats.instr.proposals.gc, 13);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn exception_handling() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (tag $e)
+            (func
+                (block $h (result exnref)
+                    (try_table (catch_all_ref $h)
+                        throw $e
+                    )
+                    unreachable
+                )
+                throw_ref
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        // try_table, throw, throw_ref and the tag definition.
+        assert_eq!(stats.instr.proposals.exception_handling, 4);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn tag_imports_and_exports() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "error" (tag (param i32)))
+            (tag $e (export "e"))
+            (func (export "f") i32.const 0 throw 0)
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.imports.tags, 1);
+        assert_eq!(stats.imports.funcs, 0);
+        assert_eq!(stats.exports.tags, 1);
+        assert_eq!(stats.exports.funcs, 1);
+        assert_eq!(stats.exports.other, 0);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(feature = "wasmparser-fallback"))]
+    fn exception_handling_without_fallback() -> Result<()> {
+        // wasmbin can't decode tags or the instructions that throw them, but
+        // the module's other sections still produce stats.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "log" (func (param i32)))
+            (tag $e (export "e"))
+            (func (export "f") throw $e)
+        )
+        "#,
+        )?;
+        let kinds: Vec<_> = stats
+            .decode_errors
+            .iter()
+            .map(|e| e.split(" section").next().unwrap())
+            .collect();
+        assert_eq!(kinds, ["Unknown(13)", "Export", "Code"]);
+        assert_eq!(stats.decoder, Decoder::Wasmbin);
+        assert_eq!(stats.imports.funcs, 1);
+        assert_eq!(stats.instr.proposals.exception_handling, 0);
+        assert_eq!(
+            stats.section_order,
+            [
+                "type",
+                "import",
+                "function",
+                "tag",
+                "export",
+                "code",
+                "custom:name"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn memory64() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "mem" (memory i64 1))
+            (memory i64 1 2)
+            (func (export "f") (param i64) (result i32)
+                (i32.load 1 (local.get 0))
+                (i32.load offset=0x100000000 (local.get 0))
+                i32.add
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        // Both memories, plus the load with a 64-bit offset.
+        assert_eq!(stats.instr.proposals.memory64, 3);
+        // The imported and defined memories, plus the load from memory 1.
+        assert_eq!(stats.instr.proposals.multi_memory, 2);
+        assert_eq!(stats.instr.proposals.atomics, 0);
+        assert_eq!(stats.instr.proposals.bulk, 0);
+        assert_eq!(stats.instr.categories.load_store, 2);
+        assert_eq!(stats.instr.categories.local_var, 2);
+        assert_eq!(stats.imports.memories, 1);
+        assert_eq!(stats.exports.funcs, 1);
+        assert_eq!(stats.language, Language::Unknown);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn relaxed_simd() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (param v128 v128 v128) (result v128)
+                (f32x4.relaxed_madd
+                    (i8x16.add (local.get 0) (local.get 1))
+                    (local.get 1)
+                    (local.get 2))
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.instr.proposals.simd, 1);
+        assert_eq!(stats.instr.proposals.relaxed_simd, 1);
+        assert_eq!(stats.instr.categories.other, 2);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn atomics() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1 1 shared)
+            (func (param i32) (result i32)
+                atomic.fence
+                (drop (i32.atomic.rmw.add (local.get 0) (i32.const 1)))
+                (drop (i64.atomic.rmw8.xchg_u (local.get 0) (i64.const 2)))
+                (drop (i32.atomic.load (local.get 0)))
+                (drop (memory.atomic.notify (local.get 0) (i32.const 1)))
+                atomic.fence
+                (i32.atomic.rmw.cmpxchg (local.get 0) (i32.const 0) (i32.const 1)))
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        let atomics = &stats.instr.atomics;
+        assert_eq!(atomics.rmw, 3);
+        assert_eq!(atomics.fence, 2);
+        // Every instruction, plus the shared memory.
+        assert_eq!(stats.instr.proposals.atomics, 8);
+        assert_eq!(stats.instr.categories.wait_notify, 1);
+        assert_eq!(stats.instr.categories.load_store, 1);
+        assert_eq!(stats.instr.categories.other, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn graphics() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "_glDrawElements" (func (param i32 i32 i32 i32)))
+            (import "env" "_glTexStorage2D" (func (param i32 i32 i32 i32 i32)))
+            (import "env" "_JS_Sound_Play" (func (param i32)))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.graphics, Some(GraphicsApi::Webgl2));
+        assert_eq!(stats.graphics_imports, 2);
+
+        let stats =
+            stats_from_wat(r#"(module (import "wasi_snapshot_preview1" "fd_write" (func)))"#)?;
+        assert_eq!(stats.graphics, None);
+        assert_eq!(stats.graphics_imports, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn threading() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "memory" (memory 256 32768 shared))
+            (import "env" "__pthread_create_js" (func (param i32 i32 i32 i32) (result i32)))
+            (func (export "_emscripten_thread_init") (param i32 i32 i32 i32 i32 i32))
+            (func (export "_emscripten_check_blocking_allowed"))
+            (func (param i32) (result i32)
+                (drop (memory.atomic.wait32 (local.get 0) (i32.const 0) (i64.const -1)))
+                (i32.atomic.rmw.add (local.get 0) (i32.const 1)))
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        let threading = &stats.threading;
+        assert!(threading.shared_memory);
+        assert_eq!(threading.atomic_instructions, 2);
+        assert_eq!(threading.wait_notify, 1);
+        assert_eq!(threading.runtime, Some(ThreadRuntime::EmscriptenPthreads));
+
+        // Atomics on a shared memory, but nothing to start threads with.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1 1 shared)
+            (func (param i32) (result i32)
+                (i32.atomic.load (local.get 0)))
+        )
+        "#,
+        )?;
+        let threading = &stats.threading;
+        assert!(threading.shared_memory);
+        assert_eq!(threading.atomic_instructions, 1);
+        assert_eq!(threading.wait_notify, 0);
+        assert_eq!(threading.runtime, None);
+
+        // Shared memories count towards the proposal, but aren't
+        // instructions.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "memory" (memory 1 1 shared))
+            (memory 1 1 shared)
+            (memory 1 1 shared))
+        "#,
+        )?;
+        assert_eq!(stats.instr.proposals.atomics, 2);
+        assert_eq!(stats.threading.atomic_instructions, 0);
+
+        let stats = stats_from_wat(
+            r#"(module (import "wbg" "__wbindgen_thread_id" (func (result i32))))"#,
+        )?;
+        assert!(!stats.threading.shared_memory);
+        assert_eq!(stats.threading.runtime, Some(ThreadRuntime::WasmBindgen));
+        Ok(())
+    }
+
+    #[test]
+    fn br_table() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (param i32)
+                block block block block block block block block block block
+                local.get 0
+                br_table 0 1 2 3 4 5 6 7 8 9
+                end end end end end end end end end end)
+            (func (param i32)
+                block block
+                local.get 0
+                br_table 0 1
+                end end)
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        let br_table = &stats.instr.br_table;
+        assert_eq!(br_table.count, 2);
+        assert_eq!(br_table.max_targets, 10);
+        assert_eq!(br_table.mean_targets, 6.0);
+        assert_eq!(
+            stats_from_wat("(module (func))")?
+                .instr
+                .br_table
+                .mean_targets,
+            0.0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mem_access() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1)
+            (func (param i32)
+                (drop (i32.load (local.get 0)))
+                (drop (i32.load align=1 (local.get 0)))
+                (drop (i64.load align=4 (local.get 0)))
+                (drop (i32.load8_u (local.get 0)))
+                (drop (f64.load (local.get 0)))
+                (i32.store16 align=1 (local.get 0) (i32.const 0))
+                (v128.store (local.get 0) (v128.const i64x2 0 0))
+                memory.size
+                drop)
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        let access = &stats.instr.mem_access;
+        assert_eq!(access.align_0, 3);
+        assert_eq!(access.align_1, 0);
+        assert_eq!(access.align_2, 2);
+        assert_eq!(access.align_3, 1);
+        assert_eq!(access.align_4_or_more, 1);
+        // `align=1` on the 4- and 2-byte accesses, and `align=4` on the i64.
+        assert_eq!(access.below_natural, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn multi_memory() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory $a 1)
+            (memory $b 1)
+            (func (export "f") (result i32)
+                (memory.copy $b $a (i32.const 0) (i32.const 0) (i32.const 1))
+                (i32.store $a (i32.const 0) (i32.load $b (i32.const 0)))
+                memory.size $a
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        // The second memory, memory.copy and i32.load.
+        assert_eq!(stats.instr.proposals.multi_memory, 3);
+        assert_eq!(stats.instr.categories.load_store, 2);
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (memory 1)
+            (func (result i32) (i32.load (i32.const 0)))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.instr.proposals.multi_memory, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn extended_const() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "base" (global $base i32))
+            (memory 1)
+            (table 2 funcref)
+            (global i32 (i32.add (i32.const 1) (i32.const 2)))
+            (global i32 (global.get $base))
+            (data (i32.add (global.get $base) (i32.const 16)) "hi")
+            (data (i32.const 0) "hi")
+            (elem (i32.mul (global.get $base) (i32.const 2)) func $f)
+            (elem (i32.const 0) funcref (ref.func $f))
+            (func $f)
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.instr.proposals.extended_const, 3);
+        assert!(stats.size.init > 0);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn function_references() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $f (func (param i32) (result i32)))
+            (type $g (func (param (ref $f)) (result i32)))
+            (table 1 (ref $f) (ref.func $id))
+            (global (ref $f) (ref.func $id))
+            (elem declare func $id)
+            (func $id (type $f) local.get 0)
+            (func (type $g)
+                (call_ref $f (i32.const 1) (local.get 0))
+                drop
+                (block $l
+                    (br_on_null $l (ref.null $f))
+                    drop
+                )
+                (return_call_ref $f (i32.const 2) (ref.as_non_null (local.get 0)))
+            )
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        // call_ref, br_on_null, ref.as_non_null and return_call_ref, plus
+        // the $g type, the table and the global.
+        assert_eq!(stats.instr.proposals.function_references, 7);
+        assert_eq!(stats.instr.proposals.tail_calls, 1);
+        assert_eq!(stats.instr.categories.indirect_calls, 2);
+        assert_eq!(stats.instr.proposals.gc, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn component() -> Result<()> {
+        let core = wat::parse_str("(module (func))")?;
+        let mut inner = b"\0asm\x0d\0\x01\0".to_vec();
+        inner.push(1);
+        inner.push(core.len() as u8);
+        inner.extend(&core);
+
+        let mut wasm = b"\0asm\x0d\0\x01\0".to_vec();
+        // A core module, a nested component with another core module, and
+        // an empty export section.
+        wasm.push(1);
+        wasm.push(core.len() as u8);
+        wasm.extend(&core);
+        wasm.push(4);
+        wasm.push(inner.len() as u8);
+        wasm.extend(&inner);
+        wasm.extend([11, 1, 0]);
+
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.binary_kind, BinaryKind::Component);
+        assert_eq!(stats.size.total, wasm.len());
+        assert_eq!(stats.component.core_modules, 2);
+        let sections: Vec<_> = stats
+            .component
+            .sections
+            .iter()
+            .map(|section| (section.kind.as_str(), section.size))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                ("core_module", core.len() + 1),
+                ("component", inner.len() + 1),
+                ("export", 2)
+            ]
+        );
+        assert_eq!(stats.funcs, 0);
+
+        let stats = stats_from_wat("(module)")?;
+        assert_eq!(stats.binary_kind, BinaryKind::Module);
+        assert_eq!(stats.component.core_modules, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn component_decode_errors() -> Result<()> {
+        // A custom section whose name runs past its end, then an export
+        // section that runs past the end of the input.
+        let mut wasm = b"\0asm\x0d\0\x01\0".to_vec();
+        wasm.extend([0, 2, 5, b'a']);
+        wasm.extend([11, 10, 0]);
+
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.binary_kind, BinaryKind::Component);
+        assert_eq!(stats.decode_errors.len(), 2, "{:?}", stats.decode_errors);
+        assert!(
+            stats.decode_errors[0].starts_with("custom section at offset 8:"),
+            "{}",
+            stats.decode_errors[0]
+        );
+        assert!(
+            stats.decode_errors[1].starts_with("export section at offset 12:"),
+            "{}",
+            stats.decode_errors[1]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deeply_nested_components() -> Result<()> {
+        const HEADER: &[u8] = b"\0asm\x0d\0\x01\0";
+        const DEPTH: usize = 100_000;
+
+        // Sizes are worked out from the inside out first, so that the
+        // components can be written from the outside in without copying.
+        let mut sizes = vec![HEADER.len()];
+        for _ in 0..DEPTH {
+            let inner = *sizes.last().unwrap();
+            let mut len = Vec::new();
+            write_leb(&mut len, inner);
+            sizes.push(HEADER.len() + 1 + len.len() + inner);
+        }
+        let mut wasm = Vec::with_capacity(sizes[DEPTH]);
+        for &inner in sizes[..DEPTH].iter().rev() {
+            wasm.extend(HEADER);
+            wasm.push(4);
+            write_leb(&mut wasm, inner);
+        }
+        wasm.extend(HEADER);
+        assert_eq!(wasm.len(), sizes[DEPTH]);
+
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(stats.binary_kind, BinaryKind::Component);
+        assert_eq!(stats.component.core_modules, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn import_modules() -> Result<()> {
+        let wat = r#"
+        (module
+            (import "wbg" "__wbg_log_1" (func))
+            (import "env" "memory" (memory 1))
+            (import "wbg" "__wbg_new_2" (func))
+            (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+        )
+        "#;
+        let stats = stats_from_wat(wat)?;
+        let modules: Vec<_> = stats
+            .import_modules
+            .iter()
+            .map(|(module, count)| (module.as_str(), *count))
+            .collect();
+        assert_eq!(
+            modules,
+            [("env", 1), ("wasi_snapshot_preview1", 1), ("wbg", 2)]
+        );
+        assert!(stats.import_names.is_none());
+
+        let options = Options {
+            import_names: true,
+            ..Default::default()
+        };
+        let stats = get_stats_with(&wat::parse_str(wat)?, &options)?;
+        let names: Vec<_> = stats
+            .import_names
+            .unwrap()
+            .into_iter()
+            .map(|import| format!("{}.{}", import.module, import.name))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "wbg.__wbg_log_1",
+                "env.memory",
+                "wbg.__wbg_new_2",
+                "wasi_snapshot_preview1.fd_write"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn wasi() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+            (import "env" "f" (func))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.wasi.as_deref(), Some("preview1"));
+        assert_eq!(stats.wasi_imports, 2);
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "wasi:io/streams@0.2.0" "[method]output-stream.write" (func (param i32)))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.wasi.as_deref(), Some("preview2"));
+        assert_eq!(stats.wasi_imports, 1);
+
+        for imports in [
+            [
+                r#"(import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))"#,
+                r#"(import "wasi:cli/exit@0.2.0" "exit" (func (param i32)))"#,
+            ],
+            [
+                r#"(import "wasi:cli/exit@0.2.0" "exit" (func (param i32)))"#,
+                r#"(import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))"#,
+            ],
+        ] {
+            let stats = stats_from_wat(&format!("(module {})", imports.join(" ")))?;
+            assert_eq!(stats.wasi.as_deref(), Some("preview2"), "{:?}", imports);
+            assert_eq!(stats.wasi_imports, 2);
+        }
+
+        let stats = stats_from_wat(r#"(module (import "env" "fd_write" (func)))"#)?;
+        assert_eq!(stats.wasi, None);
+        assert_eq!(stats.wasi_imports, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn custom_section_sizes() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        wasm.extend(custom_section(".debug_info", &[0; 20]));
+        wasm.extend(custom_section(".debug_line", &[0; 5]));
+        wasm.extend(custom_section(".debug_info", &[0; 3]));
+        let stats = get_stats(&wasm)?;
+        assert_eq!(stats.schema_version, SCHEMA_VERSION);
+        let sections: Vec<_> = stats
+            .custom_sections
+            .iter()
+            .map(|section| (section.name.as_str(), section.size))
+            .collect();
+        // Each section also has a one byte length, and the name with its
+        // one byte length.
+        assert_eq!(
+            sections,
+            [
+                (".debug_info", 33),
+                (".debug_line", 18),
+                (".debug_info", 16)
+            ]
+        );
+        assert_eq!(stats.size.custom, 33 + 18 + 16);
+        Ok(())
+    }
+
+    #[test]
+    fn debug_info() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        let url = "https://example.com/module.wasm.map";
+        let mut data = vec![url.len() as u8];
+        data.extend(url.as_bytes());
+        wasm.extend(custom_section("sourceMappingURL", &data));
+        wasm.extend(custom_section(".debug_info", &[0; 20]));
+        wasm.extend(custom_section(".debug_str", &[0; 4]));
+        wasm.extend(custom_section("build_id", &[1, 0xab]));
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert!(stats.debug_info.dwarf);
+        assert_eq!(stats.debug_info.dwarf_size, 33 + 16);
+        assert_eq!(stats.debug_info.source_map_url.as_deref(), Some(url));
+        assert!(stats.debug_info.build_id);
+        assert!(!stats.debug_info.external_debug_info);
+        assert!(!stats.is_stripped);
+
+        let stats = stats_from_wat("(module (func))")?;
+        assert!(!stats.debug_info.dwarf);
+        assert_eq!(stats.debug_info.source_map_url, None);
+        Ok(())
+    }
+
+    #[test]
+    fn target_features() -> Result<()> {
+        let mut wasm = wat::parse_str(
+            r#"
+        (module
+            (func (param v128) (result v128) (i8x16.add (local.get 0) (local.get 0)))
+        )
+        "#,
+        )?;
+        let mut data = vec![4];
+        for (prefix, name) in [
+            (b'+', "simd128"),
+            (b'+', "atomics"),
+            (b'-', "bulk-memory"),
+            (b'+', "mystery"),
+        ] {
+            data.push(prefix);
+            data.push(name.len() as u8);
+            data.extend(name.as_bytes());
+        }
+        wasm.extend(custom_section("target_features", &data));
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(
+            stats.target_features,
+            ["+simd128", "+atomics", "-bulk-memory", "+mystery"]
+        );
+        assert_eq!(stats.declared_but_unused_features, ["atomics"]);
+        Ok(())
+    }
+
+    #[test]
+    fn dylink_section() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        // A memory info subsection (memory size, alignment, table size and
+        // alignment) followed by a needed subsection with two libraries.
+        let mut data = vec![1, 5, 0x80, 0x02, 2, 3, 0, 2, 11, 2, 4];
+        data.extend(b"libc");
+        data.push(4);
+        data.extend(b"libm");
+        wasm.extend(custom_section("dylink.0", &data));
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        let dylink = stats.dylink.unwrap();
+        assert_eq!(dylink.memory_size, 256);
+        assert_eq!(dylink.memory_alignment, 2);
+        assert_eq!(dylink.table_size, 3);
+        assert_eq!(dylink.table_alignment, 0);
+        assert_eq!(dylink.needed, ["libc", "libm"]);
+        assert_eq!(stats.language, Language::Emscripten);
+
+        let mut wasm = wat::parse_str("(module (func))")?;
+        let mut data = vec![16, 4, 1, 0, 1, 6];
+        data.extend(b"libfoo");
+        wasm.extend(custom_section("dylink", &data));
+        let dylink = get_stats(&wasm)?.dylink.unwrap();
+        assert_eq!(dylink.memory_size, 16);
+        assert_eq!(dylink.needed, ["libfoo"]);
+        Ok(())
+    }
+
+    #[test]
+    fn producers_section() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        wasm.extend(custom_section(
+            "producers",
+            &producers(&[
+                ("language", &[("Rust", "")]),
+                (
+                    "processed-by",
+                    &[("rustc", "1.70.0"), ("wasm-bindgen", "0.2.87")],
+                ),
+            ]),
+        ));
+        let stats = get_stats(&wasm)?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert_eq!(custom_section_names(&stats), ["producers"]);
+        let producers = stats.producers.unwrap();
+        assert_eq!(producers.language.len(), 1);
+        assert_eq!(producers.language[0].name, "Rust");
+        assert_eq!(producers.processed_by.len(), 2);
+        assert_eq!(producers.processed_by[1].name, "wasm-bindgen");
+        assert_eq!(producers.processed_by[1].version, "0.2.87");
+        assert!(producers.sdk.is_empty());
+        assert_eq!(stats.funcs, 1);
+        assert_eq!(stats.optimized_with_binaryen, Some(false));
+        Ok(())
+    }
+
+    #[test]
+    fn optimized_with_binaryen() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        wasm.extend(custom_section(
+            "producers",
+            &producers(&[(
+                "processed-by",
+                &[("clang", "17.0.0"), ("Binaryen", "version 116")],
+            )]),
+        ));
+        wasm.extend(custom_section(
+            "target_features",
+            &[1, b'+', 4, b's', b'i', b'm', b'd'],
+        ));
+        assert_eq!(get_stats(&wasm)?.optimized_with_binaryen, Some(true));
+
+        // No evidence either way.
+        let mut wasm = wat::parse_str("(module (func))")?;
+        wasm.extend(custom_section(
+            "target_features",
+            &[1, b'+', 4, b's', b'i', b'm', b'd'],
+        ));
+        assert_eq!(get_stats(&wasm)?.optimized_with_binaryen, None);
+        Ok(())
+    }
+
+    #[test]
+    fn producers_section_malformed() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        let mut data = producers(&[("language", &[("Rust", "")])]);
+        data.truncate(data.len() - 2);
+        wasm.extend(custom_section("producers", &data));
+        let stats = get_stats(&wasm)?;
+        assert!(stats.producers.is_none());
+        assert_eq!(stats.decode_errors.len(), 1);
+        assert!(
+            stats.decode_errors[0].starts_with("Custom section at offset"),
+            "{}",
+            stats.decode_errors[0]
+        );
+        assert_eq!(custom_section_names(&stats), ["producers"]);
+        assert_eq!(stats.funcs, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn name_section() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module $m
+            (func $add (param $a i32) (param $b i32) (result i32)
+                (i32.add (local.get $a) (local.get $b)))
+            (func $nop)
+            (func)
+        )
+        "#,
+        )?;
+        assert!(stats.decode_errors.is_empty(), "{:?}", stats.decode_errors);
+        assert!(!stats.is_stripped);
+        let names = stats.names.unwrap();
+        assert!(names.module_name);
+        assert_eq!(names.functions, 2);
+        assert_eq!(names.locals, 2);
+        assert!(names.size > 0 && names.size < stats.size.custom);
+
+        let stats = stats_from_wat("(module (func))")?;
+        assert!(stats.names.is_none());
+        assert!(stats.is_stripped);
+
+        let mut wasm = wat::parse_str("(module (func))")?;
+        wasm.extend(custom_section(".debug_info", b"\0\0"));
+        assert!(!get_stats(&wasm)?.is_stripped);
+        Ok(())
+    }
+
+    #[test]
+    fn name_section_malformed() -> Result<()> {
+        let mut wasm = wat::parse_str("(module (func))")?;
+        // A function names subsection claiming more bytes than it has.
+        wasm.extend(custom_section("name", &[1, 10, 1, 0]));
+        let stats = get_stats(&wasm)?;
+        assert!(stats.names.is_none());
+        assert_eq!(stats.decode_errors.len(), 1);
+        assert!(!stats.is_stripped);
+        assert_eq!(stats.funcs, 1);
+        Ok(())
+    }
+
+    fn assert_language(
+        stats: &Stats,
+        language: Language,
+        confidence: Confidence,
+        evidence: &[&str],
+    ) {
+        assert_eq!(stats.language, language);
+        assert_eq!(stats.language_confidence, confidence);
+        assert_eq!(stats.language_evidence, evidence);
+    }
+
+    #[test]
+    fn infer_language_producers() -> Result<()> {
+        // The imports alone look like Emscripten.
+        let module = wat::parse_str(
+            r#"
+        (module
+            (import "env" "emscripten_memcpy_big" (func (param i32 i32 i32)))
+        )
+        "#,
+        )?;
+        let with_producers = |fields: &[(&str, &[(&str, &str)])]| -> Result<Stats> {
+            let mut wasm = module.clone();
+            wasm.extend(custom_section("producers", &producers(fields)));
+            get_stats(&wasm)
+        };
+
+        let stats = with_producers(&[("language", &[("Rust", "")])])?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::High,
+            &["producers language Rust"],
+        );
+        let stats = with_producers(&[("language", &[("Go", "1.21")])])?;
+        assert_language(
+            &stats,
+            Language::Go,
+            Confidence::High,
+            &["producers language Go"],
+        );
+        let stats = with_producers(&[("language", &[("AssemblyScript", "0.27")])])?;
+        assert_language(
+            &stats,
+            Language::AssemblyScript,
+            Confidence::High,
+            &["producers language AssemblyScript"],
+        );
+        let stats = with_producers(&[("processed-by", &[("wasm-bindgen", "0.2.87")])])?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::High,
+            &["producers processed-by wasm-bindgen"],
+        );
+
+        // Unrecognised producers fall back to the imports.
+        let stats = with_producers(&[("language", &[("Zig", "")])])?;
+        assert_language(
+            &stats,
+            Language::Emscripten,
+            Confidence::High,
+            &["import env.emscripten_memcpy_big"],
+        );
+
+        let mut wasm =
+            wat::parse_str(r#"(module (import "a" "a" (func)) (import "a" "b" (func)))"#)?;
+        wasm.extend(custom_section(
+            "producers",
+            &producers(&[
+                ("language", &[("C_plus_plus", "")]),
+                ("processed-by", &[("Emscripten", "3.1.40")]),
+            ]),
+        ));
+        assert_eq!(get_stats(&wasm)?.language, Language::Emscripten);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_unknown() -> Result<()> {
+        let stats = stats_from_wat("(module)")?;
+        assert!(stats.language_candidates.is_empty());
+        assert_language(&stats, Language::Unknown, Confidence::Low, &[]);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_rust() -> Result<()> {
+        // b63e9f90187a9f5cec9a7a9cfc15e68e9330979ac39a29258282c020780cd6ec.wasm
+        //
+        // exports mention 'wbindgen'
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t8 (func (param i32)))
+            (func $__wbindgen_malloc (type $t8) (param $p0 i32))
+            (export "__wbindgen_malloc" (func $__wbindgen_malloc))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::High,
+            &["export __wbindgen_malloc"],
+        );
+
+        // 82f052ee941598c3f70b9adfdebcb8fda239e5095e48d3e4a2edcc208b0c769c.wasm
+        //
+        // import references a 'wbg' module
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t2 (func (param i32)))
+            (import "wbg" "__wbindgen_object_drop_ref" (func $wasm_bindgen::__wbindgen_object_drop_ref::hc5b72d1598c36103 (type $t2)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::High,
+            &["import wbg.__wbindgen_object_drop_ref"],
+        );
+
+        // d792c9bfa765ab3e849bb2f266e1d2b19e555fc4a59c51d22a47fa73b27180b8.wasm
+        //
+        // import references a function containing 'wbg'
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t11 (func (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)))
+            (import "./source_compiler_bg.js" "__wbg_sourcerorLogCallback_9555c6dd7a1fa2a1" (func $./source_compiler_bg.js.__wbg_sourcerorLogCallback_9555c6dd7a1fa2a1 (type $t11)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::High,
+            &["import ./source_compiler_bg.js.__wbg_sourcerorLogCallback_9555c6dd7a1fa2a1"],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rust_packaging() -> Result<()> {
+        for (wat, packaging) in [
+            (
+                r#"(module (import "wbg" "__wbindgen_object_drop_ref" (func (param i32))))"#,
+                Some(RustPackaging::BindgenProcessed),
+            ),
+            (
+                r#"(module (import "./source_compiler_bg.js" "__wbg_log_9555c6dd7a1fa2a1" (func)))"#,
+                Some(RustPackaging::BindgenProcessed),
+            ),
+            (
+                r#"(module (import "__wbindgen_placeholder__" "__wbindgen_object_drop_ref" (func (param i32))))"#,
+                Some(RustPackaging::Raw),
+            ),
+            // Exports alone don't say which.
+            (
+                r#"(module (func (export "__wbindgen_malloc") (param i32)))"#,
+                None,
+            ),
+        ] {
+            let stats = stats_from_wat(wat)?;
+            assert_eq!(stats.language, Language::Rust, "{}", wat);
+            assert_eq!(stats.rust_packaging, packaging, "{}", wat);
+        }
+        let stats = stats_from_wat(r#"(module (import "env" "abort" (func)))"#)?;
+        assert_eq!(stats.rust_packaging, None);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_blazor() -> Result<()> {
+        // 9bd69204e55c94eb68b385ed4f79dffc752dc8fbccd526fd5c61d13a5df5d5de.wasm
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t4 (func (param i32 i32 i32) (result i32)))
+            (type $t8 (func (param i32 i32 i32 i32 i32) (result i32)))
+            (import "env" "mono_wasm_invoke_js_blazor" (func $env.mono_wasm_invoke_js_blazor (type $t8)))
+            (import "env" "emscripten_asm_const_int" (func $env.emscripten_asm_const_int (type $t4)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Blazor,
+            Confidence::High,
+            &["import env.mono_wasm_invoke_js_blazor"],
+        );
+        assert_eq!(stats.dotnet_mode, Some(DotnetMode::Interpreter));
+        // Emscripten is involved too.
+        assert_eq!(
+            stats.language_candidates,
+            [Language::Blazor, Language::Emscripten]
+        );
+
+        // dotnet.wasm builds without the blazor imports.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "mono_wasm_invoke_js" (func (param i32 i32) (result i32)))
+            (import "env" "mono_wasm_release_cs_owned_object" (func (param i32)))
+            (import "env" "emscripten_asm_const_int" (func (param i32 i32 i32) (result i32)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Blazor,
+            Confidence::High,
+            &["import env.mono_wasm_invoke_js"],
+        );
+        assert_eq!(stats.dotnet_mode, Some(DotnetMode::Interpreter));
+
+        let funcs = "(func)".repeat(language::DOTNET_AOT_MIN_FUNCS);
+        let stats = stats_from_wat(&format!(
+            r#"(module (import "env" "corehost_get_properties" (func)) {})"#,
+            funcs
+        ))?;
+        assert_language(
+            &stats,
+            Language::Blazor,
+            Confidence::High,
+            &["import env.corehost_get_properties"],
+        );
+        assert_eq!(stats.dotnet_mode, Some(DotnetMode::Aot));
+
+        assert_eq!(stats_from_wat("(module)")?.dotnet_mode, None);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_emscripten() -> Result<()> {
+        // 70c2f8e0269dd409da3153196ee3e4258f196d313ea271b1516c7fc241c52adb.wasm
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t3 (func (param i32) (result i32)))
+            (import "env" "_emscripten_asm_const_i" (func $env._emscripten_asm_const_i (type $t3)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Emscripten,
+            Confidence::High,
+            &["import env._emscripten_asm_const_i"],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_unity() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "emscripten_asm_const_int" (func (param i32 i32 i32) (result i32)))
+            (import "env" "il2cpp_codegen_register" (func (param i32)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Unity,
+            Confidence::High,
+            &["import env.il2cpp_codegen_register"],
+        );
+        assert_eq!(
+            stats.language_candidates,
+            [Language::Unity, Language::Emscripten]
+        );
+
+        let stats = stats_from_wat_scanning_data(
+            r#"
+        (module
+            (import "a" "a" (func))
+            (import "a" "b" (func))
+            (memory 1)
+            (data (i32.const 16) "UnityEngine.Object")
+        )
+        "#,
+            1024,
+        )?;
+        assert_language(
+            &stats,
+            Language::Unity,
+            Confidence::High,
+            &["data UnityEngine"],
+        );
+
+        // Plain Emscripten modules are unaffected.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "emscripten_asm_const_int" (func (param i32 i32 i32) (result i32)))
+            (memory 1)
+            (data (i32.const 16) "hello, world")
+        )
+        "#,
+        )?;
+        assert_eq!(stats.language, Language::Emscripten);
+        assert_eq!(stats.language_candidates, [Language::Emscripten]);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_data_strings() -> Result<()> {
+        let wat = |data: &str| {
+            format!(
+                r#"
+            (module
+                (import "a" "a" (func))
+                (import "a" "b" (func))
+                (memory 1)
+                (data (i32.const 0) "{}")
+            )
+            "#,
+                data
+            )
+        };
+
+        let rust =
+            wat("called `Option::unwrap()` on a `None` value/rustc/abc/library/core/src/option.rs");
+        let stats = stats_from_wat_scanning_data(&rust, 1024)?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::Medium,
+            &["data library/core/src/"],
+        );
+        assert_eq!(
+            stats.language_candidates,
+            [Language::Rust, Language::LikelyEmscripten]
+        );
+        // The scan is opt-in.
+        assert_eq!(stats_from_wat(&rust)?.language, Language::LikelyEmscripten);
+
+        let stats = stats_from_wat_scanning_data(&wat("\\00runtime.gopanic\\00"), 1024)?;
+        assert_language(
+            &stats,
+            Language::Go,
+            Confidence::Medium,
+            &["data runtime.gopanic"],
+        );
+
+        let stats = stats_from_wat_scanning_data(&wat("emscripten_get_now"), 1024)?;
+        assert_language(
+            &stats,
+            Language::Emscripten,
+            Confidence::Medium,
+            &["data emscripten_"],
+        );
+
+        // Patterns past the limit aren't seen.
+        let stats = stats_from_wat_scanning_data(&wat("0123456789runtime.gopanic"), 16)?;
+        assert_eq!(stats.language, Language::LikelyEmscripten);
+        Ok(())
+    }
+
+    #[test]
+    fn toolchain_version() -> Result<()> {
+        let mut wasm = wat::parse_str("(module)")?;
+        wasm.extend(custom_section(
+            "producers",
+            &producers(&[("processed-by", &[("wasm-bindgen", "0.2.87 (f0a8ae3b9)")])]),
+        ));
+        assert_eq!(
+            get_stats(&wasm)?.toolchain_version.as_deref(),
+            Some("0.2.87")
+        );
+
+        // Without a producers section, custom sections are searched.
+        let mut wasm = wat::parse_str("(module)")?;
+        wasm.extend(custom_section(
+            "emscripten_metadata",
+            b"built by Emscripten 3.1.45.",
+        ));
+        assert_eq!(
+            get_stats(&wasm)?.toolchain_version.as_deref(),
+            Some("3.1.45")
+        );
+
+        // Data segments are only searched when scanning is enabled.
+        let wat = r#"(module (memory 1) (data (i32.const 0) "wasm-bindgen 0.2.84"))"#;
+        assert_eq!(stats_from_wat(wat)?.toolchain_version, None);
+        assert_eq!(
+            stats_from_wat_scanning_data(wat, 1024)?
+                .toolchain_version
+                .as_deref(),
+            Some("0.2.84")
+        );
+
+        // Conflicting versions are dropped.
+        let mut wasm =
+            wat::parse_str(r#"(module (memory 1) (data (i32.const 0) "emscripten 3.1.45"))"#)?;
+        wasm.extend(custom_section("emscripten_metadata", b"emscripten 2.0.1"));
+        let options = Options {
+            scan_data: Some(1024),
+            ..Default::default()
+        };
+        assert_eq!(get_stats_with(&wasm, &options)?.toolchain_version, None);
+
+        assert_eq!(stats_from_wat("(module)")?.toolchain_version, None);
+        Ok(())
+    }
+
+    #[test]
+    fn start_and_entrypoint() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "init" (func))
+            (func $f)
+            (start $f)
+        )
+        "#,
+        )?;
+        assert!(stats.has_start);
+        let start = stats.start.unwrap();
+        assert_eq!(start.function, 1);
+        assert!(!start.imported);
+        assert_eq!(stats.entrypoint, Some(Entrypoint::Custom));
+
+        let stats = stats_from_wat(r#"(module (import "env" "init" (func)) (start 0))"#)?;
+        assert!(stats.start.unwrap().imported);
+
+        let stats = stats_from_wat(r#"(module (func (export "_start")))"#)?;
+        assert!(!stats.has_start);
+        assert!(stats.start.is_none());
+        assert_eq!(stats.entrypoint, Some(Entrypoint::WasiCommand));
+
+        let stats = stats_from_wat(r#"(module (func (export "_initialize")))"#)?;
+        assert_eq!(stats.entrypoint, Some(Entrypoint::WasiReactor));
+
+        assert_eq!(stats_from_wat("(module)")?.entrypoint, None);
+        Ok(())
+    }
+
+    #[test]
+    fn asyncify_exports() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (global $state (mut i32) (i32.const 0))
+            (func (export "asyncify_get_state") (result i32) global.get $state)
+            (func (export "asyncify_start_unwind") (param i32) i32.const 1 global.set $state)
+            (func $instrumented
+                global.get $state
+                if
+                    global.get $state
+                    drop
+                end)
+            (func $plain)
+        )
+        "#,
+        )?;
+        assert!(stats.asyncify);
+        assert_eq!(stats.asyncify_instrumented_funcs, Some(3));
+
+        let stats = stats_from_wat(r#"(module (func (export "asyncify_stop_rewind")))"#)?;
+        assert!(stats.asyncify);
+        assert_eq!(stats.asyncify_instrumented_funcs, None);
+
+        let stats = stats_from_wat(r#"(module (func (export "_start")))"#)?;
+        assert!(!stats.asyncify);
+        assert_eq!(stats.asyncify_instrumented_funcs, None);
+        Ok(())
+    }
+
+    #[test]
+    fn asyncify_name_section() -> Result<()> {
+        // Stripped of its exports, e.g. when the JS glue calls it internally,
+        // but with the state global still named.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (global $other (mut i32) (i32.const 0))
+            (global $__asyncify_state (mut i32) (i32.const 0))
+            (func global.get $__asyncify_state drop)
+            (func global.get $other drop)
+        )
+        "#,
+        )?;
+        assert!(stats.asyncify);
+        assert_eq!(stats.asyncify_instrumented_funcs, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn export_details() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (export "a")) (func (export "b")) (func (export "c"))
+            (memory (export "memory") 1)
+        )
+        "#,
+        )?;
+        let details = &stats.export_details;
+        assert_eq!(details.names, ["a", "b", "c", "memory"]);
+        assert_eq!(details.mean_name_length, 9.0 / 4.0);
+        assert!(details.looks_minified);
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func (export "_malloc")) (func (export "_free")) (func (export "a"))
+        )
+        "#,
+        )?;
+        assert!(!stats.export_details.looks_minified);
+
+        let options = Options {
+            export_names: 2,
+            ..Default::default()
+        };
+        let wasm = wat::parse_str(
+            "(module (func (export \"x\")) (func (export \"y\")) (func (export \"z\")))",
+        )?;
+        let stats = get_stats_with(&wasm, &options)?;
+        assert_eq!(stats.export_details.names, ["x", "y"]);
+        assert_eq!(stats.export_details.mean_name_length, 1.0);
+        assert_eq!(stats.exports.funcs, 3);
+
+        let stats = stats_from_wat("(module)")?;
+        assert!(stats.export_details.names.is_empty());
+        assert!(!stats.export_details.looks_minified);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_emscripten_exports() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "a" "a" (func (param i32)))
+            (import "a" "b" (func (param i32 i32)))
+            (table 2 funcref)
+            (func (export "stackSave") (result i32) i32.const 0)
+            (func (export "dynCall_vi") (param i32 i32)
+                (call_indirect (param i32) (local.get 1) (local.get 0)))
+            (func (export "dynCall_ii") (param i32 i32) (result i32) i32.const 0)
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Emscripten,
+            Confidence::Medium,
+            &["export stackSave", "export dynCall_vi"],
+        );
+        assert_eq!(
+            stats.language_candidates,
+            [Language::Emscripten, Language::LikelyEmscripten]
+        );
+        assert_eq!(stats.dyncall_exports, 2);
+
+        // The trampolines alone aren't enough.
+        let stats =
+            stats_from_wat(r#"(module (func (export "dynCall_v")) (func (export "dynCall_vi")))"#)?;
+        assert_eq!(stats.language, Language::Unknown);
+        assert_eq!(stats.dyncall_exports, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_go() -> Result<()> {
+        // 1b98798659012dc524343d1a44da2488fb09436fd6ca587c804ad272367d294d.wasm
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t1 (func (param i32)))
+            (import "go" "runtime.resetMemoryDataView" (func $go.runtime.resetMemoryDataView (type $t1)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Go,
+            Confidence::High,
+            &["import go.runtime.resetMemoryDataView"],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn go_details() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "go" "runtime.wasmExit" (func (param i32)))
+            (import "go" "runtime.nanotime1" (func (param i32)))
+            (import "go" "syscall/js.valueGet" (func (param i32)))
+            (import "go" "syscall/js.valueCall" (func (param i32)))
+            (import "go" "syscall/js.finalizeRef" (func (param i32)))
+            (import "go" "debug" (func (param i32)))
+        )
+        "#,
+        )?;
+        let go = stats.go_details.unwrap();
+        assert_eq!((go.imports, go.syscall_js, go.runtime), (6, 3, 2));
+
+        // Pure computation only needs the runtime.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "go" "runtime.wasmExit" (func (param i32)))
+            (import "go" "runtime.walltime" (func (param i32)))
+            (import "go" "runtime.getRandomData" (func (param i32)))
+        )
+        "#,
+        )?;
+        let go = stats.go_details.unwrap();
+        assert_eq!((go.imports, go.syscall_js, go.runtime), (3, 0, 3));
+
+        // Go 1.21 renamed the module.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "gojs" "runtime.wasmExit" (func (param i32)))
+            (import "gojs" "syscall/js.valueGet" (func (param i32)))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.language, Language::Go);
+        let go = stats.go_details.unwrap();
+        assert_eq!((go.imports, go.syscall_js, go.runtime), (2, 1, 1));
+
+        assert!(stats_from_wat("(module)")?.go_details.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_assemblyscript() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "abort" (func $abort (param i32 i32 i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "__new") (param i32 i32) (result i32) i32.const 0)
+            (func (export "__pin") (param i32) (result i32) local.get 0)
+            (func (export "__unpin") (param i32))
+            (func (export "__collect"))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::AssemblyScript,
+            Confidence::Medium,
+            &["import env.abort", "export __new"],
+        );
+
+        // Debug builds keep the standard library's names.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func $~lib/rt/itcms/__new (param i32 i32) (result i32) i32.const 0)
+            (func $assembly/index/add (export "add") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::AssemblyScript,
+            Confidence::High,
+            &["name ~lib/rt/itcms/__new"],
+        );
+
+        // Emscripten's `abort` takes no arguments.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "abort" (func))
+            (func (export "__new"))
+        )
+        "#,
+        )?;
+        assert_language(&stats, Language::Unknown, Confidence::Low, &[]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "wasmparser-fallback")]
+    fn infer_language_kotlin() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "js_code" "kotlin.captureStackTrace" (func (result externref)))
+            (import "js_code" "kotlin.wasm.internal.getJsEmptyString" (func (result externref)))
+            (func (export "main"))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Kotlin,
+            Confidence::High,
+            &["import js_code.kotlin.captureStackTrace"],
+        );
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $Any (sub (struct (field (mut i32)))))
+            (global (mut (ref null $Any)) (ref.null $Any))
+            (func (export "_initialize"))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Kotlin,
+            Confidence::Low,
+            &["export _initialize", "gc proposal"],
+        );
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func $kotlin.collections.ArrayList.add (param i32))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Kotlin,
+            Confidence::High,
+            &["name kotlin.collections.ArrayList.add"],
+        );
+
+        // Without GC types, it's just a WASI reactor.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+            (func (export "_initialize"))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::CClang,
+            Confidence::Low,
+            &["export _initialize"],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_clang() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 2)
+            (global (export "__heap_base") i32 (i32.const 66560))
+            (global (export "__data_end") i32 (i32.const 1024))
+            (func $malloc (param i32) (result i32) (i32.const 0))
+            (func (export "_start"))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::CClang,
+            Confidence::Medium,
+            &["export __heap_base", "name malloc"],
+        );
+
+        // Without the names, it could just as well be Zig or TinyGo.
+        let mut wasm = wat::parse_str(
+            r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+            (func (export "_start"))
+        )
+        "#,
+        )?;
+        assert_language(
+            &get_stats(&wasm)?,
+            Language::CClang,
+            Confidence::Low,
+            &["export _start"],
+        );
+        wasm.extend(custom_section(
+            "producers",
+            &producers(&[("processed-by", &[("clang", "17.0.6")])]),
+        ));
+        assert_language(
+            &get_stats(&wasm)?,
+            Language::CClang,
+            Confidence::Medium,
+            &["export _start", "producers processed-by clang"],
+        );
+
+        // With no imports at all, there's nothing to say it's WASI.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (global (export "__heap_base") i32 (i32.const 66560))
+            (func (export "_start"))
+        )
+        "#,
+        )?;
+        assert_language(&stats, Language::Unknown, Confidence::Low, &[]);
+
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+            (func $_ZN3foo3barEv)
+            (func $_start (export "_start") call $_ZN3foo3barEv)
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::CppClang,
+            Confidence::Medium,
+            &["export _start", "name _ZN3foo3barEv"],
+        );
+
+        // Rust's legacy mangling looks like C++, apart from the hash.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func $_ZN4core3fmt5write17h0123456789abcdefE)
+            (global (export "__heap_base") i32 (i32.const 1048576))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::Rust,
+            Confidence::Medium,
+            &["name _ZN4core3fmt5write17h0123456789abcdefE"],
+        );
+
+        // Importing from anywhere other than WASI rules it out.
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (import "env" "f" (func))
+            (func (export "_start"))
+        )
+        "#,
+        )?;
+        assert_language(&stats, Language::Unknown, Confidence::Low, &[]);
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_symbol_origins() -> Result<()> {
+        // Named functions in a module that imports from `env`, so that none
+        // of the rules match.
+        let module = |names: &[&str]| -> Result<Stats> {
+            let funcs: String = names
+                .iter()
+                .map(|name| format!("(func ${})", name))
+                .collect();
+            stats_from_wat(&format!(r#"(module (import "env" "f" (func)) {})"#, funcs))
+        };
+        let origins = |stats: &Stats| -> Vec<(String, usize)> {
+            stats
+                .symbol_origins
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect()
+        };
+
+        let stats = module(&[
+            "_ZNSt3__212basic_stringIcNS_11char_traitsIcEENS_9allocatorIcEEE6appendEPKc",
+            "_ZN7Physics5World4stepEf",
+            "_Z4mainv",
+            "main",
+        ])?;
+        assert_language(
+            &stats,
+            Language::CppClang,
+            Confidence::Low,
+            &["name _ZNSt3__212basic_stringIcNS_11char_traitsIcEENS_9allocatorIcEEE6appendEPKc"],
+        );
+        assert_eq!(
+            origins(&stats),
+            [("cpp".to_owned(), 3), ("other".to_owned(), 1)]
+        );
+
+        // The C++ runtime is linked into Emscripten builds too.
+        let stats = module(&["_ZN7Physics5World4stepEf", "dlmalloc", "dlfree", "sbrk"])?;
+        assert_language(
+            &stats,
+            Language::Emscripten,
+            Confidence::Low,
+            &["name dlmalloc"],
+        );
+        assert_eq!(
+            origins(&stats),
+            [
+                ("cpp".to_owned(), 1),
+                ("emscripten_allocator".to_owned(), 3)
+            ]
+        );
+
+        let stats = module(&[
+            "runtime.mallocgc",
+            "type..eq.main.point",
+            "go.buildid",
+            "main.main",
+        ])?;
+        assert_language(
+            &stats,
+            Language::Go,
+            Confidence::Low,
+            &["name runtime.mallocgc"],
+        );
+        assert_eq!(
+            origins(&stats),
+            [("go".to_owned(), 3), ("other".to_owned(), 1)]
+        );
+
+        let stats = module(&["main", "f"])?;
+        assert_language(&stats, Language::Unknown, Confidence::Low, &[]);
+        assert_eq!(origins(&stats), [("other".to_owned(), 2)]);
+
+        // Rust symbols are already matched by the Rust rule, so the names
+        // aren't classified.
+        let stats = module(&[
+            "_ZN4core9panicking5panic17h2c0a6e2f0b3c1d4eE",
+            "_ZN5alloc7raw_vec11finish_grow17h8a9b0c1d2e3f4a5bE",
+        ])?;
+        assert_eq!(stats.language, Language::Rust);
+        assert_eq!(stats.language_confidence, Confidence::Medium);
+        assert!(stats.symbol_origins.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn detected_libraries() -> Result<()> {
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (func $sqlite3_open (param i32 i32) (result i32) i32.const 0)
+            (func $png_create_read_struct)
+            (func $main)
+            (func (export "FT_Init_FreeType"))
+        )
+        "#,
+        )?;
+        assert_eq!(stats.detected_libraries, ["libpng", "freetype", "sqlite"]);
+        assert!(stats_from_wat("(module (func $main))")?
+            .detected_libraries
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn infer_language_likely_emscripten() -> Result<()> {
+        // 38049c6cc89d4c6ac8c2635fc0af29901109d68247ba7e57d2bff551216a322e.wasm
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t4 (func (param i32 i32 i32) (result i32)))
+            (import "a" "a" (func $a.a (type $t4)))
+            (import "a" "b" (func $a.b (type $t4)))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::LikelyEmscripten,
+            Confidence::Low,
+            &["import a.a", "import a.b"],
+        );
+
+        // f50ed354fd14cce39533af5fc58c0e4387a326748114c57a2ce3c98611da673b.wasm
+        let stats = stats_from_wat(
+            r#"
+        (module
+            (type $t6 (func (param i32 i32 i32 i32)))
+            (import "env" "b" (func $env.b (type $t6)))
+            (import "env" "a" (global $env.a i32))
+        )
+        "#,
+        )?;
+        assert_language(
+            &stats,
+            Language::LikelyEmscripten,
+            Confidence::Low,
+            &["import env.a", "import env.b"],
+        );
+
+        Ok(())
+    }
+
+    /// Drops descriptions, which come from doc comments and can be reworded
+    /// without changing the shape of the records.
+    fn without_descriptions(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.remove("description");
+                map.values_mut().for_each(without_descriptions);
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(without_descriptions),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn schema_matches_version() -> Result<()> {
+        let path = format!(
+            "{}/schema/v{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            SCHEMA_VERSION
+        );
+        let mut expected: serde_json::Value = match std::fs::read_to_string(&path) {
+            Ok(snapshot) => serde_json::from_str(&snapshot)?,
+            Err(_) => serde_json::Value::Null,
+        };
+        let mut actual = schema();
+        without_descriptions(&mut expected);
+        without_descriptions(&mut actual);
+        assert!(
+            expected == actual,
+            "Stats no longer matches {}; bump SCHEMA_VERSION, add the new version to \
+             CHANGELOG.md and replace the snapshot with the output of schema()",
+            path
+        );
+        Ok(())
+    }
+}
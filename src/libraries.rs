@@ -0,0 +1,200 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Well-known C libraries that a module statically links, going by the
+//! function names in its name section and its exports.
+//!
+//! This is a lower bound: stripped modules have no names to go on, and C++
+//! wrappers only show up under their mangled names.
+
+enum Pattern {
+    /// The start of the name, matching case.
+    Prefix(&'static str),
+    /// The whole name.
+    Exact(&'static str),
+}
+
+use Pattern::*;
+
+/// Patterns for each library, in the order they're reported. Prefixes are
+/// chosen to be specific to the library, so `deflate_` (its internal
+/// strategies) rather than `deflate`.
+const LIBRARIES: &[(&str, &[Pattern])] = &[
+    (
+        "zlib",
+        &[
+            Prefix("deflate_"),
+            Prefix("deflateInit"),
+            Prefix("inflateInit"),
+            Exact("adler32"),
+        ],
+    ),
+    ("libpng", &[Prefix("png_")]),
+    ("libjpeg", &[Prefix("jpeg_")]),
+    ("libwebp", &[Prefix("WebPDecode"), Prefix("WebPEncode")]),
+    ("freetype", &[Prefix("FT_")]),
+    (
+        "harfbuzz",
+        &[Prefix("hb_buffer_"), Prefix("hb_font_"), Prefix("hb_shape")],
+    ),
+    ("sqlite", &[Prefix("sqlite3_")]),
+    // ICU suffixes its symbols with its major version, e.g. `u_strlen_72`.
+    (
+        "icu",
+        &[
+            Prefix("u_strlen"),
+            Prefix("ucnv_"),
+            Prefix("ubrk_"),
+            Prefix("ucol_"),
+        ],
+    ),
+    ("ffmpeg", &[Prefix("avcodec_"), Prefix("avformat_")]),
+];
+
+fn matches(pattern: &Pattern, name: &str) -> bool {
+    match *pattern {
+        Prefix(prefix) => name.starts_with(prefix),
+        Exact(exact) => name == exact,
+    }
+}
+
+/// The libraries that any of `names` belongs to, in table order.
+pub fn detect<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut found = vec![false; LIBRARIES.len()];
+    for name in names {
+        for (i, (_, patterns)) in LIBRARIES.iter().enumerate() {
+            if !found[i] && patterns.iter().any(|p| matches(p, name)) {
+                found[i] = true;
+            }
+        }
+    }
+    LIBRARIES
+        .iter()
+        .zip(found)
+        .filter(|(_, found)| *found)
+        .map(|((library, _), _)| (*library).to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_detects(library: &str, names: &[&str], lookalikes: &[&str]) {
+        for name in names {
+            assert_eq!(detect([*name]), [library], "{}", name);
+        }
+        for name in lookalikes {
+            assert!(detect([*name]).is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn zlib() {
+        assert_detects(
+            "zlib",
+            &["deflate_slow", "deflateInit2_", "inflateInit_", "adler32"],
+            &["deflate", "inflate", "adler32_combine_", "my_deflate_"],
+        );
+    }
+
+    #[test]
+    fn libpng() {
+        assert_detects(
+            "libpng",
+            &["png_create_read_struct", "png_read_info"],
+            &["load_png_", "PNG_read"],
+        );
+    }
+
+    #[test]
+    fn libjpeg() {
+        assert_detects(
+            "libjpeg",
+            &["jpeg_read_header", "jpeg_start_decompress"],
+            &["decode_jpeg_", "jpeg"],
+        );
+    }
+
+    #[test]
+    fn libwebp() {
+        assert_detects(
+            "libwebp",
+            &["WebPDecodeRGBA", "WebPEncodeLosslessRGBA"],
+            &["WebPGetInfo", "webpdecode"],
+        );
+    }
+
+    #[test]
+    fn freetype() {
+        assert_detects(
+            "freetype",
+            &["FT_Init_FreeType", "FT_Load_Glyph"],
+            &["ft_smooth_render", "SOFT_reset"],
+        );
+    }
+
+    #[test]
+    fn harfbuzz() {
+        assert_detects(
+            "harfbuzz",
+            &["hb_buffer_create", "hb_font_get_glyph", "hb_shape_full"],
+            &["hb_blob_", "thb_font_"],
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        assert_detects(
+            "sqlite",
+            &["sqlite3_open", "sqlite3_prepare_v2"],
+            &["sqlite_open", "my_sqlite3_"],
+        );
+    }
+
+    #[test]
+    fn icu() {
+        assert_detects(
+            "icu",
+            &[
+                "u_strlen_72",
+                "ucnv_open_72",
+                "ubrk_open",
+                "ucol_strcoll_74",
+            ],
+            &["u_str", "menu_strlen"],
+        );
+    }
+
+    #[test]
+    fn ffmpeg() {
+        assert_detects(
+            "ffmpeg",
+            &["avcodec_send_packet", "avformat_open_input"],
+            &["av_malloc", "avcodec"],
+        );
+    }
+
+    #[test]
+    fn several_libraries() {
+        let names = [
+            "main",
+            "sqlite3_open",
+            "png_read_info",
+            "png_create_read_struct",
+        ];
+        assert_eq!(detect(names), ["libpng", "sqlite"]);
+    }
+}
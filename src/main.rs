@@ -13,550 +13,470 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-use anyhow::{anyhow, Result};
+mod aggregate;
+mod bq;
+mod cli;
+mod compression;
+mod diff;
+mod errors;
+#[cfg(feature = "fetch")]
+mod fetch;
+mod inputs;
+mod output;
+mod progress;
+mod select;
+mod text;
+
+use aggregate::Aggregate;
+use anyhow::{anyhow, Context, Result};
+use cli::{Args, Command, ErrorRecords, ShowProgress};
+use errors::{ErrorRecord, Panic, Skipped};
+use inputs::{Bytes, HttpInfo, Input, Source};
+use output::RecordWriter;
+use progress::{Progress, Summary};
+use schemars::JsonSchema;
+use select::Selection;
 use serde::Serialize;
-use std::{io::Write, path::PathBuf};
-use wasmbin::{
-    builtins::Blob,
-    sections::{ExportDesc, FuncBody, ImportDesc, Section},
-    types::ValueType,
-    visit::Visit,
+use sha2::{Digest, Sha256};
+use std::{
+    io::{IsTerminal, Read, Write},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    time::Duration,
 };
-use written_size::WrittenSize;
-
-#[derive(Default, Debug, Serialize)]
-struct ProposalStats {
-    atomics: usize,
-    ref_types: usize,
-    simd: usize,
-    tail_calls: usize,
-    bulk: usize,
-    multi_value: usize,
-    non_trapping_conv: usize,
-    sign_extend: usize,
-    mutable_externals: usize,
-    bigint_externals: usize,
+use text::InputFormat;
+use wasm_stats::{get_stats_with, Options, Stats};
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct Record<'a> {
+    file: &'a str,
+    file_size: u64,
+    sha256: String,
+    input_format: InputFormat,
+    /// The response, for inputs fetched from a URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http: Option<HttpInfo>,
+    #[serde(flatten)]
+    stats: Stats,
 }
 
-#[derive(Serialize, Eq, PartialEq, Hash, Debug)]
-enum Language {
-    Rust,
-    Emscripten,
-    // a category for WebAssembly modules where there is some evidence that
-    // it is Emscripten, but the methods used are not terribly reliable.
-    LikelyEmscripten,
-    AssemblyScript,
-    Blazor,
-    Unknown,
-    Go,
+/// The statistics for an input, along with the size and hash of its bytes as
+/// read.
+struct Analysis {
+    file_size: u64,
+    sha256: String,
+    input_format: InputFormat,
+    http: Option<HttpInfo>,
+    stats: Stats,
 }
 
-impl Default for Language {
-    fn default() -> Self {
-        Language::Unknown
-    }
+/// Lowercase hex SHA-256 of the given bytes.
+fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
 }
 
-#[derive(Default, Debug, Serialize)]
-struct InstructionCategoryStats {
-    load_store: usize,
-    local_var: usize,
-    global_var: usize,
-    table: usize,
-    memory: usize,
-    control_flow: usize,
-    direct_calls: usize,
-    indirect_calls: usize,
-    constants: usize,
-    wait_notify: usize,
-    other: usize,
+/// What's needed from the command line to analyse one input, owned so that
+/// it can be sent to a thread of its own for `--timeout`.
+#[derive(Clone)]
+struct Settings {
+    decompress: Option<compression::Encoding>,
+    max_size: Option<u64>,
+    /// The Brotli quality to estimate compressed sizes with, if at all.
+    estimate_compression: Option<u32>,
+    options: Options,
+    /// `--timeout`, and the places on the threads that inputs are analysed
+    /// on with it.
+    timeout: Option<(Duration, Arc<Places>)>,
+    /// Extra time each input takes to analyse, to test `--timeout`.
+    #[cfg(test)]
+    delay: Duration,
 }
 
-#[derive(Default, Debug, Serialize)]
-struct InstructionStats {
-    total: usize,
-    proposals: ProposalStats,
-    categories: InstructionCategoryStats,
-}
+impl Settings {
+    fn new(args: &Args, jobs: usize) -> Self {
+        let mut options = Options::default();
+        options.scan_data = args.scan_data.then_some(args.scan_data_limit);
+        options.top_functions = args.top_functions;
+        options.opcodes = args.opcodes;
+        options.import_names = args.import_names;
+        options.export_names = args.export_names;
+        options.data_contents = args.data_contents.then_some(args.data_contents_limit);
+        options.validate = args.validate;
+        options.timings = args.timings;
+        Settings {
+            decompress: args.decompress,
+            max_size: args.max_size,
+            estimate_compression: args.estimate_compression.then_some(args.brotli_quality),
+            options,
+            timeout: args
+                .timeout
+                .map(|secs| (Duration::from_secs(secs), Places::new(jobs))),
+            #[cfg(test)]
+            delay: Duration::ZERO,
+        }
+    }
 
-#[derive(Default, Debug, Serialize)]
-struct SizeStats {
-    code: usize,
-    init: usize,
-    externals: usize,
-    types: usize,
-    custom: usize,
-    descriptors: usize,
-    total: usize,
+    /// Fails with [`Skipped::TooLarge`] if `size` is over `--max-size`.
+    fn check_size(&self, size: usize) -> Result<()> {
+        match self.max_size {
+            Some(max) if size as u64 > max => Err(Skipped::TooLarge {
+                size: size as u64,
+                max,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
 }
 
-#[derive(Default, Debug, Serialize)]
-struct ExternalStats {
-    funcs: usize,
-    memories: usize,
-    globals: usize,
-    tables: usize,
+fn analyse(name: &str, bytes: Bytes, settings: &Settings) -> Result<Analysis> {
+    // Hash the input before decompression, so that it matches the crawled
+    // response body.
+    let file_size = bytes.len() as u64;
+    let sha256 = sha256(&bytes);
+    let decoded = compression::decompress(name, bytes, settings.decompress)?;
+    settings.check_size(decoded.wasm.len())?;
+    let (wasm, input_format) = text::assemble(name, decoded.wasm)?;
+    #[cfg(test)]
+    std::thread::sleep(settings.delay);
+    let mut stats = get_stats_with(&wasm, &settings.options)?;
+    stats.size.compressed = decoded.compressed_size;
+    if let Some(quality) = settings.estimate_compression {
+        let estimate = compression::estimate(&wasm, quality);
+        stats.size.gzip = Some(estimate.gzip);
+        stats.size.brotli = Some(estimate.brotli);
+    }
+    Ok(Analysis {
+        file_size,
+        sha256,
+        input_format,
+        http: None,
+        stats,
+    })
 }
 
-#[derive(Default, Debug, Serialize)]
-struct Stats {
-    funcs: usize,
-    language: Language,
-    instr: InstructionStats,
-    size: SizeStats,
-    imports: ExternalStats,
-    exports: ExternalStats,
-    custom_sections: Vec<String>,
-    has_start: bool,
+/// A fixed number of places for threads analysing inputs, to bound how many
+/// run at once.
+struct Places {
+    free: Mutex<usize>,
+    freed: Condvar,
 }
 
-fn calc_size(wasm: &impl wasmbin::io::Encode) -> Result<usize> {
-    let mut written_size = WrittenSize::new();
-    wasm.encode(&mut written_size)?;
-    Ok(written_size.size() as usize)
-}
+impl Places {
+    fn new(count: usize) -> Arc<Self> {
+        Arc::new(Places {
+            free: Mutex::new(count),
+            freed: Condvar::new(),
+        })
+    }
 
-fn get_instruction_stats(funcs: &[Blob<FuncBody>]) -> Result<InstructionStats> {
-    use wasmbin::instructions::{simd::SIMD, Instruction as I, Misc as M};
-
-    let mut stats = InstructionStats::default();
-    for func in funcs {
-        let func = &func.try_contents()?.expr;
-        stats.total += func.len();
-        for i in func {
-            match i {
-                I::BlockStart(_)
-                | I::LoopStart(_)
-                | I::IfStart(_)
-                | I::IfElse
-                | I::End
-                | I::Unreachable
-                | I::Br(_)
-                | I::BrIf(_)
-                | I::BrTable { .. }
-                | I::Return
-                | I::Select
-                | I::SelectWithTypes(_)
-                | I::Nop
-                | I::Drop => stats.categories.control_flow += 1,
-                I::SIMD(i) => {
-                    stats.proposals.simd += 1;
-                    match i {
-                        SIMD::V128Load(_)
-                        | SIMD::V128Load8x8S(_)
-                        | SIMD::V128Load8x8U(_)
-                        | SIMD::V128Load16x4S(_)
-                        | SIMD::V128Load16x4U(_)
-                        | SIMD::V128Load32x2S(_)
-                        | SIMD::V128Load32x2U(_)
-                        | SIMD::V128Load8Splat(_)
-                        | SIMD::V128Load16Splat(_)
-                        | SIMD::V128Load32Splat(_)
-                        | SIMD::V128Load64Splat(_)
-                        | SIMD::V128Store(_)
-                        | SIMD::V128Load8Lane(_, _)
-                        | SIMD::V128Load16Lane(_, _)
-                        | SIMD::V128Load32Lane(_, _)
-                        | SIMD::V128Load64Lane(_, _)
-                        | SIMD::V128Store8Lane(_, _)
-                        | SIMD::V128Store16Lane(_, _)
-                        | SIMD::V128Store32Lane(_, _)
-                        | SIMD::V128Store64Lane(_, _) => stats.categories.load_store += 1,
-                        SIMD::V128Const(_) => stats.categories.constants += 1,
-                        _ => stats.categories.other += 1,
-                    }
-                }
-                I::Atomic(i) => {
-                    stats.proposals.atomics += 1;
-                    match i {
-                        wasmbin::instructions::Atomic::Wake(_)
-                        | wasmbin::instructions::Atomic::I32Wait(_)
-                        | wasmbin::instructions::Atomic::I64Wait(_) => {
-                            stats.categories.wait_notify += 1;
-                        }
-                        wasmbin::instructions::Atomic::I32Load(_)
-                        | wasmbin::instructions::Atomic::I64Load(_)
-                        | wasmbin::instructions::Atomic::I32Load8U(_)
-                        | wasmbin::instructions::Atomic::I32Load16U(_)
-                        | wasmbin::instructions::Atomic::I64Load8U(_)
-                        | wasmbin::instructions::Atomic::I64Load16U(_)
-                        | wasmbin::instructions::Atomic::I64Load32U(_)
-                        | wasmbin::instructions::Atomic::I32Store(_)
-                        | wasmbin::instructions::Atomic::I64Store(_)
-                        | wasmbin::instructions::Atomic::I32Store8(_)
-                        | wasmbin::instructions::Atomic::I32Store16(_)
-                        | wasmbin::instructions::Atomic::I64Store8(_)
-                        | wasmbin::instructions::Atomic::I64Store16(_)
-                        | wasmbin::instructions::Atomic::I64Store32(_) => {
-                            stats.categories.load_store += 1;
-                        }
-                        _ => stats.categories.other += 1,
-                    }
-                }
-                I::RefFunc(_) | I::RefIsNull | I::RefNull(_) => {
-                    stats.proposals.ref_types += 1;
-                    match i {
-                        I::RefIsNull => stats.categories.other += 1,
-                        _ => stats.categories.constants += 1,
-                    }
-                }
-                I::Misc(i) => match i {
-                    M::MemoryInit { .. }
-                    | M::MemoryCopy { .. }
-                    | M::MemoryFill(_)
-                    | M::DataDrop(_) => {
-                        stats.proposals.bulk += 1;
-                        stats.categories.memory += 1;
-                    }
-                    M::TableInit { .. }
-                    | M::TableCopy { .. }
-                    | M::TableFill(_)
-                    | M::ElemDrop(_) => {
-                        stats.proposals.bulk += 1;
-                        stats.categories.table += 1;
-                    }
-                    M::TableGrow(_) | M::TableSize(_) => {
-                        stats.proposals.ref_types += 1;
-                        stats.categories.table += 1;
-                    }
-                    M::I32TruncSatF32S
-                    | M::I32TruncSatF32U
-                    | M::I32TruncSatF64S
-                    | M::I32TruncSatF64U
-                    | M::I64TruncSatF32S
-                    | M::I64TruncSatF32U
-                    | M::I64TruncSatF64S
-                    | M::I64TruncSatF64U => {
-                        stats.proposals.non_trapping_conv += 1;
-                        stats.categories.other += 1;
-                    }
-                },
-                I::Call(_) => stats.categories.direct_calls += 1,
-                I::CallIndirect(_) => stats.categories.indirect_calls += 1,
-                I::ReturnCall(_) => {
-                    stats.categories.control_flow += 1;
-                    stats.categories.direct_calls += 1;
-                    stats.proposals.tail_calls += 1;
-                }
-                I::ReturnCallIndirect(_) => {
-                    stats.categories.control_flow += 1;
-                    stats.categories.indirect_calls += 1;
-                    stats.proposals.tail_calls += 1;
-                }
-                I::I32Const(_) | I::I64Const(_) | I::F32Const(_) | I::F64Const(_) => {
-                    stats.categories.constants += 1
-                }
-                I::LocalGet(_) | I::LocalSet(_) | I::LocalTee(_) => {
-                    stats.categories.local_var += 1;
-                }
-                I::GlobalGet(_) | I::GlobalSet(_) => {
-                    stats.categories.global_var += 1;
-                }
-                I::TableGet(_) | I::TableSet(_) => {
-                    stats.categories.table += 1;
-                }
-                I::I32Load(_)
-                | I::I64Load(_)
-                | I::F32Load(_)
-                | I::F64Load(_)
-                | I::I32Load8S(_)
-                | I::I32Load8U(_)
-                | I::I32Load16S(_)
-                | I::I32Load16U(_)
-                | I::I64Load8S(_)
-                | I::I64Load8U(_)
-                | I::I64Load16S(_)
-                | I::I64Load16U(_)
-                | I::I64Load32S(_)
-                | I::I64Load32U(_)
-                | I::I32Store(_)
-                | I::I64Store(_)
-                | I::F32Store(_)
-                | I::F64Store(_)
-                | I::I32Store8(_)
-                | I::I32Store16(_)
-                | I::I64Store8(_)
-                | I::I64Store16(_)
-                | I::I64Store32(_) => {
-                    stats.categories.load_store += 1;
-                }
-                I::MemorySize(_) | I::MemoryGrow(_) => {
-                    stats.categories.memory += 1;
-                }
-                I::I64ExtendI32U
-                | I::I32Extend8S
-                | I::I32Extend16S
-                | I::I64Extend8S
-                | I::I64Extend16S
-                | I::I64Extend32S => {
-                    stats.proposals.sign_extend += 1;
-                    stats.categories.other += 1;
-                }
-                _ => {
-                    stats.categories.other += 1;
-                }
-            }
+    /// Waits for a free place, which is given back when the returned guard
+    /// is dropped.
+    fn take(self: &Arc<Self>) -> Place {
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        while *free == 0 {
+            free = self.freed.wait(free).unwrap_or_else(|e| e.into_inner());
         }
+        *free -= 1;
+        Place(Arc::clone(self))
     }
-    Ok(stats)
 }
 
-macro_rules! get_external_stats {
-    ($section:expr, $ns:path) => {{
-        use $ns::*;
+struct Place(Arc<Places>);
 
-        let mut stats = ExternalStats::default();
-
-        for external in $section {
-            match external.desc {
-                Func(_) => stats.funcs += 1,
-                Global(_) => stats.globals += 1,
-                Mem(_) => stats.memories += 1,
-                Table(_) => stats.tables += 1,
-            }
-        }
+impl Drop for Place {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.freed.notify_one();
+    }
+}
 
-        stats
-    }};
+/// Runs [`analyse`] on a thread of its own, giving up on it after `timeout`.
+///
+/// There's no way to stop the thread, so one that runs over is left to
+/// finish in the background and its result is dropped. It keeps its place
+/// until then, so that there are never more than `--jobs` analyses running,
+/// however many have been given up on.
+fn analyse_with_timeout(
+    name: &str,
+    bytes: Bytes,
+    settings: &Settings,
+    timeout: Duration,
+    places: &Arc<Places>,
+) -> Result<Analysis> {
+    let (name, settings) = (name.to_owned(), settings.clone());
+    let (sender, receiver) = mpsc::channel();
+    let place = places.take();
+    std::thread::spawn(move || {
+        let _place = place;
+        let _ = sender.send(catch_panics(|| analyse(&name, bytes, &settings)));
+    });
+    receiver
+        .recv_timeout(timeout)
+        .unwrap_or_else(|_| Err(Skipped::Timeout(timeout).into()))
 }
 
-struct MaybeExternal<T> {
-    pub value: T,
-    pub is_external: bool,
+/// Turns a panic into an error, so that it only fails the input at hand.
+fn catch_panics<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    // Inputs share nothing but `Args`, which is only read, so carrying on
+    // after a panic is safe.
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(Panic::new(payload).into()))
 }
 
-impl<T> MaybeExternal<T> {
-    fn external(self) -> Option<T> {
-        if self.is_external {
-            Some(self.value)
-        } else {
-            None
-        }
+/// How many inputs each job gets per batch. Results are held until their
+/// whole batch is done, so this bounds memory use while giving slow inputs
+/// a chance to overlap with others.
+const INPUTS_PER_JOB: usize = 4;
+
+/// The outcome of analysing an input, with the number of bytes read if it
+/// could be read.
+type Analysed = (Option<usize>, Result<Analysis>);
+
+/// Reads an input, fetching it with `--max-size` as a cap if it's a URL.
+#[cfg_attr(not(feature = "fetch"), allow(unused_variables))]
+fn read_input(
+    input: &Input,
+    settings: &Settings,
+    stdin: &mut impl Read,
+) -> Result<(Bytes, Option<HttpInfo>)> {
+    #[cfg(feature = "fetch")]
+    if let Source::Url(url) = &input.source {
+        let fetched = fetch::fetch(url, settings.max_size)?;
+        return Ok((fetched.body.into(), Some(fetched.http)));
     }
+    Ok((input.read(stdin)?, None))
 }
 
-fn infer_language(module: &wasmbin::Module) -> Result<Language> {
-    let mut imports = Vec::new();
-    let mut exports = Vec::new();
-
-    for section in &module.sections {
-        match section {
-            Section::Import(section) => {
-                let section = section.try_contents()?;
-                for import in section {
-                    imports.push(&import.path);
-                }
+fn analyse_input(input: &Input, settings: &Settings, stdin: &mut impl Read) -> Analysed {
+    let mut size = None;
+    let result = catch_panics(|| {
+        let (bytes, http) = read_input(input, settings, stdin)?;
+        size = Some(bytes.len());
+        settings.check_size(bytes.len())?;
+        let analysis = match &settings.timeout {
+            Some((timeout, places)) => {
+                analyse_with_timeout(&input.name, bytes, settings, *timeout, places)
             }
-            Section::Export(section) => {
-                let section = section.try_contents()?;
-                for export in section {
-                    exports.push(export);
+            None => analyse(&input.name, bytes, settings),
+        }?;
+        Ok(Analysis { http, ..analysis })
+    });
+    (size, result)
+}
+
+/// Analyses a batch of inputs on up to `jobs` threads, returning the results
+/// in input order. Stdin is read on the calling thread.
+fn analyse_batch(
+    inputs: &[Input],
+    settings: &Settings,
+    jobs: usize,
+    stdin: &mut impl Read,
+) -> Vec<Analysed> {
+    let mut results: Vec<Option<Analysed>> = inputs
+        .iter()
+        .map(|input| match input.source {
+            Source::Stdin => Some(analyse_input(input, settings, stdin)),
+            _ => None,
+        })
+        .collect();
+    let next = AtomicUsize::new(0);
+    let pending = &results;
+    let done: Vec<(usize, Analysed)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(inputs.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let input = match inputs.get(i) {
+                            Some(input) => input,
+                            None => return done,
+                        };
+                        if pending[i].is_none() {
+                            done.push((i, analyse_input(input, settings, &mut std::io::empty())));
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("analysis thread panicked"))
+            .collect()
+    });
+    for (i, analysed) in done {
+        results[i] = Some(analysed);
+    }
+    results.into_iter().flatten().collect()
+}
+
+/// Analyses the inputs and writes one record per line to `out`, in input
+/// order, using up to `--jobs` threads.
+///
+/// Failures are reported to `err`, and optionally as error records to `out`,
+/// and don't stop the rest of the batch; the counts of each outcome are
+/// returned. With `--aggregate`, a single summary is written at the end
+/// instead.
+///
+/// The progress bar is drawn on `err` with `--progress always`; `main` turns
+/// `auto` into one of the others, since only it knows what `err` is.
+fn run(
+    inputs: &[Input],
+    args: &Args,
+    stdin: &mut impl Read,
+    out: &mut RecordWriter<impl Write>,
+    err: &mut impl Write,
+) -> Result<Summary> {
+    // With --aggregate, failures are counted in the summary instead.
+    let mut aggregate = args.aggregate.then(Aggregate::default);
+    let error_records = aggregate.is_none()
+        && match args.error_records {
+            ErrorRecords::Auto => inputs.len() > 1,
+            ErrorRecords::Always => true,
+            ErrorRecords::Never => false,
+        };
+    let jobs = args.jobs.map_or_else(
+        || std::thread::available_parallelism().map_or(1, |n| n.get()),
+        |n| n.get(),
+    );
+    let settings = Settings::new(args, jobs);
+    let selection = args.fields.as_deref().map(Selection);
+    let mut progress = Progress::new(inputs.len(), args.progress == ShowProgress::Always);
+    progress.draw(err)?;
+    for batch in inputs.chunks(jobs * INPUTS_PER_JOB) {
+        let analysed = analyse_batch(batch, &settings, jobs, stdin);
+        progress.clear(err)?;
+        for (input, (size, result)) in batch.iter().zip(analysed) {
+            progress.add(&result);
+            match result {
+                Ok(analysis) => match &mut aggregate {
+                    Some(aggregate) => aggregate.add(&analysis.stats),
+                    None => {
+                        let record = Record {
+                            file: &input.name,
+                            file_size: analysis.file_size,
+                            sha256: analysis.sha256,
+                            input_format: analysis.input_format,
+                            http: analysis.http,
+                            stats: analysis.stats,
+                        };
+                        match &selection {
+                            Some(selection) => {
+                                out.write(&selection.apply(serde_json::to_value(record)?))?
+                            }
+                            None => out.write(&record)?,
+                        }
+                    }
+                },
+                Err(e) => {
+                    writeln!(err, "{}: {:#}", input.name, e)?;
+                    if error_records {
+                        out.write(&ErrorRecord::new(&input.name, &e, size))?;
+                    }
                 }
             }
-            _ => {}
         }
+        progress.draw(err)?;
     }
-
-    // NOTE: Need to check for Blazor ahead of Emscripten
-    if imports.iter().any(|i| i.name.contains("blazor")) {
-        return Ok(Language::Blazor);
+    progress.clear(err)?;
+    let summary = progress.finish();
+    if let Some(aggregate) = aggregate {
+        out.write(&aggregate.finish(summary.unsuccessful()))?;
     }
+    Ok(summary)
+}
 
-    if imports.iter().any(|i| i.name.contains("emscripten")) {
-        return Ok(Language::Emscripten);
-    }
+fn record_schema() -> serde_json::Value {
+    schemars::schema_for!(Record).to_value()
+}
 
-    if imports.iter().any(|i| i.module == "go") {
-        return Ok(Language::Go);
+/// Prints the JSON Schema of the records, or the BigQuery table schema.
+fn print_schema(bq: bool) -> Result<()> {
+    let mut schema = record_schema();
+    if bq {
+        schema = bq::table_schema(&schema);
     }
+    output::write_stats(&mut std::io::stdout().lock(), &schema, true)
+}
 
-    // these are all based on Rust using wasm-bindgen
-    if imports.iter().any(|i| {
-        i.name.contains("wbindgen")
-            || i.name.contains("wbg")
-            || i.module == "wbg"
-            || i.module == "wbindgen"
-    }) || exports.iter().any(|e| e.name.contains("wbindgen"))
-    {
-        return Ok(Language::Rust);
-    }
-
-    // Many of the wasm modules have been compressed with this very distinctive pattern. From looking at a number of wasm modules
-    // and inspecting their contents, or the page that hosts them, it seems quite likely this is Emscripten. For example:
-    //
-    // https://tweet2doom.github.io/t2d-explorer.wasm
-    //   => https://github.com/tweet2doom/tweet2doom.github.io - strong evidence of Emscripten
-    //
-    // https://graphonline.ru/script/Graphoffline.Emscripten.wasm - the clue is in the filename!
-    //
-    // https://wsr-starfinder.com/js/stellarium-web-engine.06229ae9.wasm
-    //  => https://github.com/Stellarium/stellarium-web-engine - code makes reference to using Emscripten
-    if (imports.iter().any(|i| i.module == "a" && i.name == "a")
-        && imports.iter().any(|i| i.module == "a" && i.name == "b"))
-
-    // another distinctive pattern, again, evidence suggests Emscripten
-    // https://tx.me/
-    // => https://github.com/Samsung/rlottie/blob/master/src/wasm/rlottiewasm.cpp - this is a cool project ;-)
-    //
-    // https://demo.harmonicvision.com - Emscripten mentioned in the page source
-    //
-    // https://webcamera.io - uses FFMpeg, which is an Emscripten project
-    || (imports.iter().any(|i| i.module == "env" && i.name == "a")
-        && imports.iter().any(|i| i.module == "env" && i.name == "b"))
-    {
-        return Ok(Language::LikelyEmscripten);
-    }
+/// Reads and decompresses a module for `diff`.
+fn read_module(path: &Path) -> Result<diff::Module> {
+    let input = Input::file(path.to_owned(), path);
+    let bytes = input.read(&mut std::io::empty())?;
+    let decoded = compression::decompress(&input.name, bytes, None)?;
+    diff::Module::new(&decoded.wasm).with_context(|| input.name.clone())
+}
 
-    Ok(Language::Unknown)
+/// Prints the differences between two modules.
+fn print_diff(a: &Path, b: &Path) -> Result<()> {
+    let diff = diff::diff(&read_module(a)?, &read_module(b)?);
+    output::write_stats(&mut std::io::stdout().lock(), &diff, true)
 }
 
-fn get_stats(wasm: &[u8]) -> Result<Stats> {
-    let m = wasmbin::Module::decode_from(wasm)?;
-    let mut stats = Stats {
-        size: SizeStats {
-            total: wasm.len(),
-            ..Default::default()
-        },
-        language: infer_language(&m)?,
-        ..Default::default()
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+    match &args.command {
+        Some(Command::Schema { bq }) => return print_schema(*bq),
+        Some(Command::Diff { a, b }) => return print_diff(a, b),
+        None => {}
+    }
+    let mut stderr = std::io::stderr().lock();
+    let inputs = inputs::collect_inputs(&args.paths, &args.exts, &mut stderr)?;
+    #[cfg(feature = "fetch")]
+    if inputs
+        .iter()
+        .any(|input| matches!(input.source, inputs::Source::Url(_)))
+    {
+        fetch::check_curl()?;
+    }
+    let (out, dest): (Box<dyn Write>, _) = match &args.output {
+        Some(path) => (
+            Box::new(output::open_file(path, args.append)?),
+            path.display().to_string(),
+        ),
+        None => (Box::new(std::io::stdout().lock()), "stdout".to_owned()),
     };
-    let mut global_types = Vec::new();
-    let mut func_types = Vec::new();
-    let mut types = &[] as &[_];
-    for section in &m.sections {
-        match section {
-            Section::Custom(section) => {
-                stats.size.custom += calc_size(section)?;
-                stats
-                    .custom_sections
-                    .push(section.try_contents()?.name().to_owned());
-            }
-            Section::Type(section) => {
-                stats.size.types += calc_size(section)?;
-                types = section.try_contents()?;
-                for ty in types {
-                    if ty.results.len() > 1 {
-                        stats.instr.proposals.multi_value += 1;
-                    }
-                }
-            }
-            Section::Import(section) => {
-                stats.size.externals += calc_size(section)?;
-                let section = section.try_contents()?;
-                stats.imports = get_external_stats!(section, ImportDesc);
-                for item in section {
-                    match &item.desc {
-                        ImportDesc::Global(ty) => {
-                            global_types.push(MaybeExternal {
-                                value: ty.clone(),
-                                is_external: true,
-                            });
-                        }
-                        ImportDesc::Func(type_id) => {
-                            func_types.push(MaybeExternal {
-                                value: *type_id,
-                                is_external: true,
-                            });
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Section::Function(section) => {
-                stats.size.descriptors += calc_size(section)?;
-                func_types.extend(section.try_contents()?.iter().map(|type_id| MaybeExternal {
-                    value: *type_id,
-                    is_external: false,
-                }));
-            }
-            Section::Table(section) => {
-                stats.size.descriptors += calc_size(section)?;
-            }
-            Section::Memory(section) => {
-                stats.size.descriptors += calc_size(section)?;
-                for ty in section.try_contents()? {
-                    if ty.is_shared {
-                        stats.instr.proposals.atomics += 1;
-                    }
-                }
-            }
-            Section::Global(section) => {
-                stats.size.descriptors += calc_size(section)?;
-                global_types.extend(section.try_contents()?.iter().map(|global| MaybeExternal {
-                    value: global.ty.clone(),
-                    is_external: false,
-                }));
-            }
-            Section::Export(section) => {
-                stats.size.externals += calc_size(section)?;
-                let section = section.try_contents()?;
-                stats.exports = get_external_stats!(section, ExportDesc);
-                for item in section {
-                    match item.desc {
-                        ExportDesc::Global(global_id) => {
-                            global_types[global_id.index as usize].is_external = true;
-                        }
-                        ExportDesc::Func(func_id) => {
-                            func_types[func_id.index as usize].is_external = true;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Section::Start(_) => {
-                stats.has_start = true;
-            }
-            Section::Element(section) => {
-                stats.size.init += calc_size(section)?;
-            }
-            Section::DataCount(_) => {
-                stats.instr.proposals.bulk += 1;
-            }
-            Section::Code(section) => {
-                stats.size.code = calc_size(section)?;
-                let funcs = section.try_contents()?;
-                stats.funcs = funcs.len();
-                stats.instr = get_instruction_stats(funcs)?;
-            }
-            Section::Data(section) => {
-                stats.size.init += calc_size(section)?;
-            }
+    let mut out = RecordWriter::new(out, args.output_format)
+        .pretty(args.pretty)
+        .schema(record_schema())
+        .arrays(args.flatten_arrays);
+    // Don't repeat the CSV header when resuming into a file that has one.
+    if let (true, Some(path)) = (args.append, &args.output) {
+        if std::fs::metadata(path)?.len() > 0 {
+            out = out.without_header();
         }
     }
-    global_types
-        .into_iter()
-        .filter_map(MaybeExternal::external)
-        .for_each(|ty| {
-            if ty.mutable {
-                stats.instr.proposals.mutable_externals += 1;
-            }
-            if let ValueType::I64 = ty.value_type {
-                stats.instr.proposals.bigint_externals += 1;
-            }
-        });
-    func_types
-        .into_iter()
-        .filter_map(MaybeExternal::external)
-        .try_for_each(|type_id| {
-            types[type_id.index as usize].visit(|ty: &ValueType| {
-                if let ValueType::I64 = ty {
-                    stats.instr.proposals.bigint_externals += 1;
-                }
-            })
-        })?;
-    Ok(stats)
-}
-
-fn main() -> Result<()> {
-    let path_str = std::env::args_os()
-        .nth(1)
-        .ok_or_else(|| anyhow!("Please provide wasm file path"))?;
-    let path = PathBuf::from(&path_str);
-    let abs_path = std::fs::canonicalize(&path)?;
-    let wasm = std::fs::read(&abs_path)?;
-    let stats = get_stats(&wasm)?;
-    let serialized = serde_json::to_string(&stats)? + "\n";
-    std::io::stdout().write_all(serialized.as_bytes())?;
+    if args.progress == ShowProgress::Auto {
+        args.progress = if inputs.len() > 1 && stderr.is_terminal() {
+            ShowProgress::Always
+        } else {
+            ShowProgress::Never
+        };
+    }
+    let summary = run(
+        &inputs,
+        &args,
+        &mut std::io::stdin().lock(),
+        &mut out,
+        &mut stderr,
+    )
+    .and_then(|summary| out.flush().map(|()| summary))
+    .with_context(|| format!("Failed to write output to {}", dest))?;
+    if inputs.len() > 1 {
+        writeln!(stderr, "{}", summary)?;
+    }
+    if let Some(path) = &args.summary_json {
+        summary.write_json(path)?;
+    }
+    let failed = summary.unsuccessful();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} inputs failed", failed, inputs.len()));
+    }
 
     Ok(())
 }
@@ -564,153 +484,516 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use output::OutputFormat;
+
+    #[test]
+    fn run_batch_mixed_inputs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let good = dir.path().join("good.wasm");
+        std::fs::write(&good, wat::parse_str("(module (func) (func))")?)?;
+        let bad = dir.path().join("bad.wasm");
+        std::fs::write(&bad, b"not wasm")?;
+        let missing = dir.path().join("missing.wasm");
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let inputs: Vec<_> = [&bad, &good, &missing]
+            .iter()
+            .map(|path| Input::file(path.to_path_buf(), path))
+            .collect();
+        let summary = run(
+            &inputs,
+            &Args::try_parse_from(["wasm-stats", "--error-records", "never"])?,
+            &mut std::io::empty(),
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut err,
+        )?;
+        assert_eq!(summary.failed, 2);
+
+        let out = String::from_utf8(out)?;
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let record: serde_json::Value = serde_json::from_str(lines[0])?;
+        assert_eq!(record["file"], good.to_string_lossy().as_ref());
+        assert_eq!(record["funcs"], 2);
+
+        let err = String::from_utf8(err)?;
+        assert_eq!(err.lines().count(), 2);
+        assert!(err.contains(bad.to_string_lossy().as_ref()));
+        assert!(err.contains(missing.to_string_lossy().as_ref()));
+        Ok(())
+    }
+
+    fn error_records(files: &[(&str, &[u8])]) -> Result<Vec<serde_json::Value>> {
+        run_files(files, &[])
+    }
+
+    /// Runs over the given files with extra flags, returning the records.
+    fn run_files(files: &[(&str, &[u8])], flags: &[&str]) -> Result<Vec<serde_json::Value>> {
+        let dir = tempfile::tempdir()?;
+        let mut inputs = Vec::new();
+        for (name, contents) in files {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents)?;
+            inputs.push(Input::file(path, name.as_ref()));
+        }
+        let mut out = Vec::new();
+        run(
+            &inputs,
+            &Args::try_parse_from(std::iter::once("wasm-stats").chain(flags.iter().copied()))?,
+            &mut std::io::empty(),
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
+        )?;
+        Ok(serde_json::Deserializer::from_slice(&out)
+            .into_iter()
+            .collect::<Result<_, _>>()?)
+    }
 
-    fn stats_from_wat(wat: &str) -> Result<Stats> {
-        let binary = wat::parse_str(wat)?;
-        get_stats(&binary[..])
+    #[test]
+    fn run_emits_error_records() -> Result<()> {
+        let wasm = wat::parse_str("(module (func) (func))")?;
+        // Truncated sections only produce partial stats, but a module cut
+        // off within its header can't be analysed at all.
+        let truncated = &wasm[..6];
+        let records = error_records(&[
+            ("truncated.wasm", truncated),
+            ("text.wasm", b"not a wasm module"),
+        ])?;
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0]["file"], "truncated.wasm");
+        assert_eq!(records[0]["size"], truncated.len());
+        let error = records[0]["error"].as_str().unwrap();
+        assert!(error.starts_with("decode error: "), "{}", error);
+
+        assert_eq!(records[1]["file"], "text.wasm");
+        assert_eq!(records[1]["size"], 17);
+        let error = records[1]["error"].as_str().unwrap();
+        assert!(error.starts_with("invalid magic or version: "), "{}", error);
+        assert_eq!(
+            records[1].as_object().unwrap().keys().collect::<Vec<_>>(),
+            ["file", "error", "size"]
+        );
+        Ok(())
     }
 
     #[test]
-    fn get_stats_funcs() -> Result<()> {
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (func $foo)
-            (func (export "bar")
-                call $foo
-            )
-        )
-        "#,
+    fn run_in_parallel_keeps_input_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut inputs = Vec::new();
+        // Bigger modules first, so that later ones tend to finish first.
+        for funcs in (0..20).rev() {
+            let name = format!("{}.wasm", funcs);
+            let path = dir.path().join(&name);
+            let body = "(func)".repeat(funcs * 500);
+            std::fs::write(&path, wat::parse_str(format!("(module {})", body))?)?;
+            inputs.push(Input::file(path, name.as_ref()));
+        }
+        let mut out = Vec::new();
+        let summary = run(
+            &inputs,
+            &Args::try_parse_from(["wasm-stats", "--jobs", "4"])?,
+            &mut std::io::empty(),
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
         )?;
-        // TODO: test more of the stats
-        assert_eq!(stats.funcs, 2);
+        assert_eq!(summary.failed, 0);
+        let records: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&out)
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        let files: Vec<_> = records
+            .iter()
+            .map(|r| r["file"].as_str().unwrap())
+            .collect();
+        let expected: Vec<_> = inputs.iter().map(|input| input.name.as_str()).collect();
+        assert_eq!(files, expected);
+        for (record, funcs) in records.iter().zip((0..20).rev()) {
+            assert_eq!(record["funcs"], funcs * 500);
+        }
         Ok(())
     }
 
     #[test]
-    fn infer_language_unknown() -> Result<()> {
-        let stats = stats_from_wat("(module)")?;
-        assert_eq!(stats.language, Language::Unknown);
+    fn run_error_record_for_missing_file() -> Result<()> {
+        let mut out = Vec::new();
+        let inputs = [
+            Input::file("does/not/exist.wasm".into(), "exist.wasm".as_ref()),
+            Input::stdin(),
+        ];
+        run(
+            &inputs,
+            &Args::try_parse_from(["wasm-stats"])?,
+            &mut &wat::parse_str("(module)")?[..],
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
+        )?;
+        let record: serde_json::Value =
+            serde_json::from_slice(out.split(|&b| b == b'\n').next().unwrap())?;
+        assert!(record["error"].as_str().unwrap().starts_with("io error: "));
+        assert!(record["size"].is_null());
         Ok(())
     }
 
     #[test]
-    fn infer_language_rust() -> Result<()> {
-        // b63e9f90187a9f5cec9a7a9cfc15e68e9330979ac39a29258282c020780cd6ec.wasm
-        //
-        // exports mention 'wbindgen'
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t8 (func (param i32)))
-            (func $__wbindgen_malloc (type $t8) (param $p0 i32))
-            (export "__wbindgen_malloc" (func $__wbindgen_malloc))
-        )
-        "#,
+    fn run_catches_panics() -> Result<()> {
+        struct Panicking;
+        impl Read for Panicking {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                panic!("boom")
+            }
+        }
+        let mut out = Vec::new();
+        let summary = run(
+            &[Input::stdin()],
+            &Args::try_parse_from(["wasm-stats", "--error-records", "always"])?,
+            &mut Panicking,
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
         )?;
-        assert_eq!(stats.language, Language::Rust);
-
-        // 82f052ee941598c3f70b9adfdebcb8fda239e5095e48d3e4a2edcc208b0c769c.wasm
-        //
-        // import references a 'wbg' module
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t2 (func (param i32)))
-            (import "wbg" "__wbindgen_object_drop_ref" (func $wasm_bindgen::__wbindgen_object_drop_ref::hc5b72d1598c36103 (type $t2)))
-        )
-        "#,
+        assert_eq!(summary.failed, 1);
+        let record: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(record["error"], "panic: Analysis panicked: boom");
+        Ok(())
+    }
+
+    /// Runs the inputs, returning the records written.
+    fn run_records(inputs: &[Input], args: &[&str]) -> Result<Vec<serde_json::Value>> {
+        let mut out = Vec::new();
+        run(
+            inputs,
+            &Args::try_parse_from(args)?,
+            &mut std::io::empty(),
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
+        )?;
+        let out = String::from_utf8(out)?;
+        out.lines()
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    #[test]
+    fn run_max_size() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let small = dir.path().join("small.wasm");
+        std::fs::write(&small, wat::parse_str("(module)")?)?;
+        let large = dir.path().join("large.wasm");
+        std::fs::write(&large, wat::parse_str("(module (func) (func))")?)?;
+        let inputs: Vec<_> = [&small, &large]
+            .iter()
+            .map(|path| Input::file(path.to_path_buf(), path))
+            .collect();
+        let records = run_records(
+            &inputs,
+            &["wasm-stats", "--error-records", "always", "--max-size", "8"],
         )?;
-        assert_eq!(stats.language, Language::Rust);
-
-        // d792c9bfa765ab3e849bb2f266e1d2b19e555fc4a59c51d22a47fa73b27180b8.wasm
-        //
-        // import references a function containing 'wbg'
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t11 (func (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)))
-            (import "./source_compiler_bg.js" "__wbg_sourcerorLogCallback_9555c6dd7a1fa2a1" (func $./source_compiler_bg.js.__wbg_sourcerorLogCallback_9555c6dd7a1fa2a1 (type $t11)))
-        )
-        "#,
+        assert_eq!(records[0]["size"]["total"], 8);
+        assert_eq!(
+            records[1]["error"],
+            "skipped: too large: 28 bytes, over the limit of 8"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_max_size_after_decompression() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("zeros.wasm.gz");
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gzipped.write_all(&[0; 4096])?;
+        std::fs::write(&path, gzipped.finish()?)?;
+        let records = run_records(
+            &[Input::file(path.clone(), &path)],
+            &[
+                "wasm-stats",
+                "--error-records",
+                "always",
+                "--max-size",
+                "1024",
+            ],
         )?;
-        assert_eq!(stats.language, Language::Rust);
+        assert_eq!(
+            records[0]["error"],
+            "skipped: too large: 4096 bytes, over the limit of 1024"
+        );
         Ok(())
     }
 
+    #[cfg(feature = "fetch")]
     #[test]
-    fn infer_language_blazor() -> Result<()> {
-        // 9bd69204e55c94eb68b385ed4f79dffc752dc8fbccd526fd5c61d13a5df5d5de.wasm
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t4 (func (param i32 i32 i32) (result i32)))
-            (type $t8 (func (param i32 i32 i32 i32 i32) (result i32)))
-            (import "env" "mono_wasm_invoke_js_blazor" (func $env.mono_wasm_invoke_js_blazor (type $t8)))
-            (import "env" "emscripten_asm_const_int" (func $env.emscripten_asm_const_int (type $t4)))
-        )
-        "#,
+    fn run_url_input() -> Result<()> {
+        use fetch::tests::{response, serve};
+
+        let wasm = wat::parse_str("(module (func (export \"f\")))")?;
+        let (url, server) = serve(vec![
+            response("200 OK", &["Content-Type: application/wasm"], &wasm),
+            response("500 Internal Server Error", &[], b""),
+            response("200 OK", &[], &[0; 4096]),
+        ])?;
+        let inputs: Vec<_> = ["/module.wasm", "/broken.wasm", "/large.wasm"]
+            .iter()
+            .map(|path| Input::url(&format!("{}{}", url, path)))
+            .collect();
+        let records = run_records(
+            &inputs,
+            &[
+                "wasm-stats",
+                "--error-records",
+                "always",
+                "--max-size",
+                "1024",
+                "--jobs",
+                "1",
+            ],
         )?;
-        assert_eq!(stats.language, Language::Blazor);
+        server.join().unwrap();
+
+        assert_eq!(records[0]["file"], format!("{}/module.wasm", url));
+        assert_eq!(records[0]["file_size"], wasm.len());
+        assert_eq!(records[0]["exports"]["funcs"], 1);
+        assert_eq!(
+            records[0]["http"],
+            serde_json::json!({
+                "url": format!("{}/module.wasm", url),
+                "status": 200,
+                "content_type": "application/wasm",
+            })
+        );
+        assert_eq!(
+            records[1]["error"],
+            format!("network error: HTTP status 500 from {}/broken.wasm", url)
+        );
+        assert_eq!(
+            records[2]["error"],
+            "skipped: too large: download over the limit of 1024 bytes"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_wat_input() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let wat = r#"
+            (module
+                (import "env" "f" (func $f (param i32)))
+                (memory (export "memory") 1)
+                (func $main (export "main") i32.const 1 call $f)
+                (data (i32.const 0) "hello"))
+        "#;
+        let text = dir.path().join("module.wat");
+        std::fs::write(&text, wat)?;
+        let binary = dir.path().join("module.wasm");
+        std::fs::write(&binary, wat::parse_str(wat)?)?;
+        let inputs: Vec<_> = [&text, &binary]
+            .iter()
+            .map(|path| Input::file(path.to_path_buf(), path))
+            .collect();
+
+        let mut records = run_records(&inputs, &["wasm-stats"])?;
+        assert_eq!(records[0]["input_format"], "wat");
+        assert_eq!(records[1]["input_format"], "binary");
+        for record in &mut records {
+            let record = record.as_object_mut().unwrap();
+            for field in ["file", "file_size", "sha256", "input_format"] {
+                record.remove(field);
+            }
+            record["size"].as_object_mut().unwrap().remove("total");
+        }
+        assert_eq!(records[0], records[1]);
         Ok(())
     }
 
     #[test]
-    fn infer_language_emscripten() -> Result<()> {
-        // 70c2f8e0269dd409da3153196ee3e4258f196d313ea271b1516c7fc241c52adb.wasm
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t3 (func (param i32) (result i32)))
-            (import "env" "_emscripten_asm_const_i" (func $env._emscripten_asm_const_i (type $t3)))
-        )
-        "#,
+    fn run_wat_parse_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("bad.wat");
+        std::fs::write(&path, "(module\n  (func (i32.bogus)))")?;
+        let records = run_records(
+            &[Input::file(path.clone(), &path)],
+            &["wasm-stats", "--error-records", "always"],
         )?;
-        assert_eq!(stats.language, Language::Emscripten);
+        let error = records[0]["error"].as_str().unwrap();
+        assert!(error.starts_with("text format error: "), "{}", error);
+        assert!(error.contains("bad.wat:2:"), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn run_estimate_compression() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data.wasm");
+        let data = "0123456789abcdef".repeat(64);
+        let wat = format!(r#"(module (memory 1) (data (i32.const 0) "{}"))"#, data);
+        std::fs::write(&path, wat::parse_str(wat)?)?;
+        let inputs = [Input::file(path.clone(), &path)];
+
+        for args in [
+            &["wasm-stats", "--estimate-compression"][..],
+            &[
+                "wasm-stats",
+                "--estimate-compression",
+                "--brotli-quality",
+                "4",
+            ],
+        ] {
+            let records = run_records(&inputs, args)?;
+            let size = &records[0]["size"];
+            let total = size["total"].as_u64().unwrap();
+            for field in ["gzip", "brotli"] {
+                let compressed = size[field].as_u64().unwrap();
+                assert!(compressed > 0 && compressed < total, "{}: {}", field, size);
+            }
+        }
+
+        let records = run_records(&inputs, &["wasm-stats"])?;
+        let size = records[0]["size"].as_object().unwrap();
+        assert!(!size.contains_key("gzip"));
+        assert!(!size.contains_key("brotli"));
+
+        assert!(Args::try_parse_from(["wasm-stats", "--brotli-quality", "4"]).is_err());
+        assert!(Args::try_parse_from([
+            "wasm-stats",
+            "--estimate-compression",
+            "--brotli-quality",
+            "12"
+        ])
+        .is_err());
         Ok(())
     }
 
     #[test]
-    fn infer_language_go() -> Result<()> {
-        // 1b98798659012dc524343d1a44da2488fb09436fd6ca587c804ad272367d294d.wasm
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t1 (func (param i32)))
-            (import "go" "runtime.resetMemoryDataView" (func $go.runtime.resetMemoryDataView (type $t1)))
-        )
-        "#,
+    fn run_summary_json() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let small = dir.path().join("small.wasm");
+        std::fs::write(&small, wat::parse_str("(module)")?)?;
+        let large = dir.path().join("large.wasm");
+        std::fs::write(&large, wat::parse_str("(module (func) (func))")?)?;
+        let bad = dir.path().join("bad.wasm");
+        std::fs::write(&bad, b"not wasm")?;
+        let inputs: Vec<_> = [&small, &large, &bad, &small]
+            .iter()
+            .map(|path| Input::file(path.to_path_buf(), path))
+            .collect();
+        let mut err = Vec::new();
+        let summary = run(
+            &inputs,
+            &Args::try_parse_from(["wasm-stats", "--max-size", "16", "--progress", "always"])?,
+            &mut std::io::empty(),
+            &mut RecordWriter::new(&mut Vec::new(), OutputFormat::Json),
+            &mut err,
         )?;
-        assert_eq!(stats.language, Language::Go);
+        let path = dir.path().join("summary.json");
+        summary.write_json(&path)?;
+        let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+        assert_eq!(json["processed"], 4);
+        assert_eq!(json["succeeded"], 2);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["skipped"], 1);
+        assert!(json["wall_time_ms"].is_u64());
+        // The bar ends up cleared, leaving only the failure messages.
+        let err = String::from_utf8(err)?;
+        assert!(err.contains("4/4 inputs, 1 failed, 1 skipped"));
+        assert!(err.ends_with("\r\x1b[K"));
+        Ok(())
+    }
+
+    #[test]
+    fn analyse_timeout() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("module.wasm");
+        std::fs::write(&path, wat::parse_str("(module)")?)?;
+        let inputs: Vec<_> = (0..3).map(|_| Input::file(path.clone(), &path)).collect();
+        let args = Args::try_parse_from(["wasm-stats", "--timeout", "1"])?;
+        let mut settings = Settings::new(&args, 1);
+
+        let analysed = analyse_batch(&inputs, &settings, 1, &mut std::io::empty());
+        for (_, result) in analysed {
+            assert_eq!(result?.stats.size.total, 8);
+        }
+
+        let places = Places::new(1);
+        settings.timeout = Some((Duration::from_millis(20), Arc::clone(&places)));
+        settings.delay = Duration::from_millis(100);
+        let start = std::time::Instant::now();
+        let analysed = analyse_batch(&inputs, &settings, 1, &mut std::io::empty());
+        for (_, result) in analysed {
+            let err = result.err().expect("timed out");
+            assert_eq!(err.to_string(), "timeout: not done after 20ms");
+        }
+        // Each input waited for the one before it, given up on, to finish
+        // before it could start.
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        // And the last one gives its place back once it's done too.
+        drop(places.take());
         Ok(())
     }
 
     #[test]
-    fn infer_language_likely_emscripten() -> Result<()> {
-        // 38049c6cc89d4c6ac8c2635fc0af29901109d68247ba7e57d2bff551216a322e.wasm
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t4 (func (param i32 i32 i32) (result i32)))
-            (import "a" "a" (func $a.a (type $t4)))
-            (import "a" "b" (func $a.b (type $t4)))
-        )
-        "#,
+    fn run_stdin() -> Result<()> {
+        let wasm = wat::parse_str("(module (func))")?;
+        let mut out = Vec::new();
+        let summary = run(
+            &[Input::stdin()],
+            &Args::try_parse_from(["wasm-stats"])?,
+            &mut &wasm[..],
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
         )?;
-        assert_eq!(stats.language, Language::LikelyEmscripten);
-
-        // f50ed354fd14cce39533af5fc58c0e4387a326748114c57a2ce3c98611da673b.wasm
-        let stats = stats_from_wat(
-            r#"
-        (module
-            (type $t6 (func (param i32 i32 i32 i32)))
-            (import "env" "b" (func $env.b (type $t6)))
-            (import "env" "a" (global $env.a i32))
-        )
-        "#,
+        assert_eq!(summary.failed, 0);
+        let record: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(record["file"], "-");
+        assert_eq!(record["funcs"], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn record_identifies_input() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let plain = dir.path().join("empty.wasm");
+        std::fs::write(&plain, b"\0asm\x01\0\0\0")?;
+        let gzipped = dir.path().join("empty.wasm.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(b"\0asm\x01\0\0\0")?;
+        let compressed = encoder.finish()?;
+        std::fs::write(&gzipped, &compressed)?;
+
+        let mut out = Vec::new();
+        run(
+            &[
+                Input::file(plain.clone(), Path::new("empty.wasm")),
+                Input::file(gzipped.clone(), Path::new("empty.wasm.gz")),
+            ],
+            &Args::try_parse_from(["wasm-stats"])?,
+            &mut std::io::empty(),
+            &mut RecordWriter::new(&mut out, OutputFormat::Json),
+            &mut Vec::new(),
         )?;
-        assert_eq!(stats.language, Language::LikelyEmscripten);
+        let records: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&out)
+            .into_iter()
+            .collect::<Result<_, _>>()?;
+        assert_eq!(records[0]["file"], "empty.wasm");
+        assert_eq!(records[0]["file_size"], 8);
+        assert_eq!(
+            records[0]["sha256"],
+            "93a44bbb96c751218e4c00d479e4c14358122a389acca16205b1e4d0dc5f9476"
+        );
+        // Compressed inputs are identified by the bytes as crawled.
+        assert_eq!(records[1]["file_size"], compressed.len());
+        assert_eq!(records[1]["sha256"], sha256(&compressed));
+        Ok(())
+    }
 
+    #[test]
+    fn run_aggregate_counts_failures() -> Result<()> {
+        let records = run_files(
+            &[("good.wasm", b"\0asm\x01\0\0\0"), ("bad.wasm", b"nope")],
+            &["--aggregate"],
+        )?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["modules"], 1);
+        assert_eq!(records[0]["failed"], 1);
+        assert_eq!(records[0]["size"]["total"]["sum"], 8);
         Ok(())
     }
 }
@@ -0,0 +1,389 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::bq;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    /// Flattened JSON rows for the BigQuery tables, selected with `--bq`.
+    #[value(skip)]
+    Bq,
+    /// Single-level JSON objects, selected with `--flatten`.
+    #[value(skip)]
+    Flat,
+}
+
+/// How `--flatten` writes arrays and maps, which can't be spread over a fixed
+/// set of keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FlatArrays {
+    /// As a string of JSON.
+    Json,
+    /// As the number of items or entries.
+    Count,
+}
+
+/// Serializes a struct into its fields, in declaration order.
+pub fn fields(value: impl Serialize) -> Map<String, Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// Flattens nested objects into dot-separated column names.
+///
+/// Arrays are joined with `;` so that each record stays a single row, as are
+/// maps with arbitrary keys (as `key=value`), which would otherwise give each
+/// record different columns.
+fn flatten(
+    prefix: &str,
+    value: &Value,
+    schema: &Value,
+    defs: &Value,
+    columns: &mut Vec<(String, String)>,
+) {
+    let (schema, _) = bq::resolve(schema, defs);
+    match value {
+        Value::Object(map) if bq::is_map(schema) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, cell(value)))
+                .collect();
+            columns.push((prefix.to_owned(), entries.join(";")));
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                let name = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&name, value, &schema["properties"][key], defs, columns);
+            }
+        }
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(cell).collect();
+            columns.push((prefix.to_owned(), items.join(";")));
+        }
+        _ => columns.push((prefix.to_owned(), cell(value))),
+    }
+}
+
+/// Flattens nested objects into a single object with underscore-joined keys,
+/// e.g. `instr_categories_load_store`.
+///
+/// Keys only keep letters, digits and underscores, so that they can be used
+/// as SQL column names. Arrays, and maps with arbitrary keys, are written as
+/// `arrays` says, so that every record has the same keys.
+pub fn flatten_keys(value: &Value, schema: &Value, arrays: FlatArrays) -> Value {
+    let mut row = Map::new();
+    flatten_keys_into("", value, schema, &schema["$defs"], arrays, &mut row);
+    Value::Object(row)
+}
+
+fn flatten_keys_into(
+    prefix: &str,
+    value: &Value,
+    schema: &Value,
+    defs: &Value,
+    arrays: FlatArrays,
+    row: &mut Map<String, Value>,
+) {
+    let (schema, _) = bq::resolve(schema, defs);
+    let value = match (value, arrays) {
+        (Value::Object(map), _) if !bq::is_map(schema) => {
+            for (key, value) in map {
+                let column: String = key
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                let name = if prefix.is_empty() {
+                    column
+                } else {
+                    format!("{}_{}", prefix, column)
+                };
+                let schema = &schema["properties"][key];
+                flatten_keys_into(&name, value, schema, defs, arrays, row);
+            }
+            return;
+        }
+        (Value::Array(items), FlatArrays::Count) => Value::from(items.len()),
+        (Value::Object(map), FlatArrays::Count) => Value::from(map.len()),
+        (Value::Array(_) | Value::Object(_), FlatArrays::Json) => Value::String(value.to_string()),
+        _ => value.clone(),
+    };
+    row.insert(prefix.to_owned(), value);
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+fn write_csv_row<'a>(out: &mut impl Write, cells: impl Iterator<Item = &'a str>) -> Result<()> {
+    for (i, cell) in cells.enumerate() {
+        if i != 0 {
+            out.write_all(b",")?;
+        }
+        if cell.contains(&[',', '"', '\n', '\r'][..]) {
+            write!(out, "\"{}\"", cell.replace('"', "\"\""))?;
+        } else {
+            out.write_all(cell.as_bytes())?;
+        }
+    }
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Opens the `--output` file, either truncating it or appending to it.
+pub fn open_file(path: &Path, append: bool) -> Result<BufWriter<File>> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .with_context(|| format!("Failed to open output file {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+/// Serializes a record as JSON followed by a newline.
+///
+/// Compact output keeps each record on a single line, which is what NDJSON
+/// consumers expect; pretty output is meant for eyeballing a single module.
+pub fn write_stats(out: &mut impl Write, record: &impl Serialize, pretty: bool) -> Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(&mut *out, record)?;
+    } else {
+        serde_json::to_writer(&mut *out, record)?;
+    }
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Writes records one per line in the requested format.
+///
+/// For CSV, the header is taken from the first record written; later records
+/// are matched to it by column name, with missing columns left empty.
+pub struct RecordWriter<W> {
+    out: W,
+    format: OutputFormat,
+    pretty: bool,
+    schema: Value,
+    arrays: FlatArrays,
+    columns: Option<Vec<String>>,
+    columns_written: bool,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(out: W, format: OutputFormat) -> Self {
+        RecordWriter {
+            out,
+            format,
+            pretty: false,
+            schema: Value::Null,
+            arrays: FlatArrays::Json,
+            columns: None,
+            columns_written: false,
+        }
+    }
+
+    /// Skips the CSV header, e.g. when appending to a file that already has one.
+    pub fn without_header(mut self) -> Self {
+        self.columns_written = true;
+        self
+    }
+
+    /// Pretty-prints JSON records; has no effect on CSV.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Uses the JSON Schema of the records to tell maps, whose keys vary
+    /// between records, from structs when flattening them.
+    pub fn schema(mut self, schema: Value) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// How arrays and maps are written with `--flatten`.
+    pub fn arrays(mut self, arrays: FlatArrays) -> Self {
+        self.arrays = arrays;
+        self
+    }
+
+    pub fn write(&mut self, record: &impl Serialize) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => write_stats(&mut self.out, record, self.pretty)?,
+            OutputFormat::Bq => {
+                let row = bq::flatten_row(&serde_json::to_value(record)?, &self.schema);
+                write_stats(&mut self.out, &row, false)?
+            }
+            OutputFormat::Flat => {
+                let record = serde_json::to_value(record)?;
+                let row = flatten_keys(&record, &self.schema, self.arrays);
+                write_stats(&mut self.out, &row, false)?
+            }
+            OutputFormat::Csv => {
+                let mut cells = Vec::new();
+                let defs = &self.schema["$defs"];
+                let record = serde_json::to_value(record)?;
+                flatten("", &record, &self.schema, defs, &mut cells);
+                let columns = match &self.columns {
+                    Some(columns) => columns,
+                    None => {
+                        let columns = cells.iter().map(|(name, _)| name.clone()).collect();
+                        let columns = self.columns.get_or_insert(columns);
+                        if !self.columns_written {
+                            write_csv_row(&mut self.out, columns.iter().map(String::as_str))?;
+                            self.columns_written = true;
+                        }
+                        columns
+                    }
+                };
+                let row = columns.iter().map(|column| {
+                    cells
+                        .iter()
+                        .find(|(name, _)| name == column)
+                        .map_or("", |(_, value)| value.as_str())
+                });
+                write_csv_row(&mut self.out, row)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn write_stats_pretty_and_compact() -> Result<()> {
+        let stats = wasm_stats::get_stats(&wat::parse_str("(module (func) (func))")?)?;
+
+        let mut compact = Vec::new();
+        write_stats(&mut compact, &stats, false)?;
+        let mut pretty = Vec::new();
+        write_stats(&mut pretty, &stats, true)?;
+
+        assert_eq!(compact.iter().filter(|&&b| b == b'\n').count(), 1);
+        assert!(pretty.iter().filter(|&&b| b == b'\n').count() > 1);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&compact)?,
+            serde_json::from_slice::<Value>(&pretty)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn csv_flattens_and_escapes() -> Result<()> {
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::new(&mut out, OutputFormat::Csv);
+        writer.write(&json!({
+            "file": "a,b.wasm",
+            "size": { "code": 1, "total": 2 },
+            "custom_sections": ["name", "producers"],
+        }))?;
+        writer.write(&json!({
+            "file": "say \"hi\".wasm",
+            "size": { "code": 3 },
+            "custom_sections": [],
+        }))?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "file,size.code,size.total,custom_sections\n\
+             \"a,b.wasm\",1,2,name;producers\n\
+             \"say \"\"hi\"\".wasm\",3,,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn flatten_keys_counts_arrays_and_maps() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "import_modules": {
+                    "type": "object",
+                    "additionalProperties": { "type": "integer" },
+                },
+            },
+        });
+        let record = json!({
+            "file": "a.wasm",
+            "size": { "code": 1 },
+            "producers": { "processed-by": [{ "name": "rustc" }] },
+            "import_modules": { "env": 2, "wbg": 1 },
+            "names": null,
+        });
+        assert_eq!(
+            flatten_keys(&record, &schema, FlatArrays::Count),
+            json!({
+                "file": "a.wasm",
+                "size_code": 1,
+                "producers_processed_by": 1,
+                "import_modules": 2,
+                "names": null,
+            })
+        );
+        assert_eq!(
+            flatten_keys(&record, &schema, FlatArrays::Json)["producers_processed_by"],
+            r#"[{"name":"rustc"}]"#
+        );
+    }
+
+    #[test]
+    fn csv_joins_maps() -> Result<()> {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "import_modules": {
+                    "type": "object",
+                    "additionalProperties": { "type": "integer" },
+                },
+            },
+        });
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::new(&mut out, OutputFormat::Csv).schema(schema);
+        writer.write(&json!({ "file": "a.wasm", "import_modules": { "env": 2, "wbg": 1 } }))?;
+        writer.write(&json!({ "file": "b.wasm", "import_modules": { "a": 5 } }))?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "file,import_modules\na.wasm,env=2;wbg=1\nb.wasm,a=5\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! A progress bar on stderr while a batch runs, and the counts it ends with
+//! for the summary line and `--summary-json`.
+use crate::errors::Skipped;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{io::Write, path::Path, time::Instant};
+
+const BAR_WIDTH: usize = 30;
+
+/// What happened to the inputs of a run. Skipped inputs aren't counted as
+/// `failed`, so `processed` is the sum of the other three.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub processed: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub wall_time_ms: u64,
+}
+
+impl Summary {
+    /// Inputs that didn't produce a record, whether they failed or were
+    /// skipped.
+    pub fn unsuccessful(&self) -> usize {
+        self.failed + self.skipped
+    }
+
+    /// Writes the summary as JSON for `--summary-json`.
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write summary to {}", path.display()))
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Processed {} inputs in {:.1}s: {} succeeded, {} failed, {} skipped",
+            self.processed,
+            self.wall_time_ms as f64 / 1000.0,
+            self.succeeded,
+            self.failed,
+            self.skipped
+        )
+    }
+}
+
+/// Counts inputs as they're done, redrawing a single line on stderr if
+/// `shown`.
+pub struct Progress {
+    total: usize,
+    shown: bool,
+    start: Instant,
+    summary: Summary,
+}
+
+impl Progress {
+    pub fn new(total: usize, shown: bool) -> Self {
+        Progress {
+            total,
+            shown,
+            start: Instant::now(),
+            summary: Summary::default(),
+        }
+    }
+
+    pub fn add<T>(&mut self, result: &Result<T>) {
+        self.summary.processed += 1;
+        match result {
+            Ok(_) => self.summary.succeeded += 1,
+            Err(e) if e.downcast_ref::<Skipped>().is_some() => self.summary.skipped += 1,
+            Err(_) => self.summary.failed += 1,
+        }
+    }
+
+    /// Redraws the bar, e.g.
+    /// `[=========>          ] 310/1000 inputs, 2 failed, 3 skipped, 52.1/s`.
+    pub fn draw(&self, err: &mut impl Write) -> std::io::Result<()> {
+        if !self.shown {
+            return Ok(());
+        }
+        let processed = self.summary.processed;
+        let filled = BAR_WIDTH * processed / self.total.max(1);
+        let bar = match filled {
+            0 => " ".repeat(BAR_WIDTH),
+            n if n >= BAR_WIDTH => "=".repeat(BAR_WIDTH),
+            n => format!("{}>{}", "=".repeat(n - 1), " ".repeat(BAR_WIDTH - n)),
+        };
+        let secs = self.start.elapsed().as_secs_f64();
+        let rate = if secs > 0.0 {
+            processed as f64 / secs
+        } else {
+            0.0
+        };
+        write!(
+            err,
+            "\r\x1b[K[{}] {}/{} inputs, {} failed, {} skipped, {:.1}/s",
+            bar, processed, self.total, self.summary.failed, self.summary.skipped, rate
+        )?;
+        err.flush()
+    }
+
+    /// Clears the bar, so that other messages get a line of their own.
+    pub fn clear(&self, err: &mut impl Write) -> std::io::Result<()> {
+        if self.shown {
+            write!(err, "\r\x1b[K")?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Summary {
+        self.summary.wall_time_ms = self.start.elapsed().as_millis() as u64;
+        self.summary
+    }
+}
@@ -0,0 +1,257 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use crate::HeaderError;
+use std::{collections::HashSet, convert::TryFrom};
+use wasmbin::{
+    io::{Decode, DecodeError},
+    sections::{Kind, Section},
+    visit::Visit,
+};
+use wasmparser::{BinaryReader, CustomSectionReader};
+
+/// Size of the magic number and version that precede the sections.
+pub const HEADER_SIZE: usize = 8;
+
+/// The little-endian version word from a header with a valid magic number.
+pub fn version(wasm: &[u8]) -> Result<u32, HeaderError> {
+    let header = wasm
+        .get(..HEADER_SIZE)
+        .ok_or(HeaderError::Truncated(wasm.len()))?;
+    if header[..4] != *b"\0asm" {
+        return Err(HeaderError::InvalidMagic);
+    }
+    Ok(u32::from_le_bytes([
+        header[4], header[5], header[6], header[7],
+    ]))
+}
+
+/// Id of the exception handling proposal's tag section.
+pub const TAG: u8 = 13;
+
+/// The known non-custom sections in the order the spec requires them in,
+/// which isn't the order of their ids, with the names used in
+/// `section_order`.
+const ORDER: &[(u8, &str)] = &[
+    (1, "type"),
+    (2, "import"),
+    (3, "function"),
+    (4, "table"),
+    (5, "memory"),
+    (TAG, "tag"),
+    (6, "global"),
+    (7, "export"),
+    (8, "start"),
+    (9, "element"),
+    (12, "data_count"),
+    (10, "code"),
+    (11, "data"),
+];
+
+/// A section found in the module, along with where it came from.
+pub struct RawSection<'a> {
+    pub id: u8,
+    /// Offset of the section id byte within the module.
+    pub offset: usize,
+    /// Offset of the section contents, after the id and length.
+    pub payload_offset: usize,
+    pub payload: &'a [u8],
+    /// The whole section, including the id and length.
+    bytes: &'a [u8],
+}
+
+impl<'a> RawSection<'a> {
+    /// Size of the section as measured in [`SizeStats`](crate::SizeStats),
+    /// which includes the length but not the id.
+    pub fn size(&self) -> usize {
+        self.bytes.len() - 1
+    }
+
+    /// A wasmparser reader over the section contents.
+    pub fn reader(&self) -> BinaryReader<'a> {
+        BinaryReader::new(self.payload, self.payload_offset as u64)
+    }
+
+    /// Decodes the section, leaving its contents to be decoded lazily.
+    pub fn decode(&self) -> Result<Section, DecodeError> {
+        Section::decode(&mut &self.bytes[..])
+    }
+
+    /// Measures the section the slow way, by decoding it with wasmbin and
+    /// encoding it again, as sizes were measured before they were read off
+    /// the raw bytes. Only kept to check that [`size`](Self::size) agrees.
+    #[cfg(test)]
+    pub fn encoded_size(&self) -> Result<usize, DecodeError> {
+        use wasmbin::io::Encode;
+
+        // Decoding the contents in place drops the raw bytes, so that they
+        // really are encoded again rather than copied. Custom sections are
+        // left alone, as in `check_wasmbin`.
+        let mut section = self.decode()?;
+        if !matches!(section, Section::Custom(_)) {
+            section.visit_mut(|_: &mut ()| {})?;
+        }
+        let mut size = written_size::WrittenSize::new();
+        section
+            .encode(&mut size)
+            .expect("counting bytes doesn't fail");
+        // Less the id.
+        Ok(size.size() as usize - 1)
+    }
+
+    /// Checks that wasmbin can decode the whole section, function bodies
+    /// included. Custom sections only need a name, since a malformed `name`
+    /// or `producers` section doesn't stop them from being counted.
+    pub fn check_wasmbin(&self) -> Result<(), DecodeError> {
+        match self.decode()? {
+            Section::Custom(section) => section.try_contents().map(drop),
+            section => Ok(section.visit(|_: &()| {})?),
+        }
+    }
+
+    /// The section's kind as listed in `section_order`: `custom:` followed by
+    /// the name for custom sections, and `unknown:` followed by the id for
+    /// ids that aren't in [`ORDER`].
+    pub fn label(&self) -> String {
+        if self.id == 0 {
+            return match CustomSectionReader::new(self.reader()) {
+                Ok(section) => format!("custom:{}", section.name()),
+                Err(_) => "custom".to_owned(),
+            };
+        }
+        match ORDER.iter().find(|&&(id, _)| id == self.id) {
+            Some((_, name)) => (*name).to_owned(),
+            None => format!("unknown:{}", self.id),
+        }
+    }
+
+    /// Prefixes an error with the kind and offset of this section.
+    pub fn error(&self, err: impl std::fmt::Display) -> String {
+        error(self.id, self.offset, err)
+    }
+}
+
+/// Human-readable name of a section kind, for error messages.
+fn kind_name(id: u8) -> String {
+    match Kind::try_from(id) {
+        Ok(kind) => format!("{:?}", kind),
+        Err(id) => format!("Unknown({})", id),
+    }
+}
+
+fn error(id: u8, offset: usize, err: impl std::fmt::Display) -> String {
+    error_with(kind_name, id, offset, err)
+}
+
+/// Like [`RawSection::error`], naming the section with `kind_name`, for
+/// binaries whose section ids mean something else, like components.
+pub fn error_with(
+    kind_name: fn(u8) -> String,
+    id: u8,
+    offset: usize,
+    err: impl std::fmt::Display,
+) -> String {
+    format!("{} section at offset {}: {}", kind_name(id), offset, err)
+}
+
+/// Whether the known sections are in the order the spec requires and no
+/// non-custom section appears twice. Custom sections may go anywhere, and
+/// sections with unknown ids only count as repeats.
+pub fn canonical_order(sections: &[RawSection]) -> bool {
+    let mut seen = HashSet::new();
+    let mut last = 0;
+    for section in sections.iter().filter(|section| section.id != 0) {
+        if !seen.insert(section.id) {
+            return false;
+        }
+        if let Some(position) = ORDER.iter().position(|&(id, _)| id == section.id) {
+            if position < last {
+                return false;
+            }
+            last = position;
+        }
+    }
+    true
+}
+
+/// Reads an unsigned LEB128 number, advancing `pos` past it.
+pub fn read_u32(bytes: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut result = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        if shift == 28 && byte & 0x70 != 0 {
+            return None;
+        }
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Splits the module body into sections without decoding them, so that one
+/// bad section doesn't prevent the others from being looked at.
+///
+/// Stops at the first section whose header or length runs past the end of
+/// the input, returning a description of the problem alongside the sections
+/// found before it.
+pub fn split(wasm: &[u8]) -> (Vec<RawSection<'_>>, Option<String>) {
+    split_with(wasm, kind_name)
+}
+
+/// Like [`split`], naming the section that runs past the end with
+/// `kind_name`.
+pub fn split_with(
+    wasm: &[u8],
+    kind_name: fn(u8) -> String,
+) -> (Vec<RawSection<'_>>, Option<String>) {
+    let error = |id, offset, err| error_with(kind_name, id, offset, err);
+    let mut sections = Vec::new();
+    let mut pos = HEADER_SIZE;
+    while pos < wasm.len() {
+        let offset = pos;
+        let id = wasm[pos];
+        pos += 1;
+        let len = match read_u32(wasm, &mut pos) {
+            Some(len) => len as usize,
+            None => {
+                let reason = "invalid section length".to_owned();
+                return (sections, Some(error(id, offset, reason)));
+            }
+        };
+        let payload = match wasm.get(pos..pos.saturating_add(len)) {
+            Some(payload) => payload,
+            None => {
+                let reason = format!(
+                    "section length {} runs past the end of the input at {}",
+                    len,
+                    wasm.len()
+                );
+                return (sections, Some(error(id, offset, reason)));
+            }
+        };
+        sections.push(RawSection {
+            id,
+            offset,
+            payload_offset: pos,
+            payload,
+            bytes: &wasm[offset..pos + len],
+        });
+        pos += len;
+    }
+    (sections, None)
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Pruning records down to the fields chosen with `--fields`, e.g.
+//! `size,instr.proposals.simd`.
+use crate::bq;
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+
+/// Splits a dot-separated path into the records described by `schema`,
+/// failing with the fields that could have been meant if it doesn't name
+/// one.
+pub fn parse_path(path: &str, schema: &Value) -> Result<Vec<String>> {
+    let defs = &schema["$defs"];
+    let keys: Vec<_> = path.split('.').map(str::to_owned).collect();
+    let mut schema = schema;
+    for (i, key) in keys.iter().enumerate() {
+        let (resolved, _) = bq::resolve(schema, defs);
+        let properties = match resolved["properties"].as_object() {
+            Some(properties) => properties,
+            None => bail!("{} has no fields of its own", keys[..i].join(".")),
+        };
+        schema = match properties.get(key) {
+            Some(property) => property,
+            None => {
+                let fields: Vec<_> = properties
+                    .keys()
+                    .map(|field| keys[..i].iter().chain([field]).cloned())
+                    .map(|field| field.collect::<Vec<_>>().join("."))
+                    .collect();
+                bail!("unknown field, expected one of: {}", fields.join(", "))
+            }
+        };
+    }
+    Ok(keys)
+}
+
+/// The paths chosen with `--fields`, as returned by [`parse_path`].
+#[derive(Debug)]
+pub struct Selection<'a>(pub &'a [Vec<String>]);
+
+impl Selection<'_> {
+    /// Removes every field that wasn't selected, apart from `file`, which is
+    /// always kept so that records can still be joined back to the crawl.
+    pub fn apply(&self, mut record: Value) -> Value {
+        let file = ["file".to_owned()];
+        let mut paths: Vec<&[String]> = self.0.iter().map(Vec::as_slice).collect();
+        paths.push(&file);
+        if let Value::Object(map) = &mut record {
+            retain(map, &paths);
+        }
+        record
+    }
+}
+
+fn retain(map: &mut Map<String, Value>, paths: &[&[String]]) {
+    map.retain(|key, value| {
+        let rest: Vec<_> = paths
+            .iter()
+            .filter(|path| path[0] == *key)
+            .map(|path| &path[1..])
+            .collect();
+        if rest.is_empty() {
+            return false;
+        }
+        // Unless the whole field was selected, only keep the parts that were.
+        if let (false, Value::Object(map)) = (rest.iter().any(|path| path.is_empty()), value) {
+            retain(map, &rest);
+        }
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(paths: &[&str], record: Value) -> Result<Value> {
+        let schema = wasm_stats::schema();
+        let paths = paths
+            .iter()
+            .map(|path| parse_path(path, &schema))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Selection(&paths).apply(record))
+    }
+
+    fn record() -> Result<Value> {
+        let wasm = wat::parse_str("(module (func v128.const i64x2 0 0 drop))")?;
+        let mut record = serde_json::to_value(wasm_stats::get_stats(&wasm)?)?;
+        record["file"] = json!("a.wasm");
+        Ok(record)
+    }
+
+    #[test]
+    fn nested_path() -> Result<()> {
+        assert_eq!(
+            select(&["instr.proposals.simd"], record()?)?,
+            json!({ "file": "a.wasm", "instr": { "proposals": { "simd": 1 } } })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn several_paths() -> Result<()> {
+        let record = record()?;
+        let selected = select(
+            &["language", "instr.proposals", "size", "instr.total"],
+            record.clone(),
+        )?;
+        // Fields stay in the order of the record, not the selection.
+        assert_eq!(
+            selected,
+            json!({
+                "file": "a.wasm",
+                "language": "Unknown",
+                "instr": {
+                    "total": record["instr"]["total"],
+                    "proposals": record["instr"]["proposals"],
+                },
+                "size": record["size"],
+            })
+        );
+        let keys: Vec<_> = selected.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["language", "instr", "size", "file"]);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let err = select(&["instr.bogus"], json!({})).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with("unknown field, expected one of: instr.total,"),
+            "{}",
+            message
+        );
+        assert!(message.contains(" instr.proposals,"), "{}", message);
+
+        let err = select(&["size.total.bytes"], json!({})).unwrap_err();
+        assert_eq!(err.to_string(), "size.total has no fields of its own");
+    }
+}
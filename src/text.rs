@@ -0,0 +1,76 @@
+/*
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! Modules in the text format, which are assembled before being analysed
+//! like any other, for trying out the heuristics on hand-written modules.
+use crate::inputs::Bytes;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::Path;
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Whether a record's module was read as a binary or assembled from text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    Binary,
+    Wat,
+}
+
+/// Whether the input should be read as text: it isn't a binary module, and
+/// either has a `.wat` or `.wast` extension or starts like one, with a
+/// comment or an opening parenthesis.
+fn is_text(name: &str, input: &[u8]) -> bool {
+    if input.starts_with(WASM_MAGIC) {
+        return false;
+    }
+    if name.ends_with(".wat") || name.ends_with(".wast") {
+        return true;
+    }
+    let start = input.trim_ascii_start();
+    start.starts_with(b"(") || start.starts_with(b";;")
+}
+
+/// Assembles the input if it's in the text format, leaving binaries as they
+/// are. Errors point at the line and column of the problem.
+pub fn assemble(name: &str, input: Bytes) -> Result<(Bytes, InputFormat), wat::Error> {
+    if !is_text(name, &input) {
+        return Ok((input, InputFormat::Binary));
+    }
+    let wasm = wat::Parser::new().parse_bytes(Some(Path::new(name)), &input)?;
+    Ok((wasm.into_owned().into(), InputFormat::Wat))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detection() {
+        assert!(is_text("module.wat", b"module"));
+        assert!(is_text("-", b"\n  (module)"));
+        assert!(is_text("-", b";; a comment\n(module)"));
+        assert!(!is_text("module.wat", b"\0asm\x01\0\0\0"));
+        assert!(!is_text("module.wasm", b"\x1f\x8b"));
+    }
+
+    #[test]
+    fn parse_error_position() {
+        let err = assemble("bad.wat", b"(module\n  (func (bogus)))".to_vec().into()).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("bad.wat:2:"), "{}", message);
+    }
+}
@@ -0,0 +1,196 @@
+/**
+ * Copyright 2021 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use anyhow::Result;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+fn wasm_stats() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_wasm-stats"))
+}
+
+#[test]
+fn directory_input() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(dir.path().join("nested/deeper"))?;
+    std::fs::write(
+        dir.path().join("first.wasm"),
+        wat::parse_str("(module (func))")?,
+    )?;
+    std::fs::write(
+        dir.path().join("nested/deeper/second.wasm"),
+        wat::parse_str("(module (func) (func))")?,
+    )?;
+    std::fs::write(dir.path().join("nested/notes.txt"), "not a module")?;
+
+    let output = wasm_stats().arg(dir.path()).output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let files: Vec<String> = stdout
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["file"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(files, ["first.wasm", "nested/deeper/second.wasm"]);
+
+    // Only the text file is picked up now, and it fails to decode.
+    let output = wasm_stats()
+        .args(["--ext", "txt"])
+        .arg(dir.path())
+        .output()?;
+    assert_eq!(String::from_utf8(output.stdout)?.lines().count(), 0);
+    assert!(String::from_utf8(output.stderr)?.contains("notes.txt"));
+    Ok(())
+}
+
+#[test]
+fn stdin_input() -> Result<()> {
+    for args in [&[][..], &["-"][..]] {
+        let mut child = wasm_stats()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(&wat::parse_str("(module (func) (func) (func))")?)?;
+        let output = child.wait_with_output()?;
+        assert!(output.status.success());
+        let record: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+        assert_eq!(record["funcs"], 3);
+    }
+    Ok(())
+}
+
+#[test]
+fn empty_stdin() -> Result<()> {
+    let output = wasm_stats().stdin(Stdio::null()).output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains("No wasm module was provided on stdin"));
+    Ok(())
+}
+
+/// Runs the tool over a small module on stdin with the given flags.
+fn basic_output(args: &[&str]) -> Result<String> {
+    let mut child = wasm_stats()
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(&wat::parse_str(
+        r#"
+        (module
+            (import "env" "f" (func))
+            (func (export "g") call 0)
+        )
+        "#,
+    )?)?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[test]
+fn csv_output() -> Result<()> {
+    assert_eq!(
+        basic_output(&["--output-format", "csv"])?,
+        include_str!("golden/basic.csv")
+    );
+    Ok(())
+}
+
+#[test]
+fn bq_output() -> Result<()> {
+    assert_eq!(
+        basic_output(&["--bq"])?,
+        include_str!("golden/basic_bq.ndjson")
+    );
+    let output = wasm_stats().args(["schema", "--bq"]).output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        include_str!("golden/bq_schema.json")
+    );
+    Ok(())
+}
+
+/// Pins the keys of flattened records, so that renaming or moving a field
+/// shows up here as well as in the schema.
+#[test]
+fn flatten_output() -> Result<()> {
+    assert_eq!(
+        basic_output(&["--flatten"])?,
+        include_str!("golden/basic_flat.ndjson")
+    );
+    Ok(())
+}
+
+/// Keeps the example record in the README in step with the output, so that
+/// it doesn't need updating by hand.
+#[test]
+fn readme_example() -> Result<()> {
+    const COMMAND: &str = "$ cargo run --release -- tests/fixtures/hello.wat\n";
+    let readme = include_str!("../README.md");
+    let start = readme.find(COMMAND).unwrap() + COMMAND.len();
+    let example = &readme[start..][..=readme[start..].find('\n').unwrap()];
+
+    let output = wasm_stats().arg("tests/fixtures/hello.wat").output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, example);
+    Ok(())
+}
+
+#[test]
+fn output_file() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let module = dir.path().join("module.wasm");
+    std::fs::write(&module, wat::parse_str("(module (func))")?)?;
+    let out = dir.path().join("out.ndjson");
+    std::fs::write(&out, "stale\n")?;
+
+    let status = wasm_stats().arg("-o").arg(&out).arg(&module).status()?;
+    assert!(status.success());
+    let contents = std::fs::read_to_string(&out)?;
+    assert_eq!(contents.lines().count(), 1);
+    let record: serde_json::Value = serde_json::from_str(&contents)?;
+    assert_eq!(record["funcs"], 1);
+
+    let status = wasm_stats()
+        .arg("--output")
+        .arg(&out)
+        .arg("--append")
+        .arg(&module)
+        .status()?;
+    assert!(status.success());
+    assert_eq!(std::fs::read_to_string(&out)?.lines().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn output_file_error() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join("missing-dir/out.ndjson");
+    let output = wasm_stats().arg("-o").arg(&out).arg("-").output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains(&out.display().to_string()));
+    Ok(())
+}
@@ -0,0 +1,21 @@
+;; A hand-written equivalent of a C hello world built with the WASI SDK,
+;; used for the example record in the README.
+(module
+  (type $write (func (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (type $write)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 2)
+  (global $sp (mut i32) (i32.const 66576))
+  (global (export "__heap_base") i32 (i32.const 66576))
+  (global (export "__data_end") i32 (i32.const 1036))
+  (data (i32.const 1024) "Hello, world\n")
+  (func $__original_main (result i32)
+    (local $iov i32)
+    (local.set $iov (i32.sub (global.get $sp) (i32.const 16)))
+    (i32.store (local.get $iov) (i32.const 1024))
+    (i32.store offset=4 (local.get $iov) (i32.const 13))
+    (drop (call $fd_write (i32.const 1) (local.get $iov) (i32.const 1) (i32.add (local.get $iov) (i32.const 8))))
+    (i32.const 0))
+  (func $_start (export "_start")
+    (call $proc_exit (call $__original_main)))
+)